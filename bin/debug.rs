@@ -0,0 +1,230 @@
+//! Run a script under the debugger.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use compiler::ModuleBuilder;
+use diagnostic::{DiagnosticCoordinator, InputCoordinator};
+use runtime::{Debugger, Pause, Resume, VirtualMachine};
+
+use crate::{script::read_file, Args};
+
+/// Run a file as a script, pausing at the first line to debug it.
+#[derive(clap::Parser)]
+pub struct Debug {
+    filename: PathBuf,
+
+    /// Lines to set breakpoints on before starting
+    #[clap(short, long)]
+    breakpoint: Vec<u32>,
+}
+
+impl Debug {
+    /// Run the file `filename` with a [`Console`] debugger attached.
+    pub(crate) fn run(&self, args: &Args) {
+        let mut inputs = InputCoordinator::default();
+        let mut diagnostics = DiagnosticCoordinator::default();
+
+        let (id, input) = match read_file(&self.filename, &mut inputs) {
+            Ok(ok) => ok,
+            Err(d) => {
+                diagnostics.register(d);
+                diagnostics.emit(&inputs);
+                return;
+            }
+        };
+
+        let main = match ModuleBuilder::default().input(&input) {
+            Ok(builder) => builder.with_id(Some(id)).build(),
+
+            Err(d) => {
+                diagnostics.register(d.input(id));
+                diagnostics.emit(&inputs);
+                return;
+            }
+        };
+
+        if args.dump {
+            println!("{}", main);
+            return;
+        }
+
+        let mut runtime = VirtualMachine::default();
+
+        for line in &self.breakpoint {
+            runtime.set_breakpoint(*line);
+        }
+
+        let console = Console {
+            lines: input.lines().map(String::from).collect(),
+            frame: 0,
+            detached: false,
+        };
+
+        runtime.set_debugger(console, Resume::StepInto);
+
+        match runtime.load(main) {
+            Ok(()) => println!("{}", runtime.last_result()),
+            Err(e) => {
+                runtime.stack_trace(e, &mut diagnostics);
+                diagnostics.emit(&inputs);
+            }
+        }
+    }
+}
+
+/// A debugger which reads commands from stdin.
+struct Console {
+    /// The lines of the script, to show where we are.
+    lines: Vec<String>,
+
+    /// The frame that commands like `locals` and `print` look at.
+    frame: usize,
+
+    /// Once the input ends, we just let the script finish.
+    detached: bool,
+}
+
+impl Console {
+    const PROMPT: &'static str = "(debug) ";
+
+    const HELP: &'static str = "\
+commands:
+  c, continue      run until the next breakpoint
+  s, step          step to the next line, into any calls
+  n, next          step to the next line in this function
+  o, out           step out of this function
+  b, break LINE    set a breakpoint
+  d, delete LINE   remove a breakpoint
+  bt, backtrace    show the call stack
+  f, frame N       look at frame N of the backtrace
+  l, locals        show the locals in the frame
+  p, print EXPR    evaluate an expression in the frame
+  q, quit          stop running";
+
+    fn show_line(&self, line: u32) {
+        let text = self
+            .lines
+            .get(line.saturating_sub(1) as usize)
+            .map(String::as_str)
+            .unwrap_or_default();
+
+        println!("{line:>4} | {text}");
+    }
+
+    fn backtrace(&self, vm: &VirtualMachine) {
+        for frame in 0..vm.frame_count() {
+            let marker = if frame == self.frame { '>' } else { ' ' };
+
+            let name = vm
+                .frame_closure(frame)
+                .map(|closure| format!("{:?}", closure.name()))
+                .unwrap_or_default();
+
+            match vm.frame_span(frame) {
+                Some(span) => println!("{marker} #{frame} {name} at {span}"),
+                None => println!("{marker} #{frame} {name}"),
+            }
+        }
+    }
+
+    /// Run a command, returning how to resume if the command does that.
+    fn command(
+        &mut self,
+        vm: &mut VirtualMachine,
+        line: &str,
+    ) -> Option<Resume> {
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command {
+            "c" | "continue" => return Some(Resume::Continue),
+            "s" | "step" => return Some(Resume::StepInto),
+            "n" | "next" => return Some(Resume::StepOver),
+            "o" | "out" => return Some(Resume::StepOut),
+
+            "b" | "break" => match rest.parse() {
+                Ok(line) => vm.set_breakpoint(line),
+                Err(_) => println!("expected a line number"),
+            },
+
+            "d" | "delete" => match rest.parse() {
+                Ok(line) if vm.clear_breakpoint(line) => {}
+                Ok(line) => println!("no breakpoint on line {line}"),
+                Err(_) => println!("expected a line number"),
+            },
+
+            "bt" | "backtrace" => self.backtrace(vm),
+
+            "f" | "frame" => match rest.parse() {
+                Ok(frame) if frame < vm.frame_count() => {
+                    self.frame = frame;
+                    self.backtrace(vm);
+                }
+                _ => println!("expected a frame number from the backtrace"),
+            },
+
+            "l" | "locals" => {
+                for (name, value) in vm.frame_locals(self.frame) {
+                    println!("{name} = {value:?}");
+                }
+            }
+
+            "p" | "print" => match vm.evaluate(self.frame, rest) {
+                Ok(value) => println!("{value:?}"),
+                Err(e) => println!("error: {e}"),
+            },
+
+            "q" | "quit" => std::process::exit(0),
+
+            "" => {}
+
+            _ => println!("{}", Console::HELP),
+        }
+
+        None
+    }
+}
+
+impl Debugger for Console {
+    fn paused(&mut self, vm: &mut VirtualMachine, pause: Pause) -> Resume {
+        if self.detached {
+            return Resume::Continue;
+        }
+
+        self.frame = 0;
+
+        if let Pause::Breakpoint(line) = pause {
+            println!("breakpoint on line {line}");
+        }
+
+        if let Some(span) = vm.frame_span(0) {
+            self.show_line(span.start().line() + 1);
+        }
+
+        let stdin = io::stdin();
+        let mut line = String::new();
+
+        loop {
+            print!("{}", Console::PROMPT);
+            // If the prompt doesn't show up, there's not much we can do.
+            let _ = io::stdout().flush();
+
+            line.clear();
+
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    self.detached = true;
+                    return Resume::Continue;
+                }
+                Ok(_) => {}
+            }
+
+            if let Some(resume) = self.command(vm, line.trim()) {
+                return resume;
+            }
+        }
+    }
+}
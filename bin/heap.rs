@@ -0,0 +1,30 @@
+//! Look at a heap snapshot written by `script --heap-snapshot`.
+
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use runtime::HeapSnapshot;
+
+use crate::Args;
+
+/// Report what's keeping memory alive in a heap snapshot
+#[derive(clap::Parser)]
+pub struct Heap {
+    /// The snapshot to read
+    filename: PathBuf,
+}
+
+impl Heap {
+    /// Read the snapshot and print the dominators with the most retained size.
+    pub(crate) fn run(&self, _args: &Args) {
+        let snapshot = File::open(&self.filename)
+            .and_then(|file| HeapSnapshot::read_json(BufReader::new(file)));
+
+        match snapshot {
+            Ok(snapshot) => print!("{}", snapshot.analyze()),
+            Err(e) => eprintln!(
+                "cannot read heap snapshot {}, {e}",
+                self.filename.display()
+            ),
+        }
+    }
+}
@@ -2,10 +2,16 @@
 
 use clap::{Parser, Subcommand};
 
+mod debug;
 mod eval;
+mod heap;
+mod repl;
 mod script;
 
+use debug::Debug;
 use eval::Evaluate;
+use heap::Heap;
+use repl::Repl;
 use script::Script;
 
 #[derive(clap::Parser)]
@@ -24,6 +30,9 @@ struct Args {
 enum Command {
     Script(Script),
     Eval(Evaluate),
+    Repl(Repl),
+    Debug(Debug),
+    Heap(Heap),
 }
 
 fn main() {
@@ -32,6 +41,9 @@ fn main() {
     match &args.command {
         Some(Command::Script(script)) => script.run(&args),
         Some(Command::Eval(eval)) => eval.run(&args),
+        Some(Command::Repl(repl)) => repl.run(&args),
+        Some(Command::Debug(debug)) => debug.run(&args),
+        Some(Command::Heap(heap)) => heap.run(&args),
 
         None => unreachable!("arg parser should print help"),
     }
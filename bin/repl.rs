@@ -0,0 +1,139 @@
+//! An interactive session, which keeps its state between inputs.
+
+use std::io::{self, BufRead, Write};
+
+use compiler::ModuleBuilder;
+use diagnostic::{Diagnostic, DiagnosticCoordinator, InputCoordinator};
+use parser::lexer::{Error as LexerError, Lexer, TokenKind};
+use runtime::VirtualMachine;
+
+use crate::Args;
+
+/// Start an interactive session
+#[derive(clap::Parser)]
+pub struct Repl {}
+
+impl Repl {
+    const PROMPT: &'static str = "> ";
+    const CONTINUE_PROMPT: &'static str = "| ";
+
+    /// Run the subcommand, reading and evaluating input until it ends.
+    pub(crate) fn run(&self, args: &Args) {
+        let mut session = Session::default();
+        let mut buffer = String::new();
+
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+
+        loop {
+            if buffer.is_empty() {
+                prompt(Repl::PROMPT);
+            } else {
+                prompt(Repl::CONTINUE_PROMPT);
+            }
+
+            match stdin.read_line(&mut buffer) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    session.report(Diagnostic::new(format!("{e}")));
+                    break;
+                }
+            }
+
+            if is_incomplete(&buffer) {
+                continue;
+            }
+
+            let input = std::mem::take(&mut buffer);
+
+            if !input.trim().is_empty() {
+                session.eval(input, args);
+            }
+        }
+
+        // Leave the terminal's prompt on a new line after an end of input.
+        println!();
+    }
+}
+
+/// Everything that's kept alive between inputs.
+#[derive(Default)]
+struct Session {
+    inputs: InputCoordinator,
+    diagnostics: DiagnosticCoordinator,
+    builder: ModuleBuilder,
+    runtime: VirtualMachine,
+}
+
+impl Session {
+    /// Compile and run some input on the end of what's been run already,
+    /// printing the result.
+    ///
+    /// If anything goes wrong, the error is reported and the session is put
+    /// back to how it was before this input.
+    fn eval(&mut self, input: String, args: &Args) {
+        let id = self.inputs.eval_input(input.clone());
+        let backup = self.builder.clone();
+
+        if let Err(d) = self.builder.push_input(&input) {
+            self.report(d.input(id));
+            return;
+        }
+
+        let module = self.builder.build();
+
+        if args.dump {
+            println!("{}", module);
+            return;
+        }
+
+        match self.runtime.extend(module) {
+            Ok(()) => println!("{}", self.runtime.last_result()),
+            Err(e) => {
+                self.runtime.stack_trace(e, &mut self.diagnostics);
+                self.emit();
+
+                self.runtime.rewind();
+                self.builder = backup;
+            }
+        }
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.register(diagnostic);
+        self.emit();
+    }
+
+    fn emit(&mut self) {
+        self.diagnostics.emit(&self.inputs);
+        self.diagnostics.clear();
+    }
+}
+
+fn prompt(prompt: &str) {
+    print!("{prompt}");
+    // If the prompt doesn't show up, there's not much we can do about it.
+    let _ = io::stdout().flush();
+}
+
+/// Input is incomplete if it has unclosed delimiters, or ends part way through
+/// a token like a string.
+///
+/// Anything else wrong with the input is left for the parser to report.
+fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new(input);
+    let mut depth = 0;
+
+    while !lexer.is_empty() {
+        match lexer.token().map(|t| t.kind()) {
+            Ok(TokenKind::Open(_)) => depth += 1,
+            Ok(TokenKind::Close(_)) => depth -= 1,
+            Ok(_) => {}
+            Err(LexerError::UnexpectedEOF(_)) => return true,
+            Err(_) => return false,
+        }
+    }
+
+    depth > 0
+}
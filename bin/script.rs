@@ -1,12 +1,16 @@
 //! Run some input as a script.
 
-use std::{fs::File, io::Read, path::PathBuf};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use compiler::ModuleBuilder;
 use diagnostic::{
-    verify_utf8, Diagnostic, DiagnosticCoordinator, InputCoordinator,
+    verify_utf8, Diagnostic, DiagnosticCoordinator, InputCoordinator, InputId,
 };
-use runtime::VirtualMachine;
+use runtime::{Profile, VirtualMachine};
 
 use crate::Args;
 
@@ -14,6 +18,19 @@ use crate::Args;
 #[derive(clap::Parser)]
 pub struct Script {
     filename: PathBuf,
+
+    /// Report where time was spent, or with `--profile=FOLDED` write folded
+    /// stacks for a flamegraph to that file instead
+    #[clap(long, value_name = "FOLDED", require_equals = true)]
+    profile: Option<Option<PathBuf>>,
+
+    /// Report what the garbage collector did
+    #[clap(long)]
+    gc_stats: bool,
+
+    /// Write a snapshot of the heap to this file when the script ends
+    #[clap(long, value_name = "FILE")]
+    heap_snapshot: Option<PathBuf>,
 }
 
 impl Script {
@@ -22,19 +39,8 @@ impl Script {
         let mut inputs = InputCoordinator::default();
         let mut diagnostics = DiagnosticCoordinator::default();
 
-        let mut bytes = Vec::new();
-
-        if let Err(e) = File::open(&self.filename)
-            .and_then(|mut file| file.read_to_end(&mut bytes))
-        {
-            let d = Diagnostic::new(format!("{e}"));
-            diagnostics.register(d);
-            diagnostics.emit(&inputs);
-            return;
-        }
-
-        let input = match verify_utf8(&bytes) {
-            Ok(s) => s,
+        let (id, input) = match read_file(&self.filename, &mut inputs) {
+            Ok(ok) => ok,
             Err(d) => {
                 diagnostics.register(d);
                 diagnostics.emit(&inputs);
@@ -42,9 +48,7 @@ impl Script {
             }
         };
 
-        let id = inputs.file_input(input.into(), self.filename.clone());
-
-        let main = match ModuleBuilder::default().input(input) {
+        let main = match ModuleBuilder::default().input(&input) {
             Ok(builder) => builder.with_id(Some(id)).build(),
 
             Err(d) => {
@@ -61,9 +65,73 @@ impl Script {
 
         let mut runtime = VirtualMachine::default();
 
+        if self.profile.is_some() {
+            runtime.start_profiling();
+        }
+
         if let Err(e) = runtime.load(main) {
             runtime.stack_trace(e, &mut diagnostics);
             diagnostics.emit(&inputs);
         }
+
+        if let Some(profile) = runtime.stop_profiling() {
+            self.report(&profile, &mut diagnostics);
+            diagnostics.emit(&inputs);
+        }
+
+        if self.gc_stats {
+            eprint!("{}", runtime.gc_stats());
+        }
+
+        if let Some(path) = &self.heap_snapshot {
+            if let Err(e) = File::create(path)
+                .and_then(|file| runtime.heap_snapshot().write_json(file))
+            {
+                diagnostics.register(Diagnostic::new(format!(
+                    "cannot write heap snapshot to {}, {e}",
+                    path.display()
+                )));
+                diagnostics.emit(&inputs);
+            }
+        }
     }
+
+    /// Print the profile, or write it to the folded stacks file.
+    fn report(
+        &self,
+        profile: &Profile,
+        diagnostics: &mut DiagnosticCoordinator,
+    ) {
+        match &self.profile {
+            Some(Some(path)) => {
+                if let Err(e) = File::create(path)
+                    .and_then(|mut file| profile.write_folded(&mut file))
+                {
+                    diagnostics.register(Diagnostic::new(format!(
+                        "cannot write profile to {}, {e}",
+                        path.display()
+                    )));
+                }
+            }
+
+            _ => eprint!("{profile}"),
+        }
+    }
+}
+
+/// Read a file, registering it with `inputs`.
+pub(crate) fn read_file(
+    filename: &Path,
+    inputs: &mut InputCoordinator,
+) -> Result<(InputId, String), Diagnostic> {
+    let mut bytes = Vec::new();
+
+    File::open(filename)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| Diagnostic::new(format!("{e}")))?;
+
+    let input = verify_utf8(&bytes)?.to_owned();
+    let id = inputs.file_input(input.clone(), filename.to_owned());
+
+    Ok((id, input))
 }
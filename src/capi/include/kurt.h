@@ -0,0 +1,124 @@
+// The C interface to the Kurt interpreter.
+//
+// This file is generated from src/lib.rs by tests/header.rs, don't edit it by
+// hand.
+
+#ifndef KURT_H
+#define KURT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// A virtual machine.
+typedef struct KurtVm KurtVm;
+
+// A value, kept alive until it's freed.
+typedef struct KurtValue KurtValue;
+
+// How loading some source went.
+typedef enum KurtStatus {
+    KURT_STATUS_OK = 0,
+    KURT_STATUS_COMPILE_ERROR = 1,
+    KURT_STATUS_RUNTIME_ERROR = 2,
+} KurtStatus;
+
+// The basic types of value, for inspecting them.
+typedef enum KurtType {
+    KURT_TYPE_UNIT = 0,
+    KURT_TYPE_BOOL = 1,
+    KURT_TYPE_INT = 2,
+    KURT_TYPE_FLOAT = 3,
+    KURT_TYPE_STRING = 4,
+    // Anything else, like lists and closures.
+    KURT_TYPE_OTHER = 5,
+} KurtType;
+
+// A native function, called with the VM, the `data` it was made with, and
+// its arguments. The arguments are only valid until it returns.
+//
+// It returns a new value, which the VM takes ownership of, or `NULL` if it
+// failed.
+typedef KurtValue *(*KurtCallback)(KurtVm *vm, void *data, const KurtValue *const *args, size_t count);
+
+// Make a new virtual machine.
+KurtVm *kurt_vm_new(void);
+
+// Free a virtual machine.
+//
+// Any of its values which haven't been freed yet are useless afterwards.
+// Reading one fails as if it were the wrong type, so they should just be
+// freed with `kurt_value_free`.
+void kurt_vm_free(KurtVm *vm);
+
+// Compile the nul-terminated `source` and run it.
+//
+// If it fails and `error` isn't `NULL`, `*error` is set to a description of
+// what went wrong, which must be freed with `kurt_string_free`.
+KurtStatus kurt_vm_load(KurtVm *vm, const char *source, char **error);
+
+// The last result, printed as a string, which must be freed with
+// `kurt_string_free`.
+char *kurt_vm_last_result(const KurtVm *vm);
+
+// The last result, or `NULL` if there isn't one.
+KurtValue *kurt_vm_result(KurtVm *vm);
+
+// Call `f` with `count` arguments from `args`, returning the result or
+// `NULL` if it fails.
+//
+// If it fails and `error` isn't `NULL`, `*error` is set to a description of
+// what went wrong, which must be freed with `kurt_string_free`.
+KurtValue *kurt_vm_call(KurtVm *vm, const KurtValue *f, const KurtValue *const *args, size_t count, char **error);
+
+// Make a native function which calls `callback` with `data` and `arity`
+// arguments.
+KurtValue *kurt_vm_function(KurtVm *vm, uint32_t arity, KurtCallback callback, void *data);
+
+// Free a value.
+void kurt_value_free(KurtValue *value);
+
+// Free a string returned by this library.
+void kurt_string_free(char *s);
+
+// Make `()`.
+KurtValue *kurt_value_unit(KurtVm *vm);
+
+// Make a Bool.
+KurtValue *kurt_value_bool(KurtVm *vm, bool b);
+
+// Make an Int, or return `NULL` if `i` doesn't fit in 48 bits.
+KurtValue *kurt_value_int(KurtVm *vm, int64_t i);
+
+// Make a Float.
+KurtValue *kurt_value_float(KurtVm *vm, double f);
+
+// Make a String from a nul-terminated string, or return `NULL` if it isn't
+// UTF-8.
+KurtValue *kurt_value_string(KurtVm *vm, const char *s);
+
+// What type of value `value` is.
+KurtType kurt_value_type(const KurtValue *value);
+
+// Read a Bool into `*out`, returning whether `value` was one.
+bool kurt_value_as_bool(const KurtValue *value, bool *out);
+
+// Read an Int into `*out`, returning whether `value` was one.
+bool kurt_value_as_int(const KurtValue *value, int64_t *out);
+
+// Read a Float into `*out`, returning whether `value` was one.
+bool kurt_value_as_float(const KurtValue *value, double *out);
+
+// Copy a String, returning `NULL` if `value` isn't one. The copy must be
+// freed with `kurt_string_free`.
+char *kurt_value_as_string(const KurtValue *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
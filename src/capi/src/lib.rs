@@ -0,0 +1,565 @@
+//! A C interface for embedding the interpreter.
+//!
+//! Everything here is `extern "C"`, and `include/kurt.h` declares it for C
+//! and C++. The header is generated from this file, see `tests/header.rs`.
+//!
+//! A [`KurtVm`] is a whole [`VirtualMachine`]. Values are handed out as
+//! [`KurtValue`] pointers, each of which keeps its value alive until it's
+//! freed with [`kurt_value_free`]. Strings returned to C are owned by the
+//! caller, and need to be freed with [`kurt_string_free`].
+//!
+//! Nothing here is thread safe. A VM and its values should only be used from
+//! the thread which made them.
+//!
+//! A panic never unwinds into C. If one happens, the function returns `NULL`
+//! or an error status like it would for any other failure, and the VM should
+//! only be freed afterwards.
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use compiler::ModuleBuilder;
+use diagnostic::{verify_utf8, Diagnostic};
+use runtime::{
+    classes::HostObject, Error, FromValue, Global, IntoValue, Result, Value,
+    VirtualMachine,
+};
+
+/// A virtual machine.
+#[repr(transparent)]
+pub struct KurtVm {
+    vm: VirtualMachine,
+}
+
+/// A value, kept alive until it's freed.
+pub struct KurtValue {
+    global: Global,
+}
+
+/// How loading some source went.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KurtStatus {
+    Ok = 0,
+    CompileError = 1,
+    RuntimeError = 2,
+}
+
+/// The basic types of value, for inspecting them.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KurtType {
+    Unit = 0,
+    Bool = 1,
+    Int = 2,
+    Float = 3,
+    String = 4,
+
+    /// Anything else, like lists and closures.
+    Other = 5,
+}
+
+/// A native function, called with the VM, the `data` it was made with, and
+/// its arguments. The arguments are only valid until it returns.
+///
+/// It returns a new value, which the VM takes ownership of, or `NULL` if it
+/// failed.
+pub type KurtCallback = unsafe extern "C" fn(
+    vm: *mut KurtVm,
+    data: *mut c_void,
+    args: *const *const KurtValue,
+    count: usize,
+) -> *mut KurtValue;
+
+/// Run the body of an exported function, returning `failed()` instead if it
+/// panics, since unwinding into C aborts the whole process.
+fn guard<T>(body: impl FnOnce() -> T, failed: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_| failed())
+}
+
+/// What `*error` is set to when something panics.
+const PANICKED: &str = "internal error, the VM panicked";
+
+/// Turn a Rust string into one owned by C. Any nul characters are dropped,
+/// since C can't see past them.
+fn c_string(s: &str) -> *mut c_char {
+    let s = CString::new(s.replace('\0', "")).expect("nul characters removed");
+    s.into_raw()
+}
+
+/// Put `message` in `*error`, if there's somewhere to put it.
+unsafe fn set_error(error: *mut *mut c_char, message: &str) {
+    if !error.is_null() {
+        *error = c_string(message);
+    }
+}
+
+/// A compiler diagnostic on one line, like the plain text emitter shows it.
+fn render(d: &Diagnostic) -> String {
+    match d.get_location() {
+        Some(location) => {
+            format!("{}: {} - {}", d.get_level(), location, d.get_text())
+        }
+        None => format!("{}: {}", d.get_level(), d.get_text()),
+    }
+}
+
+fn new_value(vm: &KurtVm, value: Value) -> *mut KurtValue {
+    let global = vm.vm.global(value);
+    Box::into_raw(Box::new(KurtValue { global }))
+}
+
+/// Make a new value from a conversion which might fail.
+fn try_value(
+    vm: &mut KurtVm,
+    f: impl FnOnce(&mut VirtualMachine) -> Result<Value>,
+) -> *mut KurtValue {
+    match f(&mut vm.vm) {
+        Ok(value) => new_value(vm, value),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Make a new virtual machine.
+#[no_mangle]
+pub extern "C" fn kurt_vm_new() -> *mut KurtVm {
+    guard(
+        || {
+            Box::into_raw(Box::new(KurtVm {
+                vm: VirtualMachine::default(),
+            }))
+        },
+        ptr::null_mut,
+    )
+}
+
+/// Free a virtual machine.
+///
+/// Any of its values which haven't been freed yet are useless afterwards.
+/// Reading one fails as if it were the wrong type, so they should just be
+/// freed with [`kurt_value_free`].
+///
+/// # Safety
+///
+/// `vm` must have come from [`kurt_vm_new`], or be `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_vm_free(vm: *mut KurtVm) {
+    guard(
+        || {
+            if !vm.is_null() {
+                drop(Box::from_raw(vm));
+            }
+        },
+        || (),
+    )
+}
+
+/// Compile the nul-terminated `source` and run it.
+///
+/// If it fails and `error` isn't `NULL`, `*error` is set to a description of
+/// what went wrong, which must be freed with [`kurt_string_free`].
+///
+/// # Safety
+///
+/// `vm` must be a live VM and `source` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_vm_load(
+    vm: *mut KurtVm,
+    source: *const c_char,
+    error: *mut *mut c_char,
+) -> KurtStatus {
+    guard(
+        || {
+            let vm = &mut *vm;
+            let source = CStr::from_ptr(source);
+
+            let module = verify_utf8(source.to_bytes())
+                .and_then(|input| ModuleBuilder::default().input(input))
+                .map(|builder| builder.build());
+
+            let module = match module {
+                Ok(module) => module,
+                Err(d) => {
+                    set_error(error, &render(&d));
+                    return KurtStatus::CompileError;
+                }
+            };
+
+            match vm.vm.load(module) {
+                Ok(()) => KurtStatus::Ok,
+                Err(e) => {
+                    set_error(error, &e.to_string());
+                    KurtStatus::RuntimeError
+                }
+            }
+        },
+        || {
+            set_error(error, PANICKED);
+            KurtStatus::RuntimeError
+        },
+    )
+}
+
+/// The last result, printed as a string, which must be freed with
+/// [`kurt_string_free`].
+///
+/// # Safety
+///
+/// `vm` must be a live VM.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_vm_last_result(vm: *const KurtVm) -> *mut c_char {
+    guard(|| c_string(&(*vm).vm.last_result()), ptr::null_mut)
+}
+
+/// The last result, or `NULL` if there isn't one.
+///
+/// # Safety
+///
+/// `vm` must be a live VM.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_vm_result(vm: *mut KurtVm) -> *mut KurtValue {
+    guard(
+        || {
+            let vm = &*vm;
+
+            // Nothing has been loaded, so there's no frame to look in.
+            if vm.vm.frame_count() == 0 {
+                return ptr::null_mut();
+            }
+
+            match vm.vm.stack_frame().last() {
+                Some(value) => new_value(vm, *value),
+                None => ptr::null_mut(),
+            }
+        },
+        ptr::null_mut,
+    )
+}
+
+/// Call `f` with `count` arguments from `args`, returning the result or
+/// `NULL` if it fails.
+///
+/// If it fails and `error` isn't `NULL`, `*error` is set to a description of
+/// what went wrong, which must be freed with [`kurt_string_free`].
+///
+/// # Safety
+///
+/// `vm` must be a live VM, `f` a live value, and `args` an array of `count`
+/// live values.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_vm_call(
+    vm: *mut KurtVm,
+    f: *const KurtValue,
+    args: *const *const KurtValue,
+    count: usize,
+    error: *mut *mut c_char,
+) -> *mut KurtValue {
+    guard(
+        || {
+            let vm = &mut *vm;
+
+            let args: Vec<Value> = if count == 0 {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts(args, count)
+                    .iter()
+                    .map(|arg| (**arg).global.get())
+                    .collect()
+            };
+
+            match vm.vm.call((*f).global.get(), &args) {
+                Ok(value) => new_value(vm, value),
+                Err(e) => {
+                    set_error(error, &e.to_string());
+                    ptr::null_mut()
+                }
+            }
+        },
+        || {
+            set_error(error, PANICKED);
+            ptr::null_mut()
+        },
+    )
+}
+
+/// A native function which Kurt code can call.
+struct Callback {
+    arity: u32,
+    callback: KurtCallback,
+    data: *mut c_void,
+}
+
+impl HostObject for Callback {
+    fn type_name(&self) -> &'static str {
+        "Callback"
+    }
+
+    fn call(&self, args: &[Value], vm: &mut VirtualMachine) -> Result<Value> {
+        if args.len() != self.arity as usize {
+            return Err(Error::InvalidArgCount {
+                found: args.len() as u32,
+                expected: self.arity,
+            });
+        }
+
+        let args: Vec<KurtValue> = args
+            .iter()
+            .map(|arg| KurtValue {
+                global: vm.global(*arg),
+            })
+            .collect();
+
+        let pointers: Vec<*const KurtValue> =
+            args.iter().map(|arg| arg as *const KurtValue).collect();
+
+        let vm = vm as *mut VirtualMachine as *mut KurtVm;
+
+        // SAFETY: The VM is a KurtVm, since that's transparent, and the
+        //         arguments live until after the call.
+        let result = unsafe {
+            (self.callback)(vm, self.data, pointers.as_ptr(), pointers.len())
+        };
+
+        if result.is_null() {
+            return Err(Error::Host("native callback failed".into()));
+        }
+
+        // SAFETY: Callbacks give us ownership of the value they return.
+        let result = unsafe { Box::from_raw(result) };
+        Ok(result.global.get())
+    }
+}
+
+/// Make a native function which calls `callback` with `data` and `arity`
+/// arguments.
+///
+/// # Safety
+///
+/// `vm` must be a live VM, and `callback` must be safe to call with `data`
+/// for as long as the function is alive.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_vm_function(
+    vm: *mut KurtVm,
+    arity: u32,
+    callback: KurtCallback,
+    data: *mut c_void,
+) -> *mut KurtValue {
+    guard(
+        || {
+            try_value(&mut *vm, |vm| {
+                let callback = Callback {
+                    arity,
+                    callback,
+                    data,
+                };
+
+                Ok(Value::gc(vm.make_foreign(callback)?))
+            })
+        },
+        ptr::null_mut,
+    )
+}
+
+/// Free a value.
+///
+/// # Safety
+///
+/// `value` must have come from this library and not been freed, or be
+/// `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_free(value: *mut KurtValue) {
+    guard(
+        || {
+            if !value.is_null() {
+                drop(Box::from_raw(value));
+            }
+        },
+        || (),
+    )
+}
+
+/// Free a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must have come from this library and not been freed, or be `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_string_free(s: *mut c_char) {
+    guard(
+        || {
+            if !s.is_null() {
+                drop(CString::from_raw(s));
+            }
+        },
+        || (),
+    )
+}
+
+/// Make `()`.
+///
+/// # Safety
+///
+/// `vm` must be a live VM.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_unit(vm: *mut KurtVm) -> *mut KurtValue {
+    guard(|| new_value(&*vm, Value::UNIT), ptr::null_mut)
+}
+
+/// Make a Bool.
+///
+/// # Safety
+///
+/// `vm` must be a live VM.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_bool(
+    vm: *mut KurtVm,
+    b: bool,
+) -> *mut KurtValue {
+    guard(|| new_value(&*vm, Value::bool(b)), ptr::null_mut)
+}
+
+/// Make an Int, or return `NULL` if `i` doesn't fit in 48 bits.
+///
+/// # Safety
+///
+/// `vm` must be a live VM.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_int(
+    vm: *mut KurtVm,
+    i: i64,
+) -> *mut KurtValue {
+    guard(|| try_value(&mut *vm, |vm| i.into_value(vm)), ptr::null_mut)
+}
+
+/// Make a Float.
+///
+/// # Safety
+///
+/// `vm` must be a live VM.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_float(
+    vm: *mut KurtVm,
+    f: f64,
+) -> *mut KurtValue {
+    guard(|| new_value(&*vm, Value::float(f)), ptr::null_mut)
+}
+
+/// Make a String from a nul-terminated string, or return `NULL` if it isn't
+/// UTF-8.
+///
+/// # Safety
+///
+/// `vm` must be a live VM and `s` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_string(
+    vm: *mut KurtVm,
+    s: *const c_char,
+) -> *mut KurtValue {
+    guard(
+        || match CStr::from_ptr(s).to_str() {
+            Ok(s) => try_value(&mut *vm, |vm| vm.string(s)),
+            Err(_) => ptr::null_mut(),
+        },
+        ptr::null_mut,
+    )
+}
+
+/// What type of value `value` is.
+///
+/// # Safety
+///
+/// `value` must be a live value.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_type(value: *const KurtValue) -> KurtType {
+    guard(
+        || {
+            let value = (*value).global.get();
+
+            if value.as_unit().is_some() {
+                KurtType::Unit
+            } else if value.as_bool().is_some() {
+                KurtType::Bool
+            } else if value.as_int().is_some() {
+                KurtType::Int
+            } else if value.as_float().is_some() {
+                KurtType::Float
+            } else if value.as_str().is_some() {
+                KurtType::String
+            } else {
+                KurtType::Other
+            }
+        },
+        || KurtType::Other,
+    )
+}
+
+/// Read a Bool into `*out`, returning whether `value` was one.
+///
+/// # Safety
+///
+/// `value` must be a live value and `out` valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_as_bool(
+    value: *const KurtValue,
+    out: *mut bool,
+) -> bool {
+    guard(|| read(value, out), || false)
+}
+
+/// Read an Int into `*out`, returning whether `value` was one.
+///
+/// # Safety
+///
+/// `value` must be a live value and `out` valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_as_int(
+    value: *const KurtValue,
+    out: *mut i64,
+) -> bool {
+    guard(|| read(value, out), || false)
+}
+
+/// Read a Float into `*out`, returning whether `value` was one.
+///
+/// # Safety
+///
+/// `value` must be a live value and `out` valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_as_float(
+    value: *const KurtValue,
+    out: *mut f64,
+) -> bool {
+    guard(|| read(value, out), || false)
+}
+
+/// Copy a String, returning `NULL` if `value` isn't one. The copy must be
+/// freed with [`kurt_string_free`].
+///
+/// # Safety
+///
+/// `value` must be a live value.
+#[no_mangle]
+pub unsafe extern "C" fn kurt_value_as_string(
+    value: *const KurtValue,
+) -> *mut c_char {
+    guard(
+        || match (*value).global.get().as_str() {
+            Some(s) => c_string(s),
+            None => ptr::null_mut(),
+        },
+        ptr::null_mut,
+    )
+}
+
+unsafe fn read<T: FromValue>(value: *const KurtValue, out: *mut T) -> bool {
+    match T::from_value((*value).global.get()) {
+        Ok(t) => {
+            out.write(t);
+            true
+        }
+        Err(_) => false,
+    }
+}
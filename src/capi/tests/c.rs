@@ -0,0 +1,37 @@
+//! Compile `tests/test.c` against the static library and run it.
+
+use std::{path::Path, process::Command};
+
+#[test]
+fn c_test_program() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    // The library is built alongside the tests, in `target/<profile>/deps`.
+    let exe = std::env::current_exe().unwrap();
+    let library = exe.parent().unwrap().join("libcapi.a");
+    assert!(library.exists(), "{} wasn't built", library.display());
+
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi-test");
+
+    let status = Command::new(std::env::var("CC").unwrap_or("cc".into()))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(root.join("tests/test.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()
+        .expect("a C compiler is needed to run this test");
+    assert!(status.success(), "test.c didn't compile");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "test.c failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,310 @@
+//! Generate `include/kurt.h` from `src/lib.rs`, and check it's up to date.
+//!
+//! Run with `KURT_UPDATE_HEADER=1` to write the header instead of checking it.
+
+use std::{fmt::Write, fs, path::Path};
+
+use syn::{
+    Attribute, Expr, FnArg, Item, ItemEnum, ItemFn, ItemType, Lit, Meta, Pat,
+    ReturnType, Type,
+};
+
+const PRELUDE: &str = "\
+// The C interface to the Kurt interpreter.
+//
+// This file is generated from src/lib.rs by tests/header.rs, don't edit it by
+// hand.
+
+#ifndef KURT_H
+#define KURT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+";
+
+const POSTLUDE: &str = "
+#ifdef __cplusplus
+}
+#endif
+
+#endif
+";
+
+#[test]
+fn header_is_up_to_date() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let source = fs::read_to_string(root.join("src/lib.rs")).unwrap();
+    let header = generate(&syn::parse_file(&source).unwrap());
+
+    let path = root.join("include/kurt.h");
+
+    if std::env::var_os("KURT_UPDATE_HEADER").is_some() {
+        fs::write(&path, header).unwrap();
+        return;
+    }
+
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        existing == header,
+        "include/kurt.h is out of date, run the tests with \
+         KURT_UPDATE_HEADER=1 to update it"
+    );
+}
+
+fn generate(file: &syn::File) -> String {
+    let mut header = String::from(PRELUDE);
+
+    for item in &file.items {
+        match item {
+            Item::Struct(item) if is_public(&item.vis) => {
+                let name = &item.ident;
+                header += &docs(&item.attrs);
+                writeln!(header, "typedef struct {name} {name};").unwrap();
+            }
+
+            Item::Enum(item) if is_public(&item.vis) => {
+                header += &docs(&item.attrs);
+                header += &enumeration(item);
+            }
+
+            Item::Type(item) if is_public(&item.vis) => {
+                header += &docs(&item.attrs);
+                header += &function_pointer(item);
+            }
+
+            Item::Fn(item) if is_exported(item) => {
+                header += &docs(&item.attrs);
+                header += &function(item);
+            }
+
+            _ => continue,
+        }
+    }
+
+    header + POSTLUDE
+}
+
+fn is_public(vis: &syn::Visibility) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+}
+
+fn is_exported(item: &ItemFn) -> bool {
+    is_public(&item.vis)
+        && item
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("no_mangle"))
+}
+
+/// The doc comments, up to any `# Safety` section which is only for Rust.
+fn docs(attrs: &[Attribute]) -> String {
+    let mut lines = Vec::new();
+
+    for attr in attrs {
+        let Meta::NameValue(meta) = &attr.meta else {
+            continue;
+        };
+
+        if !meta.path.is_ident("doc") {
+            continue;
+        }
+
+        let Expr::Lit(expr) = &meta.value else {
+            continue;
+        };
+
+        let Lit::Str(lit) = &expr.lit else {
+            continue;
+        };
+
+        let line = lit.value();
+        let line = line.strip_prefix(' ').unwrap_or(&line).to_owned();
+
+        if line == "# Safety" {
+            break;
+        }
+
+        lines.push(line.replace("[`", "`").replace("`]", "`"));
+    }
+
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+
+    let mut docs = String::from("\n");
+    for line in lines {
+        if line.is_empty() {
+            docs += "//\n";
+        } else {
+            writeln!(docs, "// {line}").unwrap();
+        }
+    }
+
+    docs
+}
+
+fn enumeration(item: &ItemEnum) -> String {
+    let name = &item.ident;
+    let prefix = screaming(&name.to_string());
+
+    let mut out = format!("typedef enum {name} {{\n");
+
+    for variant in &item.variants {
+        let variant_docs = docs(&variant.attrs);
+        for line in variant_docs.lines().filter(|line| !line.is_empty()) {
+            writeln!(out, "    {line}").unwrap();
+        }
+
+        let constant = screaming(&variant.ident.to_string());
+        let (_, value) = variant
+            .discriminant
+            .as_ref()
+            .expect("enums need explicit values");
+
+        let Expr::Lit(value) = value else {
+            panic!("enum values need to be literals");
+        };
+
+        let Lit::Int(value) = &value.lit else {
+            panic!("enum values need to be integers");
+        };
+
+        writeln!(out, "    {prefix}_{constant} = {value},").unwrap();
+    }
+
+    writeln!(out, "}} {name};").unwrap();
+    out
+}
+
+fn function_pointer(item: &ItemType) -> String {
+    let Type::FnPtr(f) = &*item.ty else {
+        panic!("only function pointer types can be exported");
+    };
+
+    let parameters: Vec<String> = f
+        .inputs
+        .iter()
+        .map(|input| {
+            let name = input
+                .name
+                .as_ref()
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default();
+
+            declaration(&c_type(&input.ty), &name)
+        })
+        .collect();
+
+    let name = format!("(*{})", item.ident);
+
+    format!(
+        "typedef {}({});\n",
+        declaration(&return_type(&f.output), &name),
+        parameters.join(", ")
+    )
+}
+
+fn function(item: &ItemFn) -> String {
+    let parameters: Vec<String> = item
+        .sig
+        .inputs
+        .iter()
+        .map(|input| {
+            let FnArg::Typed(input) = input else {
+                panic!("exported functions can't be methods");
+            };
+
+            let Pat::Ident(name) = &*input.pat else {
+                panic!("exported functions need simple argument names");
+            };
+
+            declaration(&c_type(&input.ty), &name.ident.to_string())
+        })
+        .collect();
+
+    let parameters = if parameters.is_empty() {
+        "void".to_owned()
+    } else {
+        parameters.join(", ")
+    };
+
+    format!(
+        "{}({parameters});\n",
+        declaration(
+            &return_type(&item.sig.output),
+            &item.sig.ident.to_string()
+        )
+    )
+}
+
+/// Declare `name` as a `ty`, so pointers are written like `char *name`.
+fn declaration(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') || name.is_empty() {
+        format!("{ty}{name}")
+    } else {
+        format!("{ty} {name}")
+    }
+}
+
+fn return_type(output: &ReturnType) -> String {
+    match output {
+        ReturnType::Default => "void".to_owned(),
+        ReturnType::Type(_, ty) => c_type(ty),
+    }
+}
+
+fn c_type(ty: &Type) -> String {
+    match ty {
+        Type::Ptr(pointer) => {
+            let pointee = c_type(&pointer.elem);
+
+            match (is_const(pointer), pointee.ends_with('*')) {
+                (true, true) => format!("{pointee}const *"),
+                (true, false) => format!("const {pointee} *"),
+                (false, true) => format!("{pointee}*"),
+                (false, false) => format!("{pointee} *"),
+            }
+        }
+
+        Type::Path(path) => {
+            let name = path.path.segments.last().unwrap().ident.to_string();
+
+            match name.as_str() {
+                "c_char" => "char",
+                "c_void" => "void",
+                "bool" => "bool",
+                "f64" => "double",
+                "i64" => "int64_t",
+                "u32" => "uint32_t",
+                "usize" => "size_t",
+                _ => return name,
+            }
+            .to_owned()
+        }
+
+        _ => panic!("this type can't be used from C"),
+    }
+}
+
+/// `CamelCase` in `SCREAMING_CASE`.
+fn screaming(name: &str) -> String {
+    let mut out = String::new();
+
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+
+    out
+}
+
+fn is_const(pointer: &syn::TypePtr) -> bool {
+    matches!(pointer.mutability, syn::PointerMutability::Const(_))
+}
@@ -0,0 +1,173 @@
+// Exercises the C interface, run by tests/c.rs.
+//
+// Each check prints what failed and exits with a failure status.
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "kurt.h"
+
+#define CHECK(condition)                                                      \
+    do {                                                                      \
+        if (!(condition)) {                                                   \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                              \
+            exit(EXIT_FAILURE);                                               \
+        }                                                                     \
+    } while (0)
+
+// Adds its two Int arguments, counting how many times it's called in `data`.
+static KurtValue *add(KurtVm *vm, void *data, const KurtValue *const *args,
+                      size_t count) {
+    int64_t a, b;
+
+    *(int *)data += 1;
+
+    if (count != 2 || !kurt_value_as_int(args[0], &a) ||
+        !kurt_value_as_int(args[1], &b)) {
+        return NULL;
+    }
+
+    return kurt_value_int(vm, a + b);
+}
+
+static void load(void) {
+    KurtVm *vm = kurt_vm_new();
+    char *error = NULL;
+
+    CHECK(kurt_vm_load(vm, "1 + 2", &error) == KURT_STATUS_OK);
+    CHECK(error == NULL);
+
+    char *result = kurt_vm_last_result(vm);
+    CHECK(strcmp(result, "3") == 0);
+    kurt_string_free(result);
+
+    KurtValue *value = kurt_vm_result(vm);
+    int64_t i = 0;
+    CHECK(kurt_value_type(value) == KURT_TYPE_INT);
+    CHECK(kurt_value_as_int(value, &i) && i == 3);
+    kurt_value_free(value);
+
+    kurt_vm_free(vm);
+}
+
+// Nothing has been loaded, so there's no result yet.
+static void fresh(void) {
+    KurtVm *vm = kurt_vm_new();
+
+    CHECK(kurt_vm_result(vm) == NULL);
+
+    char *result = kurt_vm_last_result(vm);
+    CHECK(result != NULL);
+    kurt_string_free(result);
+
+    kurt_vm_free(vm);
+}
+
+// Values outlive their VM, but can only be freed.
+static void outlived(void) {
+    KurtVm *vm = kurt_vm_new();
+    KurtValue *value = kurt_value_int(vm, 7);
+    kurt_vm_free(vm);
+
+    int64_t i = 0;
+    CHECK(!kurt_value_as_int(value, &i));
+    CHECK(kurt_value_type(value) == KURT_TYPE_OTHER);
+    kurt_value_free(value);
+}
+
+static void errors(void) {
+    KurtVm *vm = kurt_vm_new();
+    char *error = NULL;
+
+    CHECK(kurt_vm_load(vm, "1 +", &error) == KURT_STATUS_COMPILE_ERROR);
+    CHECK(error != NULL && strstr(error, "error") != NULL);
+    kurt_string_free(error);
+
+    error = NULL;
+    CHECK(kurt_vm_load(vm, "1 + true", &error) == KURT_STATUS_RUNTIME_ERROR);
+    CHECK(error != NULL && strlen(error) > 0);
+    kurt_string_free(error);
+
+    kurt_vm_free(vm);
+}
+
+static void values(void) {
+    KurtVm *vm = kurt_vm_new();
+    bool b = false;
+    double f = 0.0;
+
+    KurtValue *unit = kurt_value_unit(vm);
+    CHECK(kurt_value_type(unit) == KURT_TYPE_UNIT);
+    CHECK(!kurt_value_as_bool(unit, &b));
+    kurt_value_free(unit);
+
+    KurtValue *yes = kurt_value_bool(vm, true);
+    CHECK(kurt_value_as_bool(yes, &b) && b);
+    kurt_value_free(yes);
+
+    KurtValue *half = kurt_value_float(vm, 0.5);
+    CHECK(kurt_value_as_float(half, &f) && f == 0.5);
+    kurt_value_free(half);
+
+    CHECK(kurt_value_int(vm, INT64_MAX) == NULL);
+
+    KurtValue *string = kurt_value_string(vm, "a string too long to inline");
+    CHECK(kurt_value_type(string) == KURT_TYPE_STRING);
+    char *s = kurt_value_as_string(string);
+    CHECK(strcmp(s, "a string too long to inline") == 0);
+    kurt_string_free(s);
+    kurt_value_free(string);
+
+    kurt_vm_free(vm);
+}
+
+static void callbacks(void) {
+    KurtVm *vm = kurt_vm_new();
+    char *error = NULL;
+    int calls = 0;
+
+    CHECK(kurt_vm_load(vm, "(f) => f(1, 2) + f(3, 4)", &error) ==
+          KURT_STATUS_OK);
+
+    KurtValue *closure = kurt_vm_result(vm);
+    KurtValue *f = kurt_vm_function(vm, 2, add, &calls);
+
+    const KurtValue *args[] = {f};
+    KurtValue *result = kurt_vm_call(vm, closure, args, 1, &error);
+    int64_t i = 0;
+    CHECK(result != NULL);
+    CHECK(kurt_value_as_int(result, &i) && i == 10);
+    CHECK(calls == 2);
+    kurt_value_free(result);
+
+    // Calling the native function directly, with the wrong arguments.
+    KurtValue *unit = kurt_value_unit(vm);
+    const KurtValue *bad[] = {unit, unit};
+    CHECK(kurt_vm_call(vm, f, bad, 2, &error) == NULL);
+    CHECK(error != NULL);
+    kurt_string_free(error);
+
+    error = NULL;
+    CHECK(kurt_vm_call(vm, f, bad, 1, &error) == NULL);
+    CHECK(error != NULL && strstr(error, "arguments") != NULL);
+    kurt_string_free(error);
+
+    kurt_value_free(unit);
+    kurt_value_free(f);
+    kurt_value_free(closure);
+    kurt_vm_free(vm);
+}
+
+int main(void) {
+    load();
+    fresh();
+    outlived();
+    errors();
+    values();
+    callbacks();
+
+    printf("ok\n");
+    return EXIT_SUCCESS;
+}
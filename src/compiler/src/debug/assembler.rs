@@ -0,0 +1,665 @@
+//! An assembler for module listings.
+//!
+//! This reads the listing format written by [`Module`]'s
+//! [`Display`][std::fmt::Display] impl (which is what `--dump` prints) and
+//! turns it back into a [`Module`]. This is mostly useful for testing the
+//! runtime in isolation, or trying out new opcodes before the compiler knows
+//! how to emit them.
+//!
+//! A listing looks like this:
+//!
+//! ``` text
+//! module {
+//! constants:
+//!  000 | String "main"
+//!  001 | Keyword "ok"
+//!
+//! function 000: // main( )
+//!  name 000
+//!  parameters 0
+//!  span 000:000-000:003
+//!  debug
+//!  000 (000:000-000:003) | LoadConstant 1       // Keyword "ok"
+//!  001 (000:000-000:003) | Nop
+//!  002 (000:000-000:003) | Halt
+//! }
+//! ```
+//!
+//! The `debug` line is only present if the function has debug info, in which
+//! case every op has a span, and the names of the function's parameters follow
+//! it. It can be followed by `local` lines, giving each local's index, name
+//! and the range of ops where it's in scope. Blank lines and anything after a
+//! `//` are ignored, other than inside string and character constants.
+
+use std::fmt::{self, Display, Formatter};
+
+use common::{i48, u48, Index};
+use diagnostic::{Caret, Span};
+
+use crate::{
+    Capture, Constant, Function, FunctionDebug, LocalDebug, Module, Op,
+};
+
+/// The reasons a listing can fail to assemble.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssembleErrorKind {
+    /// The listing doesn't start with `module {`.
+    ExpectedModule,
+
+    /// The listing ended before the module's closing `}`.
+    UnexpectedEnd,
+
+    /// There's more after the module's closing `}`.
+    TrailingInput,
+
+    /// A line which doesn't belong where it is.
+    UnexpectedLine,
+
+    /// Constants, functions and ops are numbered, and must be in order.
+    OutOfOrder { expected: usize, found: usize },
+
+    /// A number that couldn't be parsed.
+    InvalidNumber(String),
+
+    /// A constant that couldn't be parsed.
+    InvalidConstant,
+
+    /// A span that couldn't be parsed.
+    InvalidSpan,
+
+    /// A capture that couldn't be parsed.
+    InvalidCapture,
+
+    /// A local's debug info that couldn't be parsed.
+    InvalidLocal,
+
+    /// An op which doesn't exist, or has the wrong arguments.
+    InvalidOp(String),
+
+    /// An op in a function with debug info that doesn't have a span.
+    MissingSpan,
+
+    /// An op with a span, in a function without debug info.
+    UnexpectedSpan,
+}
+
+/// An error found while assembling a listing, with the (1-indexed) line it's
+/// on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembleError {
+    line: usize,
+    kind: AssembleErrorKind,
+}
+
+impl AssembleError {
+    /// The line number of the problem, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> &AssembleErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for AssembleErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use AssembleErrorKind::*;
+
+        match self {
+            ExpectedModule => write!(f, "expected `module {{`"),
+            UnexpectedEnd => write!(f, "listing ended before the module did"),
+            TrailingInput => write!(f, "unexpected input after the module"),
+            UnexpectedLine => write!(f, "this line doesn't belong here"),
+            OutOfOrder { expected, found } => {
+                write!(f, "expected entry {expected} but found {found}")
+            }
+            InvalidNumber(n) => write!(f, "invalid number `{n}`"),
+            InvalidConstant => write!(f, "invalid constant"),
+            InvalidSpan => write!(f, "invalid span"),
+            InvalidCapture => write!(f, "invalid capture"),
+            InvalidLocal => write!(f, "invalid local"),
+            InvalidOp(op) => write!(f, "invalid op `{op}`"),
+            MissingSpan => write!(f, "op is missing a span"),
+            UnexpectedSpan => {
+                write!(f, "op has a span, but the function has no debug info")
+            }
+        }
+    }
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+type Result<T> = std::result::Result<T, AssembleErrorKind>;
+
+impl Module {
+    /// Assemble a module from a listing, like the ones printed by `--dump`.
+    ///
+    /// For any module `m`, assembling `m.to_string()` results in a module
+    /// equal to `m`, as long as it doesn't have an input.
+    ///
+    /// The resulting module isn't checked, see [`Module::verify`].
+    pub fn assemble(
+        listing: &str,
+    ) -> std::result::Result<Module, AssembleError> {
+        let mut lines = listing
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, strip_comment(line).trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let error = |line, kind| AssembleError { line, kind };
+
+        match lines.next() {
+            Some((_, "module {")) => {}
+            Some((n, _)) => {
+                return Err(error(n, AssembleErrorKind::ExpectedModule))
+            }
+            None => return Err(error(1, AssembleErrorKind::ExpectedModule)),
+        }
+
+        let mut module = Module {
+            input: None,
+            constants: Vec::new(),
+            functions: Vec::new(),
+            verified: Default::default(),
+        };
+
+        // We need the line again for constants, since comment stripping would
+        // mangle any strings containing `//`.
+        let raw_lines: Vec<&str> = listing.lines().collect();
+
+        let mut last = 1;
+
+        for (n, line) in lines.by_ref() {
+            last = n;
+
+            if line == "}" {
+                return match lines.next() {
+                    Some((n, _)) => {
+                        Err(error(n, AssembleErrorKind::TrailingInput))
+                    }
+                    None => Ok(module),
+                };
+            }
+
+            module
+                .assemble_line(line, raw_lines[n - 1])
+                .map_err(|kind| error(n, kind))?;
+        }
+
+        Err(error(last + 1, AssembleErrorKind::UnexpectedEnd))
+    }
+
+    fn assemble_line(&mut self, line: &str, raw: &str) -> Result<()> {
+        if line == "constants:" {
+            return if self.constants.is_empty() && self.functions.is_empty() {
+                Ok(())
+            } else {
+                Err(AssembleErrorKind::UnexpectedLine)
+            };
+        }
+
+        if let Some(rest) = line.strip_prefix("function ") {
+            let index = rest
+                .strip_suffix(':')
+                .ok_or(AssembleErrorKind::UnexpectedLine)?;
+            expect_entry(index, self.functions.len())?;
+
+            self.functions.push(Function::default());
+            return Ok(());
+        }
+
+        match self.functions.last_mut() {
+            None => self.assemble_constant(raw),
+            Some(function) => function.assemble_line(line),
+        }
+    }
+
+    fn assemble_constant(&mut self, raw: &str) -> Result<()> {
+        let (index, rest) = raw
+            .split_once('|')
+            .ok_or(AssembleErrorKind::UnexpectedLine)?;
+        expect_entry(index.trim(), self.constants.len())?;
+
+        let (kind, value) = rest
+            .trim()
+            .split_once(' ')
+            .ok_or(AssembleErrorKind::InvalidConstant)?;
+
+        let constant = match kind {
+            "Character" => {
+                let (s, rest) = parse_quoted(value, '\'')?;
+                let mut chars = s.chars();
+
+                match (chars.next(), chars.next(), is_end(rest)) {
+                    (Some(c), None, true) => Constant::Character(c),
+                    _ => return Err(AssembleErrorKind::InvalidConstant),
+                }
+            }
+
+            "Float" => {
+                let value = strip_comment(value).trim();
+
+                let bits = if let Some(hex) = value.strip_prefix("0x") {
+                    u64::from_str_radix(hex, 16).ok()
+                } else {
+                    value.parse::<f64>().ok().map(f64::to_bits)
+                };
+
+                Constant::Float(bits.ok_or(AssembleErrorKind::InvalidConstant)?)
+            }
+
+            "Keyword" | "String" => {
+                let (s, rest) = parse_quoted(value, '"')?;
+
+                if !is_end(rest) {
+                    return Err(AssembleErrorKind::InvalidConstant);
+                }
+
+                if kind == "Keyword" {
+                    Constant::Keyword(s)
+                } else {
+                    Constant::String(s)
+                }
+            }
+
+            _ => return Err(AssembleErrorKind::InvalidConstant),
+        };
+
+        self.constants.push(constant);
+        Ok(())
+    }
+}
+
+impl Function {
+    fn assemble_line(&mut self, line: &str) -> Result<()> {
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match word {
+            "name" => self.name = Some(Index::new(number(rest)?)),
+            "parameters" => self.parameter_count = number(rest)?,
+            "span" => self.span = parse_span(rest)?,
+            "capture" => self.captures.push(parse_capture(rest)?),
+
+            "debug" => {
+                let parameter_names = rest
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(String::from)
+                    .collect();
+
+                self.debug_info = Some(FunctionDebug {
+                    parameter_names,
+                    code_spans: Vec::new(),
+                    locals: Vec::new(),
+                });
+            }
+
+            "local" => {
+                let local = parse_local(rest)?;
+
+                self.debug_info
+                    .as_mut()
+                    .ok_or(AssembleErrorKind::UnexpectedLine)?
+                    .locals
+                    .push(local);
+            }
+
+            _ => return self.assemble_op(line),
+        }
+
+        Ok(())
+    }
+
+    fn assemble_op(&mut self, line: &str) -> Result<()> {
+        let (location, op) = line
+            .split_once('|')
+            .ok_or(AssembleErrorKind::UnexpectedLine)?;
+
+        let (index, span) = location
+            .trim()
+            .split_once(' ')
+            .unwrap_or((location.trim(), ""));
+        expect_entry(index, self.code.len())?;
+
+        let span = span.trim();
+
+        match &mut self.debug_info {
+            Some(_) if span.is_empty() => {
+                return Err(AssembleErrorKind::MissingSpan)
+            }
+            Some(debug) => {
+                let span = span
+                    .strip_prefix('(')
+                    .and_then(|s| s.strip_suffix(')'))
+                    .ok_or(AssembleErrorKind::InvalidSpan)?;
+                debug.code_spans.push(parse_span(span)?);
+            }
+            None if !span.is_empty() => {
+                return Err(AssembleErrorKind::UnexpectedSpan)
+            }
+            None => {}
+        }
+
+        self.code.push(parse_op(op.trim())?);
+        Ok(())
+    }
+}
+
+/// Parse an op, in the form it's [displayed][Display] in.
+///
+/// Arguments can be given either after spaces like `Call 2` or in parens like
+/// `Close(2)`, since both are used by [`Op`]'s [`Display`] impl.
+fn parse_op(text: &str) -> Result<Op> {
+    let words: String = text
+        .chars()
+        .map(|c| if matches!(c, '(' | ')' | ',') { ' ' } else { c })
+        .collect();
+
+    let mut words = words.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+
+    let invalid = || AssembleErrorKind::InvalidOp(text.to_owned());
+
+    let op = match (name, args.as_slice()) {
+        ("Halt", []) => Op::Halt,
+        ("Nop", []) => Op::Nop,
+        ("Dup", []) => Op::Dup,
+        ("Pop", []) => Op::Pop,
+        ("Close", [n]) => Op::Close(number(n)?),
+        ("True", []) => Op::True,
+        ("False", []) => Op::False,
+        ("Unit", []) => Op::Unit,
+        ("U48", [n]) => Op::U48(n.parse::<u48>().map_err(|_| invalid())?),
+        ("I48", [n]) => Op::I48(
+            n.parse::<i64>()
+                .ok()
+                .and_then(i48::from_i64)
+                .ok_or_else(invalid)?,
+        ),
+        ("LoadSelf", []) => Op::LoadSelf,
+        ("LoadConstant", [i]) => Op::LoadConstant(Index::new(number(i)?)),
+        ("LoadLocal", [i]) => Op::LoadLocal(Index::new(number(i)?)),
+        ("LoadCapture", [i]) => Op::LoadCapture(Index::new(number(i)?)),
+        ("DefineLocal", []) => Op::DefineLocal,
+        ("LoadFunction" | "LoadClosure", [i]) => {
+            Op::LoadFunction(Index::new(number(i)?))
+        }
+        ("SetLocal", [i]) => Op::SetLocal(Index::new(number(i)?)),
+        ("SetCapture", [i]) => Op::SetCapture(Index::new(number(i)?)),
+        ("SetIndex", []) => Op::SetIndex,
+        ("Index", []) => Op::Index,
+        ("Call", [n]) => Op::Call(number(n)?),
+        ("Return", []) => Op::Return,
+        ("Jump", [n]) => Op::Jump(number(n)?),
+        ("Branch", [n]) => Op::Branch(number(n)?),
+        ("BranchFalse", [n]) => Op::BranchFalse(number(n)?),
+        ("Not", []) => Op::Not,
+        ("Neg", []) => Op::Neg,
+        ("Add", []) => Op::Add,
+        ("Sub", []) => Op::Sub,
+        ("Mul", []) => Op::Mul,
+        ("Div", []) => Op::Div,
+        ("Pow", []) => Op::Pow,
+        ("Rem", []) => Op::Rem,
+        ("BitAnd", []) => Op::BitAnd,
+        ("BitOr", []) => Op::BitOr,
+        ("BitXOR", []) => Op::BitXOR,
+        ("SHL", []) => Op::SHL,
+        ("SHR", []) => Op::SHR,
+        ("Eq", []) => Op::Eq,
+        ("Ne", []) => Op::Ne,
+        ("Gt", []) => Op::Gt,
+        ("Ge", []) => Op::Ge,
+        ("Lt", []) => Op::Lt,
+        ("Le", []) => Op::Le,
+        ("List", [n]) => Op::List(number(n)?),
+        ("Tuple", [n, "true"]) => Op::Tuple(number(n)?, true),
+        ("Tuple", [n, "false"]) => Op::Tuple(number(n)?, false),
+        _ => return Err(invalid()),
+    };
+
+    Ok(op)
+}
+
+/// Parse a capture like `Local 0` or `Recapture 2`.
+fn parse_capture(text: &str) -> Result<Capture> {
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["Local", i] => Ok(Capture::Local(Index::new(number(i)?))),
+        ["Recapture", i] => Ok(Capture::Recapture(Index::new(number(i)?))),
+        _ => Err(AssembleErrorKind::InvalidCapture),
+    }
+}
+
+/// Parse a local's debug info like `0 x 3..10`, where the end of the range can
+/// be left off.
+fn parse_local(text: &str) -> Result<LocalDebug> {
+    let invalid = || AssembleErrorKind::InvalidLocal;
+
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [index, name, range] => {
+            let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+
+            Ok(LocalDebug {
+                name: name.to_string(),
+                index: Index::new(number(index)?),
+                start: Index::new(number(start)?),
+                end: match end {
+                    "" => None,
+                    end => Some(Index::new(number(end)?)),
+                },
+            })
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse a span like `001:004-001:010`.
+fn parse_span(text: &str) -> Result<Span> {
+    let caret = |text: &str| {
+        let (line, column) = text.split_once(':')?;
+        Some(Caret::new(line.parse().ok()?, column.parse().ok()?))
+    };
+
+    let (start, end) =
+        text.split_once('-').ok_or(AssembleErrorKind::InvalidSpan)?;
+
+    match (caret(start), caret(end)) {
+        (Some(start), Some(end)) => Ok(Span::new(start, end)),
+        _ => Err(AssembleErrorKind::InvalidSpan),
+    }
+}
+
+/// Check that a numbered entry is the one we expected next.
+fn expect_entry(text: &str, expected: usize) -> Result<()> {
+    let found = number::<usize>(text)?;
+
+    if found == expected {
+        Ok(())
+    } else {
+        Err(AssembleErrorKind::OutOfOrder { expected, found })
+    }
+}
+
+fn number<N: std::str::FromStr>(text: &str) -> Result<N> {
+    text.parse()
+        .map_err(|_| AssembleErrorKind::InvalidNumber(text.to_owned()))
+}
+
+/// Remove a trailing `//` comment from a line.
+fn strip_comment(line: &str) -> &str {
+    line.split_once("//").map(|(code, _)| code).unwrap_or(line)
+}
+
+/// Is there nothing but whitespace or a comment left?
+fn is_end(rest: &str) -> bool {
+    strip_comment(rest).trim().is_empty()
+}
+
+/// Parse a quoted string or character which was escaped the way Rust's
+/// [`Debug`][std::fmt::Debug] impls do it, returning the contents and whatever
+/// comes after the closing quote.
+fn parse_quoted(text: &str, quote: char) -> Result<(String, &str)> {
+    let invalid = AssembleErrorKind::InvalidConstant;
+
+    let body = text
+        .trim_start()
+        .strip_prefix(quote)
+        .ok_or(invalid.clone())?;
+    let mut chars = body.char_indices();
+    let mut buf = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = match chars.next().ok_or(invalid.clone())?.1 {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    '\\' => '\\',
+                    '\'' => '\'',
+                    '"' => '"',
+                    'u' => {
+                        let rest = &body[i + 2..];
+                        let close = rest.find('}').ok_or(invalid.clone())?;
+                        let hex = rest
+                            .get(1..close)
+                            .filter(|_| rest.starts_with('{'))
+                            .ok_or(invalid.clone())?;
+
+                        // skip past the braces and hex digits
+                        for _ in 0..=close {
+                            chars.next();
+                        }
+
+                        u32::from_str_radix(hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or(invalid.clone())?
+                    }
+                    _ => return Err(invalid),
+                };
+
+                buf.push(escaped);
+            }
+
+            c if c == quote => return Ok((buf, &body[i + 1..])),
+
+            c => buf.push(c),
+        }
+    }
+
+    Err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(module: &Module) {
+        let listing = module.to_string();
+        let assembled = Module::assemble(&listing);
+        assert_eq!(assembled.as_ref(), Ok(module), "listing:\n{listing}");
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        round_trip(&Module::default());
+    }
+
+    #[test]
+    fn round_trip_constants() {
+        let module = Module {
+            constants: vec![
+                Constant::String(
+                    "with \"quotes\", // and a \\ \n newline".into(),
+                ),
+                Constant::Keyword("ok".into()),
+                Constant::Character('\''),
+                Constant::Character('\u{301}'),
+                Constant::Float(1.5f64.to_bits()),
+                Constant::Float((-0.0f64).to_bits()),
+                Constant::Float(f64::INFINITY.to_bits()),
+                Constant::Float(0x7ff8_0000_0000_0001),
+            ],
+            ..Module::default()
+        };
+        round_trip(&module);
+    }
+
+    #[test]
+    fn round_trip_stripped() {
+        let mut module =
+            Module::try_from("let f = (a, b) => a; f(1, 2)").unwrap();
+        for function in &mut module.functions {
+            function.strip_debug();
+        }
+        round_trip(&module);
+    }
+
+    #[test]
+    fn all_ops() {
+        let ops = [
+            Op::Close(3),
+            Op::U48(u48::MAX),
+            Op::I48(i48::MIN),
+            Op::LoadCapture(Index::new(1)),
+            Op::LoadFunction(Index::new(2)),
+            Op::SetLocal(Index::new(3)),
+            Op::SetCapture(Index::new(4)),
+            Op::Jump(-5),
+            Op::Branch(6),
+            Op::BranchFalse(-7),
+            Op::List(8),
+            Op::Tuple(9, true),
+            Op::Tuple(0, false),
+        ];
+
+        for op in ops {
+            assert_eq!(parse_op(&op.to_string()), Ok(op));
+        }
+    }
+
+    #[test]
+    fn invalid_op() {
+        let listing = "module {\nfunction 000:\n 000 | Frobnicate\n}";
+        let error = Module::assemble(listing).unwrap_err();
+        assert_eq!(error.line(), 3);
+        assert_eq!(
+            error.kind(),
+            &AssembleErrorKind::InvalidOp("Frobnicate".into())
+        );
+    }
+
+    #[test]
+    fn out_of_order() {
+        let listing = "module {\nfunction 001:\n}";
+        let error = Module::assemble(listing).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &AssembleErrorKind::OutOfOrder {
+                expected: 0,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn unclosed() {
+        let listing = "module {\nfunction 000:\n 000 | Halt";
+        let error = Module::assemble(listing).unwrap_err();
+        assert_eq!(error.kind(), &AssembleErrorKind::UnexpectedEnd);
+    }
+}
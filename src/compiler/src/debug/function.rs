@@ -3,12 +3,40 @@
 use common::Index;
 use diagnostic::Span;
 
-use crate::{internal::FunctionBuilder, Op};
+use crate::{internal::FunctionBuilder, Local, Op};
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct FunctionDebug {
     pub(crate) parameter_names: Vec<String>,
     pub(crate) code_spans: Vec<Span>,
+    pub(crate) locals: Vec<LocalDebug>,
+}
+
+/// A local binding's name, and the range of code where it's in scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalDebug {
+    pub(crate) name: String,
+    pub(crate) index: Index<Local>,
+    pub(crate) start: Index<Op>,
+
+    /// The end is exclusive, and `None` if it's in scope until the end.
+    pub(crate) end: Option<Index<Op>>,
+}
+
+impl LocalDebug {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The index of the local, relative to the function's base pointer.
+    pub fn index(&self) -> Index<Local> {
+        self.index
+    }
+
+    /// Is the binding in scope when the program counter is at `pc`?
+    pub fn is_in_scope(&self, pc: Index<Op>) -> bool {
+        self.start <= pc && self.end.is_none_or(|end| pc < end)
+    }
 }
 
 impl FunctionDebug {
@@ -22,6 +50,7 @@ impl FunctionDebug {
         Some(FunctionDebug {
             parameter_names,
             code_spans: builder.code().spans().to_owned(),
+            locals: builder.local_debug().to_owned(),
         })
     }
 
@@ -29,6 +58,22 @@ impl FunctionDebug {
         &self.parameter_names
     }
 
+    /// All the local bindings in the function, including parameters, in the
+    /// order they were bound.
+    pub fn locals(&self) -> &[LocalDebug] {
+        &self.locals
+    }
+
+    /// The local bindings in scope at `pc`, from most to least recently bound.
+    ///
+    /// When names are shadowed, the first one with a name is the one in use.
+    pub fn locals_at(
+        &self,
+        pc: Index<Op>,
+    ) -> impl Iterator<Item = &LocalDebug> + '_ {
+        self.locals.iter().rev().filter(move |l| l.is_in_scope(pc))
+    }
+
     pub fn span_of(&self, index: Index<Op>) -> Option<Span> {
         self.code_spans.get(index.as_usize()).cloned()
     }
@@ -2,7 +2,11 @@
 //!
 //! These are separate so we can keep them optional in the final built modules.
 
+mod assembler;
 mod function;
 mod module;
 
-pub use self::function::FunctionDebug;
+pub use self::{
+    assembler::{AssembleError, AssembleErrorKind},
+    function::{FunctionDebug, LocalDebug},
+};
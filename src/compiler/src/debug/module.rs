@@ -1,18 +1,32 @@
 //! Module display code
+//!
+//! The listing produced here is meant to be read by people, but it also
+//! contains everything in the [`Module`] so that it can be turned back into
+//! one by the [assembler][super::assembler].
 
 use std::fmt::{self, Display, Formatter};
 
-use crate::{Function, Module, Op};
+use diagnostic::Span;
+
+use crate::{Capture, Constant, Function, Module, Op};
 
 impl Display for Module {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         writeln!(f, "module {{")?;
 
-        if !self.functions.is_empty() {
+        if !self.constants.is_empty() {
+            writeln!(f, "constants:")?;
+        }
+
+        for (i, constant) in self.constants.iter().enumerate() {
+            write!(f, " {:03} | ", i)?;
+            display_constant_entry(constant, f)?;
             writeln!(f)?;
         }
 
-        for function in &self.functions {
+        for (i, function) in self.functions.iter().enumerate() {
+            writeln!(f)?;
+            write!(f, "function {:03}: // ", i)?;
             function.display_listing(self, f)?;
         }
 
@@ -20,6 +34,41 @@ impl Display for Module {
     }
 }
 
+/// Constants are written with their kind, and in a form that can be parsed
+/// back into exactly the same constant.
+fn display_constant_entry(
+    constant: &Constant,
+    f: &mut Formatter,
+) -> fmt::Result {
+    match constant {
+        Constant::Character(c) => write!(f, "Character {:?}", c),
+        Constant::Float(bits) => {
+            let float = f64::from_bits(*bits);
+
+            // NaNs have payloads we'd lose by printing them as numbers.
+            if float.is_nan() {
+                write!(f, "Float {:#018x}", bits)
+            } else {
+                write!(f, "Float {:?}", float)
+            }
+        }
+        Constant::Keyword(s) => write!(f, "Keyword {:?}", s),
+        Constant::String(s) => write!(f, "String {:?}", s),
+    }
+}
+
+/// Spans are written as `line:column-line:column`, zero-indexed.
+pub(crate) fn display_span(span: Span, f: &mut Formatter) -> fmt::Result {
+    write!(
+        f,
+        "{:03}:{:03}-{:03}:{:03}",
+        span.start().line(),
+        span.start().column(),
+        span.end().line(),
+        span.end().column()
+    )
+}
+
 impl Function {
     fn display_listing(
         &self,
@@ -27,7 +76,8 @@ impl Function {
         f: &mut Formatter,
     ) -> fmt::Result {
         self.display_signature(f, module)?;
-        writeln!(f, ":")?;
+        writeln!(f)?;
+        self.display_attributes(f)?;
         self.display_code(f, module)
     }
 
@@ -50,31 +100,82 @@ impl Function {
     }
 
     fn display_name(&self, f: &mut Formatter, module: &Module) -> fmt::Result {
-        if let Some(name) = self.name().map(|n| &module[n]) {
-            write!(f, "{name}")
+        if let Some(name) =
+            self.name().and_then(|n| module.constants.get(n.as_usize()))
+        {
+            // This is in a comment, so it can't be allowed to span lines.
+            write!(f, "{}", name.to_string().escape_debug())
         } else {
             write!(f, "{}", Function::DEFAULT_NAMELESS_NAME)
         }
     }
 
+    fn display_attributes(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(name) = self.name() {
+            writeln!(f, " name {:03}", name.as_usize())?;
+        }
+
+        writeln!(f, " parameters {}", self.parameter_count())?;
+
+        write!(f, " span ")?;
+        display_span(self.span(), f)?;
+        writeln!(f)?;
+
+        for capture in self.captures() {
+            match capture {
+                Capture::Local(i) => writeln!(f, " capture Local {}", i)?,
+                Capture::Recapture(i) => {
+                    writeln!(f, " capture Recapture {}", i)?
+                }
+            }
+        }
+
+        if let Some(debug) = self.debug_info() {
+            write!(f, " debug")?;
+            for (i, p) in debug.parameter_names().iter().enumerate() {
+                let separator = if i == 0 { " " } else { ", " };
+                write!(f, "{separator}{p}")?;
+            }
+            writeln!(f)?;
+
+            for local in debug.locals() {
+                write!(
+                    f,
+                    " local {} {} {}..",
+                    local.index(),
+                    local.name(),
+                    local.start
+                )?;
+
+                if let Some(end) = local.end {
+                    write!(f, "{end}")?;
+                }
+
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn display_code(
         &self,
         f: &mut std::fmt::Formatter,
         module: &Module,
     ) -> fmt::Result {
         for (i, op) in self.code.iter().enumerate() {
+            write!(f, " {:03} ", i)?;
+
             if let Some(span) =
                 self.debug_info().and_then(|d| d.code_spans.get(i))
             {
-                write!(
-                    f,
-                    " {:03} ({:03}:{:03}) | ",
-                    i,
-                    span.start().line(),
-                    span.start().column()
-                )?;
+                write!(f, "(")?;
+                display_span(*span, f)?;
+                write!(f, ") ")?;
             }
 
+            write!(f, "| ")?;
+
             match op {
                 Op::LoadLocal(index) => {
                     write!(f, "{:<20} // ", format!("{op}"))?;
@@ -93,16 +194,27 @@ impl Function {
                 Op::LoadConstant(index) => {
                     write!(f, "{:<20} // ", format!("{op}"))?;
 
-                    writeln!(f, "{}", module[*index])
+                    if let Some(constant) =
+                        module.constants.get(index.as_usize())
+                    {
+                        display_constant_entry(constant, f)?;
+                        writeln!(f)
+                    } else {
+                        writeln!(f, "???")
+                    }
                 }
 
                 Op::LoadFunction(index) => {
                     write!(f, "{:<20} // ", format!("{op}"))?;
 
-                    if let Some(name) =
-                        module[*index].name().map(|n| &module[n])
-                    {
-                        writeln!(f, "{}", name)
+                    let name = module
+                        .functions
+                        .get(index.as_usize())
+                        .and_then(Function::name)
+                        .and_then(|n| module.constants.get(n.as_usize()));
+
+                    if let Some(name) = name {
+                        writeln!(f, "{}", name.to_string().escape_debug())
                     } else {
                         writeln!(f, "???")
                     }
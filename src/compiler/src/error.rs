@@ -4,7 +4,7 @@ use std::{error, fmt};
 
 use diagnostic::{Diagnostic, Span};
 
-use crate::{Function, Module};
+use crate::{Function, Module, ModuleBuilder};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -19,6 +19,7 @@ pub enum Error {
     EarlyExitKindNotSupported(Span),
     NotALegalAssignmentTarget(Span),
     ContinueWithValue(Span),
+    ReturnNotInFunction(Span),
     ShadowExport(Span, Span),
     PubNotTopLevel(Span),
     ImportNotTopLevel(Span),
@@ -38,6 +39,8 @@ pub enum Error {
     TooManyLocals(Span),
     TooManyExports(Span),
     TooManyImports(Span),
+
+    NestedTooDeeply(Span),
 }
 
 impl fmt::Display for Error {
@@ -70,6 +73,9 @@ impl fmt::Display for Error {
             ContinueWithValue(_) => {
                 write!(f, "a `continue` cannot take a value")
             }
+            ReturnNotInFunction(_) => {
+                write!(f, "a `return` must be inside a function")
+            }
             ShadowExport(_, _) => write!(f, "cannot shadow exported value"),
             JumpTooFar(_) => {
                 write!(f, "this code needs to jump too far")
@@ -114,6 +120,10 @@ impl fmt::Display for Error {
             TooManyImports(_) => {
                 write!(f, "this module has to many imported modules")
             }
+
+            NestedTooDeeply(_) => {
+                write!(f, "this expression is nested too deeply")
+            }
         }
     }
 }
@@ -131,6 +141,7 @@ impl Error {
             Error::EarlyExitKindNotSupported(s) => s,
             Error::NotALegalAssignmentTarget(s) => s,
             Error::ContinueWithValue(s) => s,
+            Error::ReturnNotInFunction(s) => s,
             Error::ShadowExport(s, _) => s,
             Error::JumpTooFar(s) => s,
             Error::PubNotTopLevel(s) => s,
@@ -147,6 +158,7 @@ impl Error {
             Error::TooManyLocals(s) => s,
             Error::TooManyExports(s) => s,
             Error::TooManyImports(s) => s,
+            Error::NestedTooDeeply(s) => s,
         }
     }
 }
@@ -172,6 +184,9 @@ impl From<Error> for Diagnostic {
                 Error::not_assignment_target(s, d)
             }
             Error::ContinueWithValue(s) => Error::continue_with_value(s, d),
+            Error::ReturnNotInFunction(s) => {
+                Error::return_not_in_function(s, d)
+            }
             Error::ShadowExport(s, p) => Error::shadow_export(s, p, d),
             Error::JumpTooFar(s) => Error::jump_too_far(s, d),
             Error::PubNotTopLevel(s) => Error::pub_not_top_level(s, d),
@@ -192,6 +207,8 @@ impl From<Error> for Diagnostic {
             Error::TooManyLocals(s) => Error::too_many_locals(s, d),
             Error::TooManyExports(s) => Error::too_many_exports(s, d),
             Error::TooManyImports(s) => Error::too_many_imports(s, d),
+
+            Error::NestedTooDeeply(s) => Error::nested_too_deeply(s, d),
         }
     }
 }
@@ -223,6 +240,11 @@ impl Error {
             .help("breaking this up with functions might help")
     }
 
+    fn return_not_in_function(s: Span, d: Diagnostic) -> Diagnostic {
+        d.highlight(s, "this `return` isn't in a function")
+            .info("top-level code has no caller to return to")
+    }
+
     fn jump_too_far(s: Span, d: Diagnostic) -> Diagnostic {
         d.highlight(s, "this value isn't allowed").info(
             "Unlike `break` or `return`, you can't give a value to `continue`",
@@ -265,6 +287,15 @@ impl Error {
             .help(help_text)
     }
 
+    fn nested_too_deeply(s: Span, d: Diagnostic) -> Diagnostic {
+        d.highlight(s, "this is where the limit was crossed")
+            .info(format!(
+                "expressions can only be nested {} deep",
+                ModuleBuilder::MAX_DEPTH
+            ))
+            .help("breaking this up with `let` bindings might help")
+    }
+
     fn too_many_params(s: Span, d: Diagnostic) -> Diagnostic {
         d.highlight(s, "this parameter is the culprit")
             .info(format!(
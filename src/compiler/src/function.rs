@@ -66,6 +66,11 @@ impl Function {
         &self.captures
     }
 
+    /// The function's code.
+    pub fn code(&self) -> &[Op] {
+        &self.code
+    }
+
     /// The function's name, if known.
     pub fn name(&self) -> Option<Index<Constant>> {
         self.name
@@ -117,7 +117,7 @@ impl ModuleBuilder {
 
         let end_span = syntax.block().close();
 
-        let jump_false = self.new_patch_obligation(end_span)?;
+        let jump_false = self.new_branch_obligation(end_span)?;
         self.block(syntax.block())?;
 
         let end = self.next_op(end_span)?;
@@ -128,7 +128,22 @@ impl ModuleBuilder {
     }
 
     /// Compile an expression
+    ///
+    /// Expressions are the only place the syntax tree can nest deeply, so this
+    /// is where we keep track of how deep we are to avoid blowing the stack.
     fn expression(&mut self, syntax: &syntax::Expression) -> Result<()> {
+        if self.depth >= ModuleBuilder::MAX_DEPTH {
+            return Err(Error::NestedTooDeeply(syntax.span()));
+        }
+
+        self.depth += 1;
+        let result = self.expression_inner(syntax);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn expression_inner(&mut self, syntax: &syntax::Expression) -> Result<()> {
         match syntax {
             syntax::Expression::Binary(b) => self.binary(b),
             syntax::Expression::Block(b) => self.block(b),
@@ -289,7 +304,7 @@ impl ModuleBuilder {
         let op_span = syntax.operator_span();
         self.expression(syntax.left())?;
 
-        let branch = self.new_patch_obligation(op_span)?;
+        let branch = self.new_branch_obligation(op_span)?;
 
         self.expression(syntax.right())?;
 
@@ -361,10 +376,29 @@ impl ModuleBuilder {
             self.emit(Op::Unit, syntax.span())?;
         }
 
+        // The code after an early exit can't be reached, but it's compiled as
+        // if the exit left its value on the stack like any other expression.
+        let height = self.current_function().stack_height();
+        self.early_exit_jump(syntax)?;
+        self.current_function_mut().set_stack_height(height);
+
+        Ok(())
+    }
+
+    /// Emit the ops which leave for an early exit, once its value is on the
+    /// top of the stack.
+    fn early_exit_jump(&mut self, syntax: &syntax::EarlyExit) -> Result<()> {
         match syntax.kind() {
-            syntax::ExitKind::Return => self.emit(Op::Return, syntax.span()),
+            syntax::ExitKind::Return => {
+                if self.compiling_main() {
+                    return Err(Error::ReturnNotInFunction(syntax.span()));
+                }
+
+                self.emit(Op::Return, syntax.span())
+            }
 
             syntax::ExitKind::Break => {
+                self.close_loop_bindings(syntax.span())?;
                 let jump = self.new_patch_obligation(syntax.span())?;
                 self.current_function_mut().register_break(jump)
             }
@@ -377,6 +411,7 @@ impl ModuleBuilder {
                     return Err(Error::ContinueWithValue(invalid.span()));
                 }
 
+                self.close_loop_bindings(syntax.span())?;
                 let jump = self.new_patch_obligation(syntax.span())?;
                 self.current_function_mut().register_continue(jump)
             }
@@ -384,6 +419,25 @@ impl ModuleBuilder {
         }
     }
 
+    /// Before a `break` or `continue` jumps, anything pushed since the loop's
+    /// body began needs to be removed from the stack, keeping the exit's value
+    /// on top. That's any bindings made inside the loop, but also the
+    /// temporaries of any expression the exit is part of, like the `1` in
+    /// `[1, break 2]`.
+    ///
+    /// ``` text
+    /// [ Close(n) ] // if there are `n` values above where the loop began
+    /// ```
+    fn close_loop_bindings(&mut self, span: Span) -> Result<()> {
+        let count = self.current_function().loop_stack_count();
+
+        if count > 0 {
+            self.emit(Op::Close(count as u32), span)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Compile a function, including it's name if known.
     fn function(
         &mut self,
@@ -466,7 +520,7 @@ impl ModuleBuilder {
         self.expression(syntax.condition())?;
 
         let branch_false =
-            self.new_patch_obligation(syntax.false_block().span())?;
+            self.new_branch_obligation(syntax.false_block().span())?;
 
         self.block(syntax.true_block())?;
 
@@ -518,13 +572,22 @@ impl ModuleBuilder {
 
     /// Compile a `loop` loop.
     ///
+    /// Like `while`, we keep the value of the last iteration on the stack so
+    /// that every jump back to the top leaves the stack the same height.
+    ///
     /// ``` text
+    ///   Unit               // placeholder for the first iteration's value
     /// top:
+    ///   Pop                // remove last value
     ///   <body>
     ///   Jump(top)
+    /// end:
+    ///   ...
     /// ```
     fn loop_loop(&mut self, syntax: &syntax::Loop) -> Result<()> {
+        self.emit(Op::Unit, syntax.loop_span())?;
         let top = self.next_op(syntax.loop_span())?;
+        self.emit(Op::Pop, syntax.loop_span())?;
 
         self.current_function_mut().begin_loop();
         self.block(syntax.body())?;
@@ -563,10 +626,12 @@ impl ModuleBuilder {
 
         self.emit(Op::Unit, condition_span)?;
         let top = self.next_op(condition_span)?;
+        let top_height = self.current_function().stack_height();
 
         self.expression(syntax.condition())?;
 
-        let condition_false_jump = self.new_patch_obligation(condition_span)?;
+        let condition_false_jump =
+            self.new_branch_obligation(condition_span)?;
 
         self.emit(Op::Pop, condition_span)?;
 
@@ -586,6 +651,8 @@ impl ModuleBuilder {
 
         self.patch(condition_false_jump, Op::BranchFalse(to_condition_false));
 
+        // The only way here is the branch, with the condition still on top.
+        self.current_function_mut().set_stack_height(top_height + 1);
         self.emit(Op::Pop, condition_span)?;
 
         let end = self.next_op(syntax.body().close())?;
@@ -11,7 +11,8 @@ use crate::{
         capture::Capture, code::Code, code_gen::jump_distance, local::Local,
     },
     opcode::Op,
-    Constant, Function, FunctionDebug,
+    verify::stack_effect,
+    Constant, Function, FunctionDebug, LocalDebug,
 };
 
 use super::module::PatchObligation;
@@ -20,6 +21,9 @@ use super::module::PatchObligation;
 struct LoopObligations {
     breaks: Vec<Index<PatchObligation>>,
     continues: Vec<Index<PatchObligation>>,
+
+    /// The height of the stack when the loop's body began.
+    height: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -31,8 +35,13 @@ pub(crate) struct FunctionBuilder {
     captures: Vec<Capture>,
     code: Code,
     locals: Vec<Local>,
+    local_debug: Vec<LocalDebug>,
     scopes: Vec<usize>,
     loops: Vec<LoopObligations>,
+
+    /// The height of the stack after the code emitted so far, relative to
+    /// where it was when the function began.
+    stack_height: usize,
 }
 
 impl FunctionBuilder {
@@ -46,8 +55,10 @@ impl FunctionBuilder {
             captures: Vec::new(),
             code: Code::default(),
             locals: Vec::default(),
+            local_debug: Vec::default(),
             scopes: vec![0],
             loops: Vec::default(),
+            stack_height: 0,
         }
     }
 
@@ -107,6 +118,11 @@ impl FunctionBuilder {
         self.parameter_count
     }
 
+    /// The scopes of every local bound in this function so far.
+    pub(crate) fn local_debug(&self) -> &[LocalDebug] {
+        &self.local_debug
+    }
+
     pub(crate) fn parameters(&self) -> &[Local] {
         &self.locals[0..(self.parameter_count() as usize)]
     }
@@ -124,9 +140,25 @@ impl FunctionBuilder {
 
     /// Emit into this function's code.
     pub(crate) fn emit(&mut self, op: Op, span: Span) -> Result<()> {
+        let (needed, pushed) = stack_effect(op);
+        self.stack_height = self.stack_height.saturating_sub(needed) + pushed;
         self.code.emit(op, span)
     }
 
+    /// The height of the stack after the code emitted so far.
+    ///
+    /// This only follows the ops in the order they're emitted, so where
+    /// control flow meets the code generator needs to set it with
+    /// [`FunctionBuilder::set_stack_height`].
+    pub(crate) fn stack_height(&self) -> usize {
+        self.stack_height
+    }
+
+    /// Set the height of the stack the next op will see.
+    pub(crate) fn set_stack_height(&mut self, height: usize) {
+        self.stack_height = height;
+    }
+
     /// The code listing for this function.
     pub(crate) fn code(&self) -> &Code {
         &self.code
@@ -163,6 +195,15 @@ impl FunctionBuilder {
 
         self.locals.truncate(total_in_scope - in_scope_count);
 
+        // The locals going out of scope are the most recent ones still open.
+        let end = self.code.next_index();
+        self.local_debug
+            .iter_mut()
+            .rev()
+            .filter(|l| l.end.is_none())
+            .take(in_scope_count)
+            .for_each(|l| l.end = end);
+
         if in_scope_count > 0 {
             self.emit(Op::Close(in_scope_count as u32), span)?;
         }
@@ -174,9 +215,24 @@ impl FunctionBuilder {
         self.loops.push(LoopObligations {
             breaks: Vec::new(),
             continues: Vec::new(),
+            height: self.stack_height,
         });
     }
 
+    /// The number of values on the stack under the top which were pushed since
+    /// the innermost loop's body began. This includes both local bindings and
+    /// any temporaries of the expressions we're in the middle of, like a
+    /// partly built list. These all need to be closed before a `break` or
+    /// `continue` jumps out of the loop.
+    ///
+    /// If we're not in a loop, this is zero.
+    pub(crate) fn loop_stack_count(&self) -> usize {
+        self.loops
+            .last()
+            .map(|obs| self.stack_height.saturating_sub(obs.height + 1))
+            .unwrap_or_default()
+    }
+
     pub(crate) fn end_loop(
         &mut self,
         start: Index<Op>,
@@ -208,6 +264,16 @@ impl FunctionBuilder {
             return Err(Error::TooManyLocals(local.span()));
         }
 
+        self.local_debug.push(LocalDebug {
+            name: local.as_str().to_owned(),
+            index: Index::new(self.locals.len() as u32),
+            start: self
+                .code
+                .next_index()
+                .expect("code length is checked when emitting"),
+            end: None,
+        });
+
         self.locals.push(local);
         Ok(())
     }
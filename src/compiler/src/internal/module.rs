@@ -8,6 +8,7 @@ use crate::{
     Capture, Constant, Function, Local, Module, Op,
 };
 
+#[derive(Clone)]
 pub struct ModuleBuilder {
     /// The input that was used to produce this code.
     id: Option<InputId>,
@@ -21,6 +22,9 @@ pub struct ModuleBuilder {
 
     /// Code is compiled into [`Function`]s which are kept here once complete.
     functions: Vec<Function>,
+
+    /// How many expressions deep the code being compiled is.
+    pub(crate) depth: usize,
 }
 
 impl Default for ModuleBuilder {
@@ -30,6 +34,7 @@ impl Default for ModuleBuilder {
             constants: Default::default(),
             compiling: Default::default(),
             functions: Default::default(),
+            depth: 0,
         };
 
         compiler.prime();
@@ -43,6 +48,12 @@ impl ModuleBuilder {
 
     const MAIN: usize = 0;
 
+    /// The deepest expressions can be nested.
+    ///
+    /// The parser doesn't limit how long chains of operators or calls can be,
+    /// and each link in the chain is another level of nesting.
+    pub const MAX_DEPTH: usize = 1024;
+
     /// Convert the current compiler state into a new [`Module`] that can be
     /// loaded into the runtime.
     pub fn build(&self) -> Module {
@@ -62,6 +73,7 @@ impl ModuleBuilder {
             input: None,
             constants: self.constants.as_vec(),
             functions,
+            verified: Default::default(),
         }
     }
 
@@ -78,6 +90,15 @@ impl ModuleBuilder {
         self.id = Some(id);
     }
 
+    /// The index of the op in `main` where code pushed next will begin.
+    ///
+    /// The code starts by popping the result of the input before it, so
+    /// running from here needs that result (or something standing in for it)
+    /// on the top of the stack.
+    pub fn entry_point(&self) -> Index<Op> {
+        Index::new(self.compiling[ModuleBuilder::MAIN].code().ops().len() as u32)
+    }
+
     /// Push some input through the module builder.
     ///
     /// This behaves the same way as [`ModuleBuilder::syntax`], but does the
@@ -132,7 +153,7 @@ impl ModuleBuilder {
         let old_function_count = self.functions.len();
         let old_constant_count = self.constants.len();
 
-        if let Err(e) = self.statement_sequence(syntax) {
+        if let Err(e) = self.continue_main(syntax) {
             // We need to recover on failure before we can return the error.
 
             self.compiling[0] = backup;
@@ -149,6 +170,18 @@ impl ModuleBuilder {
 }
 
 impl ModuleBuilder {
+    /// Compile more top-level code onto the end of `main`.
+    ///
+    /// Earlier input left its result on the stack, which needs to be thrown
+    /// away before carrying on so that the stack lines up with the locals.
+    fn continue_main(&mut self, syntax: &syntax::Module) -> Result<(), Error> {
+        if !self.current_function().code().ops().is_empty() {
+            self.emit(Op::Pop, syntax.span())?;
+        }
+
+        self.statement_sequence(syntax)
+    }
+
     /// Prime the compiler by defining a 'main' function at index zero that just
     /// halts.
     fn prime(&mut self) {
@@ -187,6 +220,11 @@ impl ModuleBuilder {
         self.current_function_mut().emit(op, span)
     }
 
+    /// Is the currently-compiling function `main`?
+    pub(crate) fn compiling_main(&self) -> bool {
+        self.compiling.len() == 1
+    }
+
     /// Get a reference to the currently-compiling function.
     pub(crate) fn current_function(&self) -> &FunctionBuilder {
         self.compiling.last().unwrap()
@@ -228,6 +266,18 @@ impl ModuleBuilder {
         Ok(Index::new(index.into()))
     }
 
+    /// Like [`ModuleBuilder::new_patch_obligation`], but for a branch that
+    /// will pop its condition when it isn't taken.
+    pub(crate) fn new_branch_obligation(
+        &mut self,
+        span: Span,
+    ) -> Result<Index<PatchObligation>, Error> {
+        let obligation = self.new_patch_obligation(span)?;
+        let function = self.current_function_mut();
+        function.set_stack_height(function.stack_height().saturating_sub(1));
+        Ok(obligation)
+    }
+
     pub(crate) fn with_scope<F, T>(
         &mut self,
         inner: F,
@@ -6,14 +6,16 @@ mod function;
 mod internal;
 mod module;
 mod opcode;
+mod verify;
 
 pub mod error;
 
 pub use crate::{
     constant::Constant,
-    debug::FunctionDebug,
+    debug::{AssembleError, AssembleErrorKind, FunctionDebug, LocalDebug},
     function::Function,
     internal::{Capture, Local, ModuleBuilder},
     module::Module,
     opcode::Op,
+    verify::{VerifyError, VerifyErrorKind},
 };
@@ -5,13 +5,19 @@
 use common::Index;
 use diagnostic::InputId;
 
-use crate::{constant::Constant, internal::ModuleBuilder, Function};
+use crate::{
+    constant::Constant, internal::ModuleBuilder, verify::Verified, Function,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Module {
     pub(crate) input: Option<InputId>,
     pub(crate) constants: Vec<Constant>,
     pub(crate) functions: Vec<Function>,
+
+    /// Set once the module passes verification. Anything which changes the
+    /// code after that needs to reset it.
+    pub(crate) verified: Verified,
 }
 
 impl Module {
@@ -0,0 +1,669 @@
+//! Bytecode verification.
+//!
+//! The runtime trusts the code it's given completely, so before a [`Module`]
+//! is loaded we check that running it can't index outside of the constant
+//! pool, function table, locals or captures, that jumps land inside the
+//! function, that the stack never underflows and has the same height whenever
+//! two paths through the code meet, and that execution can't run off the end of
+//! a function or return from `main`.
+//!
+//! This is done with a simple abstract interpretation of each function which
+//! only tracks the height of the stack above the function's base pointer.
+//!
+//! A module's code can't change once it's built, so it's only verified once.
+//! This matters when one module is shared between many VMs, which would
+//! otherwise each check the whole thing again when they load it.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use common::Index;
+
+use crate::{Capture, Constant, Function, Local, Module, Op};
+
+/// The reasons a [`Module`] can fail verification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyErrorKind {
+    /// The module has no functions, so it has no `main`.
+    NoMainFunction,
+
+    /// The module's `main` must not take parameters or capture values, since
+    /// there's nothing to provide them.
+    InvalidMain,
+
+    /// The module's `main` has a [`Op::Return`], but there's nothing for it to
+    /// return to.
+    ReturnFromMain,
+
+    /// A function has no code at all.
+    EmptyFunction,
+
+    /// A function's name isn't a constant in the module.
+    NameOutOfRange(Index<Constant>),
+
+    /// A [`Op::LoadConstant`] refers to a constant that doesn't exist.
+    ConstantOutOfRange(Index<Constant>),
+
+    /// A [`Op::LoadFunction`] refers to a function that doesn't exist.
+    FunctionOutOfRange(Index<Function>),
+
+    /// A local is used which isn't on the stack at that point.
+    LocalOutOfRange(Index<Local>),
+
+    /// A capture is used which the function doesn't have.
+    CaptureOutOfRange(Index<Capture>),
+
+    /// A jump or branch goes somewhere outside of the function.
+    JumpOutOfRange(i32),
+
+    /// An op needs more values than are on the stack.
+    StackUnderflow { needed: usize, found: usize },
+
+    /// Two paths through the code meet with different stack heights.
+    InconsistentStackDepth { expected: usize, found: usize },
+
+    /// Execution can continue past the last op of a function, instead of
+    /// ending with a [`Op::Return`] or [`Op::Halt`].
+    FallsOffEnd,
+}
+
+/// An error found while verifying a [`Module`], along with where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    function: Option<Index<Function>>,
+    op: Option<Index<Op>>,
+    kind: VerifyErrorKind,
+}
+
+impl VerifyError {
+    fn module(kind: VerifyErrorKind) -> VerifyError {
+        VerifyError {
+            function: None,
+            op: None,
+            kind,
+        }
+    }
+
+    fn function(function: Index<Function>, kind: VerifyErrorKind) -> Self {
+        VerifyError {
+            function: Some(function),
+            op: None,
+            kind,
+        }
+    }
+
+    fn op(
+        function: Index<Function>,
+        op: Index<Op>,
+        kind: VerifyErrorKind,
+    ) -> Self {
+        VerifyError {
+            function: Some(function),
+            op: Some(op),
+            kind,
+        }
+    }
+
+    /// The function the error was found in, if it's specific to one.
+    pub fn function_index(&self) -> Option<Index<Function>> {
+        self.function
+    }
+
+    /// The op the error was found at, if it's specific to one.
+    pub fn op_index(&self) -> Option<Index<Op>> {
+        self.op
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> &VerifyErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for VerifyErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use VerifyErrorKind::*;
+
+        match self {
+            NoMainFunction => write!(f, "module has no main function"),
+            InvalidMain => {
+                write!(f, "main cannot have parameters or captures")
+            }
+            ReturnFromMain => write!(f, "main cannot return"),
+            EmptyFunction => write!(f, "function has no code"),
+            NameOutOfRange(i) => write!(f, "name constant {i} out of range"),
+            ConstantOutOfRange(i) => write!(f, "constant {i} out of range"),
+            FunctionOutOfRange(i) => write!(f, "function {i} out of range"),
+            LocalOutOfRange(i) => write!(f, "local {i} out of range"),
+            CaptureOutOfRange(i) => write!(f, "capture {i} out of range"),
+            JumpOutOfRange(offset) => {
+                write!(f, "jump by {offset} leaves the function")
+            }
+            StackUnderflow { needed, found } => write!(
+                f,
+                "stack underflow, needed {needed} values but found {found}"
+            ),
+            InconsistentStackDepth { expected, found } => write!(
+                f,
+                "inconsistent stack depth, expected {expected} but found {found}"
+            ),
+            FallsOffEnd => write!(f, "execution can run past the last op"),
+        }
+    }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(function) = self.function {
+            write!(f, "function {function}")?;
+
+            if let Some(op) = self.op {
+                write!(f, ", op {op}")?;
+            }
+
+            write!(f, ": ")?;
+        }
+
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Whether a [`Module`] has passed verification already.
+///
+/// This isn't part of what the module is, so it doesn't affect equality, and
+/// a clone of a verified module is verified too.
+#[derive(Debug, Default)]
+pub(crate) struct Verified(AtomicBool);
+
+impl Clone for Verified {
+    fn clone(&self) -> Self {
+        Verified(AtomicBool::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl PartialEq for Verified {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Module {
+    /// Check that this module is safe for the runtime to load.
+    ///
+    /// See the [module documentation][self] for what's checked. Once a module
+    /// has passed, checking it again does nothing.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        if self.is_verified() {
+            return Ok(());
+        }
+
+        self.verify_functions()?;
+        self.verified.0.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Has this module already passed [verification][Module::verify]?
+    pub fn is_verified(&self) -> bool {
+        self.verified.0.load(Ordering::Relaxed)
+    }
+
+    /// The height of the stack above a function's base pointer when each of
+    /// its ops is about to run, or `None` for ops which can't be reached.
+    ///
+    /// This is worked out while [verifying][Module::verify], and fails the
+    /// same way if the function isn't valid. It's `None` if there's no such
+    /// function.
+    pub fn stack_depths(
+        &self,
+        function: Index<Function>,
+    ) -> Option<Result<Vec<Option<usize>>, VerifyError>> {
+        let code = self.functions.get(function.as_usize())?;
+        Some(FunctionVerifier::new(self, code, function).verify())
+    }
+
+    fn verify_functions(&self) -> Result<(), VerifyError> {
+        let main = self.functions.first().ok_or_else(|| {
+            VerifyError::module(VerifyErrorKind::NoMainFunction)
+        })?;
+
+        if main.parameter_count != 0 || !main.captures.is_empty() {
+            return Err(VerifyError::function(
+                Module::MAIN,
+                VerifyErrorKind::InvalidMain,
+            ));
+        }
+
+        for (i, function) in self.functions.iter().enumerate() {
+            FunctionVerifier::new(self, function, Index::new(i as u32))
+                .verify()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The state used while verifying a single [`Function`].
+struct FunctionVerifier<'a> {
+    module: &'a Module,
+    function: &'a Function,
+    index: Index<Function>,
+
+    /// The stack height on entry to each op, once it's known to be reachable.
+    depths: Vec<Option<usize>>,
+
+    /// Reachable ops which still need to be checked.
+    worklist: Vec<usize>,
+}
+
+impl<'a> FunctionVerifier<'a> {
+    fn new(
+        module: &'a Module,
+        function: &'a Function,
+        index: Index<Function>,
+    ) -> Self {
+        FunctionVerifier {
+            module,
+            function,
+            index,
+            depths: vec![None; function.code.len()],
+            worklist: Vec::new(),
+        }
+    }
+
+    /// Check the function, returning the stack depth at each op.
+    fn verify(mut self) -> Result<Vec<Option<usize>>, VerifyError> {
+        if let Some(name) = self.function.name {
+            if name.as_usize() >= self.module.constants.len() {
+                return Err(VerifyError::function(
+                    self.index,
+                    VerifyErrorKind::NameOutOfRange(name),
+                ));
+            }
+        }
+
+        if self.function.code.is_empty() {
+            return Err(VerifyError::function(
+                self.index,
+                VerifyErrorKind::EmptyFunction,
+            ));
+        }
+
+        // Parameters are the first locals on the stack.
+        let parameters = self.function.parameter_count as usize;
+        self.depths[0] = Some(parameters);
+        self.worklist.push(0);
+
+        while let Some(pc) = self.worklist.pop() {
+            let depth = self.depths[pc].expect("only reached ops are queued");
+
+            self.verify_op(pc, depth).map_err(|kind| {
+                VerifyError::op(self.index, op_index(pc), kind)
+            })?;
+        }
+
+        Ok(self.depths)
+    }
+
+    /// Check a single op, queueing up the ops it can continue to.
+    fn verify_op(
+        &mut self,
+        pc: usize,
+        depth: usize,
+    ) -> Result<(), VerifyErrorKind> {
+        let op = self.function.code[pc];
+
+        self.verify_indexes(op, depth)?;
+
+        let (needed, pushed) = stack_effect(op);
+
+        if depth < needed {
+            return Err(VerifyErrorKind::StackUnderflow {
+                needed,
+                found: depth,
+            });
+        }
+
+        let after = depth - needed + pushed;
+
+        match op {
+            Op::Return if self.index == Module::MAIN => {
+                Err(VerifyErrorKind::ReturnFromMain)
+            }
+
+            Op::Halt | Op::Return => Ok(()),
+
+            Op::Jump(offset) => self.flow_to(self.target(pc, offset)?, after),
+
+            // A branch which is taken leaves its condition on the stack, but
+            // one that isn't taken pops it.
+            Op::Branch(offset) | Op::BranchFalse(offset) => {
+                self.flow_to(self.target(pc, offset)?, depth)?;
+                self.fall_through(pc, after)
+            }
+
+            _ => self.fall_through(pc, after),
+        }
+    }
+
+    /// Check that any indexes used by an op are valid.
+    fn verify_indexes(
+        &self,
+        op: Op,
+        depth: usize,
+    ) -> Result<(), VerifyErrorKind> {
+        match op {
+            Op::LoadConstant(i)
+                if i.as_usize() >= self.module.constants.len() =>
+            {
+                Err(VerifyErrorKind::ConstantOutOfRange(i))
+            }
+
+            Op::LoadLocal(i) | Op::SetLocal(i) if i.as_usize() >= depth => {
+                Err(VerifyErrorKind::LocalOutOfRange(i))
+            }
+
+            Op::LoadCapture(i) | Op::SetCapture(i)
+                if i.as_usize() >= self.function.captures.len() =>
+            {
+                Err(VerifyErrorKind::CaptureOutOfRange(i))
+            }
+
+            Op::LoadFunction(i) => {
+                let loaded = self
+                    .module
+                    .functions
+                    .get(i.as_usize())
+                    .ok_or(VerifyErrorKind::FunctionOutOfRange(i))?;
+
+                // The new closure's captures are resolved against the frame
+                // that's loading it.
+                for capture in &loaded.captures {
+                    match capture {
+                        Capture::Local(l) if l.as_usize() >= depth => {
+                            return Err(VerifyErrorKind::LocalOutOfRange(*l))
+                        }
+                        Capture::Recapture(c)
+                            if c.as_usize() >= self.function.captures.len() =>
+                        {
+                            return Err(VerifyErrorKind::CaptureOutOfRange(*c))
+                        }
+                        _ => {}
+                    }
+                }
+
+                Ok(())
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    /// The index of the op a jump at `pc` by `offset` lands on.
+    fn target(&self, pc: usize, offset: i32) -> Result<usize, VerifyErrorKind> {
+        let target = pc as i64 + offset as i64;
+
+        if target < 0 || target >= self.function.code.len() as i64 {
+            Err(VerifyErrorKind::JumpOutOfRange(offset))
+        } else {
+            Ok(target as usize)
+        }
+    }
+
+    /// Continue on to the op after `pc`.
+    fn fall_through(
+        &mut self,
+        pc: usize,
+        depth: usize,
+    ) -> Result<(), VerifyErrorKind> {
+        if pc + 1 >= self.function.code.len() {
+            Err(VerifyErrorKind::FallsOffEnd)
+        } else {
+            self.flow_to(pc + 1, depth)
+        }
+    }
+
+    /// Record that `target` can be reached with the stack at `depth`.
+    fn flow_to(
+        &mut self,
+        target: usize,
+        depth: usize,
+    ) -> Result<(), VerifyErrorKind> {
+        match self.depths[target] {
+            None => {
+                self.depths[target] = Some(depth);
+                self.worklist.push(target);
+                Ok(())
+            }
+            Some(expected) if expected == depth => Ok(()),
+            Some(expected) => Err(VerifyErrorKind::InconsistentStackDepth {
+                expected,
+                found: depth,
+            }),
+        }
+    }
+}
+
+fn op_index(pc: usize) -> Index<Op> {
+    Index::new(pc as u32)
+}
+
+/// How many values an [`Op`] needs on the stack, and how many it leaves in
+/// their place.
+///
+/// Branches are described by what happens when they aren't taken.
+pub(crate) fn stack_effect(op: Op) -> (usize, usize) {
+    match op {
+        Op::Halt | Op::Nop | Op::Jump(_) => (0, 0),
+
+        Op::Dup => (1, 2),
+        Op::Pop => (1, 0),
+        Op::Close(n) => (n as usize + 1, 1),
+
+        Op::True
+        | Op::False
+        | Op::Unit
+        | Op::U48(_)
+        | Op::I48(_)
+        | Op::LoadSelf
+        | Op::LoadConstant(_)
+        | Op::LoadLocal(_)
+        | Op::LoadCapture(_)
+        | Op::LoadFunction(_)
+        | Op::DefineLocal => (0, 1),
+
+        Op::SetLocal(_) | Op::SetCapture(_) => (1, 1),
+        Op::SetIndex => (3, 1),
+
+        Op::Call(n) => (n as usize + 1, 1),
+        Op::Return => (1, 0),
+
+        Op::Branch(_) | Op::BranchFalse(_) => (1, 0),
+
+        Op::Not | Op::Neg => (1, 1),
+
+        Op::Index
+        | Op::Add
+        | Op::Sub
+        | Op::Mul
+        | Op::Div
+        | Op::Pow
+        | Op::Rem
+        | Op::BitAnd
+        | Op::BitOr
+        | Op::BitXOR
+        | Op::SHL
+        | Op::SHR
+        | Op::Eq
+        | Op::Ne
+        | Op::Gt
+        | Op::Ge
+        | Op::Lt
+        | Op::Le => (2, 1),
+
+        Op::List(n) => (n as usize, 1),
+
+        // A tag with no elements is left on the stack as is.
+        Op::Tuple(n, is_tagged) => (n as usize + is_tagged as usize, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(code: Vec<Op>) -> Module {
+        let mut module = Module::try_from("").unwrap();
+        module.functions[0].code = code;
+        module.functions[0].debug_info = None;
+        module
+    }
+
+    fn error_kind(module: &Module) -> VerifyErrorKind {
+        module.verify().unwrap_err().kind().clone()
+    }
+
+    #[test]
+    fn compiled_code_verifies() {
+        let module = Module::try_from(
+            "let f = (n) => { let x = n; loop { if x == 0 { break x; }; x = x - 1 } };
+            let y = 0;
+            let g = () => y;
+            while y < 3 { let z = 1; y = y + z; if y == 2 { continue; }; };
+            let h = () => loop { [1, (2, if y == 3 { break 3 } else { 4 })] };
+            while y < 6 { y = y + 1; [y, if y == 5 { continue; } else { 0 }] };
+            [f(3), g(), h(), :tag(1, 2), (1, 2)]",
+        )
+        .unwrap();
+
+        assert_eq!(module.verify(), Ok(()));
+        assert!(module.is_verified());
+    }
+
+    #[test]
+    fn verified_once() {
+        let module = Module::try_from("1 + 2").unwrap();
+        let copy = module.clone();
+        assert!(!copy.is_verified());
+
+        module.verify().unwrap();
+        assert_eq!(module, copy, "being verified isn't part of equality");
+        assert!(module.clone().is_verified());
+    }
+
+    #[test]
+    fn stack_depths() {
+        let module = module(vec![
+            Op::Unit,
+            Op::Unit,
+            Op::Add,
+            Op::Jump(2),
+            Op::Pop,
+            Op::Halt,
+        ]);
+
+        assert_eq!(
+            module.stack_depths(Module::MAIN),
+            Some(Ok(vec![Some(0), Some(1), Some(2), Some(1), None, Some(1)]))
+        );
+        assert_eq!(module.stack_depths(Index::new(1)), None);
+    }
+
+    #[test]
+    fn no_main() {
+        let mut module = module(vec![]);
+        module.functions.clear();
+        assert_eq!(error_kind(&module), VerifyErrorKind::NoMainFunction);
+    }
+
+    #[test]
+    fn return_from_main() {
+        let module = module(vec![Op::Unit, Op::Return]);
+        assert_eq!(error_kind(&module), VerifyErrorKind::ReturnFromMain);
+    }
+
+    #[test]
+    fn empty_function() {
+        let module = module(vec![]);
+        assert_eq!(error_kind(&module), VerifyErrorKind::EmptyFunction);
+    }
+
+    #[test]
+    fn constant_out_of_range() {
+        let module = module(vec![Op::LoadConstant(Index::new(99)), Op::Halt]);
+        assert_eq!(
+            error_kind(&module),
+            VerifyErrorKind::ConstantOutOfRange(Index::new(99))
+        );
+    }
+
+    #[test]
+    fn function_out_of_range() {
+        let module = module(vec![Op::LoadFunction(Index::new(1)), Op::Halt]);
+        assert_eq!(
+            error_kind(&module),
+            VerifyErrorKind::FunctionOutOfRange(Index::new(1))
+        );
+    }
+
+    #[test]
+    fn local_out_of_range() {
+        let module =
+            module(vec![Op::Unit, Op::LoadLocal(Index::new(1)), Op::Halt]);
+        assert_eq!(
+            error_kind(&module),
+            VerifyErrorKind::LocalOutOfRange(Index::new(1))
+        );
+    }
+
+    #[test]
+    fn capture_out_of_range() {
+        let module = module(vec![Op::LoadCapture(Index::START), Op::Halt]);
+        assert_eq!(
+            error_kind(&module),
+            VerifyErrorKind::CaptureOutOfRange(Index::START)
+        );
+    }
+
+    #[test]
+    fn jump_out_of_range() {
+        let module = module(vec![Op::Jump(-1), Op::Halt]);
+        assert_eq!(error_kind(&module), VerifyErrorKind::JumpOutOfRange(-1));
+    }
+
+    #[test]
+    fn stack_underflow() {
+        let module = module(vec![Op::Unit, Op::Add, Op::Halt]);
+        let error = module.verify().unwrap_err();
+        assert_eq!(error.op_index(), Some(Index::new(1)));
+        assert_eq!(
+            error.kind(),
+            &VerifyErrorKind::StackUnderflow {
+                needed: 2,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn inconsistent_depth() {
+        // Each trip around the loop leaves another value on the stack.
+        let module = module(vec![Op::Unit, Op::Jump(-1)]);
+        assert_eq!(
+            error_kind(&module),
+            VerifyErrorKind::InconsistentStackDepth {
+                expected: 0,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn falls_off_end() {
+        let module = module(vec![Op::Unit]);
+        assert_eq!(error_kind(&module), VerifyErrorKind::FallsOffEnd);
+    }
+}
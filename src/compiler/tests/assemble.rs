@@ -0,0 +1,70 @@
+//! Make sure the listings printed by `--dump` can be assembled back into the
+//! same module.
+
+use compiler::Module;
+
+fn round_trip(name: &str, module: &Module) {
+    let listing = module.to_string();
+
+    match Module::assemble(&listing) {
+        Ok(assembled) => assert_eq!(&assembled, module, "for {name}"),
+        Err(e) => panic!("{name} failed to assemble, {e}\n{listing}"),
+    }
+}
+
+#[test]
+fn runtime_inputs() {
+    let inputs =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../runtime/tests/inputs");
+
+    let mut count = 0;
+
+    for entry in std::fs::read_dir(inputs).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.extension().is_none_or(|e| e != "k") {
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&path).unwrap();
+        let module = Module::try_from(source.as_str()).unwrap();
+
+        round_trip(&path.display().to_string(), &module);
+        count += 1;
+    }
+
+    assert!(count > 0, "no inputs found in {inputs}");
+}
+
+#[test]
+fn strings_with_comments() {
+    let module = Module::try_from(r#"let f = () => "// not a comment"; f"#);
+    round_trip("strings_with_comments", &module.unwrap());
+}
+
+#[test]
+fn captures() {
+    let module = Module::try_from(
+        "let x = 1; let f = (y) => { let g = () => x + y; g }; f(2)()",
+    );
+    round_trip("captures", &module.unwrap());
+}
+
+#[test]
+fn hand_written() {
+    let listing = "
+        module {
+        constants:
+         000 | Float 2.5
+
+        function 000:
+         parameters 0
+         span 000:000-000:000
+         000 | LoadConstant 0 // comments are ignored
+         001 | Halt
+        }
+    ";
+
+    let module = Module::assemble(listing).unwrap();
+    assert!(module.verify().is_ok());
+}
@@ -64,6 +64,8 @@ mod branching {
     test_compile! { loop_continue, "loop { continue; }" }
 
     test_no_compile! { loop_no_continue_value, "loop { continue 6; }" }
+    test_no_compile! { top_level_return, "return 1" }
+    test_no_compile! { top_level_loop_return, "loop { return 1 }" }
 }
 
 mod assignment {
@@ -76,3 +78,9 @@ mod rec_data {
     // This should work, one day
     test_no_compile! { rec_data, "let rec f = [1, f];" }
 }
+
+mod nesting {
+    test_compile! { long_chain, format!("1{}", " + 1".repeat(1000)).as_str() }
+
+    test_no_compile! { chain_too_long, format!("1{}", " + 1".repeat(5000)).as_str() }
+}
@@ -0,0 +1,152 @@
+//! `#[kurt::class]`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{FnArg, ImplItem, ImplItemFn, ItemImpl, ReceiverKind, Type};
+
+use crate::signature::Arguments;
+
+pub fn expand(attr: TokenStream, item: ItemImpl) -> syn::Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "`#[kurt::class]` doesn't take any arguments",
+        ));
+    }
+
+    if let Some((path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`#[kurt::class]` goes on the type's own `impl` block",
+        ));
+    }
+
+    if item.generics.lt_token.is_some() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "classes used from Kurt can't be generic",
+        ));
+    }
+
+    let ty = &item.self_ty;
+    let name = type_name(ty)?;
+
+    let mut constructor = quote! { None };
+    let mut methods = Vec::new();
+
+    for function in item.items.iter().filter_map(|item| match item {
+        ImplItem::Fn(function) => Some(function),
+        _ => None,
+    }) {
+        match function.sig.inputs.first() {
+            Some(FnArg::Receiver(receiver)) => {
+                if !matches!(receiver.kind, ReceiverKind::Reference(_, _, None))
+                {
+                    return Err(syn::Error::new_spanned(
+                        receiver,
+                        "methods used from Kurt can only take `&self`, use a \
+                         `Cell` or `RefCell` for anything which changes",
+                    ));
+                }
+
+                methods.push(method(ty, function)?);
+            }
+
+            _ if function.sig.ident == "new" => {
+                constructor = new(ty, &name, function)?;
+            }
+
+            // Other associated functions aren't exposed.
+            _ => {}
+        }
+    }
+
+    Ok(quote! {
+        #item
+
+        impl ::runtime::classes::HostObject for #ty {
+            fn type_name(&self) -> &'static str {
+                <Self as ::runtime::native::NativeClass>::NAME
+            }
+
+            fn methods(
+                &self,
+            ) -> &'static [::runtime::native::BuiltinMethod] {
+                <Self as ::runtime::native::NativeClass>::METHODS
+            }
+        }
+
+        impl ::runtime::native::NativeClass for #ty {
+            const NAME: &'static str = #name;
+
+            const CONSTRUCTOR: Option<::runtime::native::Builtin> =
+                #constructor;
+
+            const METHODS: &'static [::runtime::native::BuiltinMethod] =
+                &[#(#methods),*];
+        }
+    })
+}
+
+/// The name of the type, without any path.
+fn type_name(ty: &Type) -> syn::Result<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .ok_or_else(|| syn::Error::new_spanned(ty, "expected a type")),
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "`#[kurt::class]` needs a named type",
+        )),
+    }
+}
+
+fn method(ty: &Type, function: &ImplItemFn) -> syn::Result<TokenStream> {
+    let arguments = Arguments::new(&function.sig)?;
+    let arity = arguments.arity();
+    let expressions = arguments.expressions();
+    let args = arguments.pattern();
+
+    let ident = &function.sig.ident;
+    let name = ident.to_string();
+
+    Ok(quote! {
+        ::runtime::native::BuiltinMethod {
+            name: #name,
+            arity: #arity,
+            call: |this, #args, vm| {
+                let this = this
+                    .downcast_ref::<#ty>()
+                    .expect("methods are only found on their own class");
+
+                let result = <#ty>::#ident(this, #(#expressions),*);
+                ::runtime::native::NativeReturn::into_result(result, vm)
+            },
+        }
+    })
+}
+
+fn new(
+    ty: &Type,
+    name: &str,
+    function: &ImplItemFn,
+) -> syn::Result<TokenStream> {
+    let arguments = Arguments::new(&function.sig)?;
+    let arity = arguments.arity();
+    let expressions = arguments.expressions();
+    let args = arguments.pattern();
+
+    Ok(quote! {
+        Some(::runtime::native::Builtin {
+            name: #name,
+            arity: #arity,
+            call: |#args, vm| {
+                let result = <#ty>::new(#(#expressions),*);
+                ::runtime::native::NewObject::<#ty>::new_object(result, vm)
+            },
+        })
+    })
+}
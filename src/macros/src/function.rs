@@ -0,0 +1,47 @@
+//! `#[kurt::function]`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ItemFn;
+
+use crate::signature::Arguments;
+
+pub fn expand(attr: TokenStream, item: ItemFn) -> syn::Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "`#[kurt::function]` doesn't take any arguments",
+        ));
+    }
+
+    let arguments = Arguments::new(&item.sig)?;
+    let arity = arguments.arity();
+    let expressions = arguments.expressions();
+    let args = arguments.pattern();
+
+    let vis = &item.vis;
+    let ident = &item.sig.ident;
+    let name = ident.to_string();
+
+    Ok(quote! {
+        #item
+
+        // The type has the same name as the function so that it can be
+        // named in a library. Types and functions don't clash.
+        #[allow(non_camel_case_types)]
+        #[doc(hidden)]
+        #vis struct #ident {}
+
+        impl ::runtime::native::NativeFunction for #ident {
+            const BUILTIN: ::runtime::native::Builtin =
+                ::runtime::native::Builtin {
+                    name: #name,
+                    arity: #arity,
+                    call: |#args, vm| {
+                        let result = #ident(#(#expressions),*);
+                        ::runtime::native::NativeReturn::into_result(result, vm)
+                    },
+                };
+        }
+    })
+}
@@ -0,0 +1,73 @@
+//! Procedural macros for exposing Rust functions and types to Kurt.
+//!
+//! Depend on this crate as `kurt`, so the attributes read as
+//! `#[kurt::function]` and `#[kurt::class]`:
+//!
+//! ```toml
+//! [dependencies]
+//! kurt = { package = "kurt-macros", path = "../macros" }
+//! runtime = { path = "../runtime" }
+//! ```
+//!
+//! The generated code refers to the `runtime` crate by that name. What it
+//! generates is put together into a [`Library`] and installed in one go:
+//!
+//! ```ignore
+//! #[kurt::function]
+//! fn add(a: i64, b: i64) -> i64 {
+//!     a + b
+//! }
+//!
+//! let math = Library::new("math").function::<add>();
+//! let value = vm.install(&math)?;
+//! ```
+//!
+//! [`Library`]: https://docs.rs/runtime/latest/runtime/native/struct.Library.html
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, ItemFn, ItemImpl};
+
+mod class;
+mod function;
+mod signature;
+
+/// Expose a Rust function to Kurt code.
+///
+/// The function stays as it is, and a type with the same name is added which
+/// implements `runtime::native::NativeFunction`, so it can be added to a
+/// library with `Library::function::<name>()`.
+///
+/// Each argument is converted with `FromValue`, and the result with
+/// `IntoValue`. The function can also return a `runtime::Result` of something
+/// which converts, and it can take a `&mut VirtualMachine` anywhere in its
+/// arguments, which isn't counted as one of the arguments Kurt code passes.
+#[proc_macro_attribute]
+pub fn function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = proc_macro2::TokenStream::from(attr);
+    let item = parse_macro_input!(item as ItemFn);
+
+    function::expand(attr, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Expose a Rust type to Kurt code, as a foreign object with methods.
+///
+/// This goes on an `impl` block for the type. Each method taking `&self`
+/// becomes a method Kurt code can call, like `object[:name](args)`, and an
+/// associated function called `new` becomes the constructor. Arguments and
+/// results are converted like they are for [`macro@function`].
+///
+/// The type gets implementations of `runtime::classes::HostObject` and
+/// `runtime::native::NativeClass`, so it can be added to a library with
+/// `Library::class::<Type>()`. Methods can't take `&mut self`, since objects
+/// are shared; use a `Cell` or `RefCell` for anything which changes.
+#[proc_macro_attribute]
+pub fn class(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = proc_macro2::TokenStream::from(attr);
+    let item = parse_macro_input!(item as ItemImpl);
+
+    class::expand(attr, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,110 @@
+//! Converting arguments for functions and methods.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{spanned::Spanned, FnArg, Signature, Type};
+
+/// One of a function's arguments, not counting `self`.
+enum Argument {
+    /// An argument passed in from Kurt code, of this type.
+    Value(Box<Type>),
+
+    /// The `&mut VirtualMachine` the function is running in.
+    VirtualMachine,
+}
+
+/// The arguments of a function, and how to get them.
+pub struct Arguments {
+    arguments: Vec<Argument>,
+}
+
+impl Arguments {
+    /// Read the arguments from a function's signature. A `self` argument is
+    /// skipped, since it's handled by whoever's calling.
+    pub fn new(signature: &Signature) -> syn::Result<Arguments> {
+        if let Some(generics) = signature.generics.lt_token {
+            return Err(syn::Error::new(
+                generics.span(),
+                "functions used from Kurt can't be generic",
+            ));
+        }
+
+        if let Some(asyncness) = signature.asyncness {
+            return Err(syn::Error::new(
+                asyncness.span(),
+                "functions used from Kurt can't be async",
+            ));
+        }
+
+        let arguments = signature
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                FnArg::Receiver(_) => None,
+                FnArg::Typed(typed) if is_virtual_machine(&typed.ty) => {
+                    Some(Argument::VirtualMachine)
+                }
+                FnArg::Typed(typed) => Some(Argument::Value(typed.ty.clone())),
+            })
+            .collect();
+
+        Ok(Arguments { arguments })
+    }
+
+    /// How many arguments Kurt code passes.
+    pub fn arity(&self) -> u32 {
+        self.arguments
+            .iter()
+            .filter(|argument| matches!(argument, Argument::Value(_)))
+            .count() as u32
+    }
+
+    /// The pattern for the `args` slice in the generated closure.
+    pub fn pattern(&self) -> TokenStream {
+        if self.arity() == 0 {
+            quote! { _ }
+        } else {
+            quote! { args }
+        }
+    }
+
+    /// The expressions for each argument, converted from the `args` slice
+    /// and with `vm` passed through.
+    pub fn expressions(&self) -> Vec<TokenStream> {
+        let mut index = 0usize;
+
+        self.arguments
+            .iter()
+            .map(|argument| match argument {
+                Argument::Value(ty) => {
+                    let expression = quote! {
+                        <#ty as ::runtime::FromValue>::from_value(
+                            args[#index],
+                        )?
+                    };
+                    index += 1;
+                    expression
+                }
+                Argument::VirtualMachine => quote! { &mut *vm },
+            })
+            .collect()
+    }
+}
+
+/// Is `ty` a `&mut VirtualMachine`?
+fn is_virtual_machine(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+
+    let Type::Path(path) = &*reference.elem else {
+        return false;
+    };
+
+    reference.mutability.is_some()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "VirtualMachine")
+}
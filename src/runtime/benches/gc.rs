@@ -0,0 +1,69 @@
+//! Compare the generational collector with plain mark-sweep.
+//!
+//! The old mark-sweep collector isn't around to run anymore, so the closest
+//! stand-in is the new one with no nursery and marking done in one slice,
+//! using the old collector's capacity and growth settings (which are still the
+//! defaults). That's a stop-the-world mark-sweep, but it isn't exactly the old
+//! code: every store still goes through the write barrier's checks, it just
+//! never has anything to remember or shade. So this measures what the nursery
+//! buys, not what the barriers cost.
+//!
+//! Run with `cargo bench -p runtime --bench gc`.
+
+use std::time::{Duration, Instant};
+
+use compiler::Module;
+use runtime::{GcConfig, VirtualMachine};
+
+const RUNS: u32 = 10;
+
+/// Lots of short-lived lists, with a few kept around.
+const CHURN: &str = "\
+let keep = [[0], [0], [0], [0]];
+let rec churn = (n) => if n == 0 { keep } else {
+    [n, [n, n], [n, n, n]];
+    keep[n % 4] = [n];
+    churn(n - 1)
+};
+churn(20000)";
+
+/// A long list built up one element at a time, which all survives.
+const NEST: &str = "\
+let rec nest = (n, list) => if n == 0 { list } else { nest(n - 1, [n, list]) };
+nest(20000, [])";
+
+/// The settings closest to the old mark-sweep collector.
+fn mark_sweep() -> GcConfig {
+    GcConfig {
+        nursery_capacity: 0,
+        mark_slice: usize::MAX,
+        ..GcConfig::default()
+    }
+}
+
+fn time(config: GcConfig, input: &str) -> Duration {
+    let mut total = Duration::ZERO;
+
+    for _ in 0..RUNS {
+        let module = Module::try_from(input).unwrap();
+        let mut vm = VirtualMachine::with_gc_config(config);
+
+        let start = Instant::now();
+        vm.load(module).unwrap();
+        total += start.elapsed();
+    }
+
+    total / RUNS
+}
+
+fn main() {
+    for (name, input) in [("churn", CHURN), ("nest", NEST)] {
+        let generational = time(GcConfig::default(), input);
+        let mark_sweep = time(mark_sweep(), input);
+
+        println!(
+            "{:<8} generational: {:>10.3?}   mark-sweep: {:>10.3?}",
+            name, generational, mark_sweep
+        );
+    }
+}
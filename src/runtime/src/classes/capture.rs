@@ -12,6 +12,7 @@ use common::Index;
 
 use crate::{
     memory::*, primitives::PrimitiveOperations, value::Value, vm::Stack,
+    VirtualMachine,
 };
 
 #[derive(Clone, Copy)]
@@ -80,8 +81,16 @@ impl CaptureCell {
         self.contents.get()
     }
 
-    pub(crate) fn close(&self, value: Value) {
+    pub(crate) fn close(&self, value: Value, vm: &mut VirtualMachine) {
         self.contents.replace(CaptureCellContents::Inline(value));
+        vm.write_barrier(&self.base, value);
+    }
+
+    /// Point an open cell at a different stack slot, because what it's
+    /// capturing has moved.
+    pub(crate) fn move_to(&self, index: Index<Stack>) {
+        debug_assert!(self.stack_index().is_some(), "only open cells can move");
+        self.contents.replace(CaptureCellContents::Stack(index));
     }
 }
 
@@ -0,0 +1,222 @@
+//! Host values, passed into Kurt code opaquely.
+//!
+//! A [`Foreign`] object boxes up any Rust value which implements
+//! [`HostObject`]. Kurt code can pass it around like any other value, and use
+//! whichever operations the host implements for it. When it's collected, the
+//! Rust value is dropped.
+
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    ptr::addr_of_mut,
+};
+
+use crate::{
+    memory::*,
+    native::{self, BoundMethod, BuiltinMethod},
+    primitives::PrimitiveOperations,
+    value::Value,
+    Error, Result, VirtualMachine,
+};
+
+/// Rust values which can be put in a [`Foreign`] object.
+///
+/// Only [`HostObject::type_name`] is required. The operations which aren't
+/// implemented are errors, and foreign objects are only equal to themselves.
+pub trait HostObject: Any {
+    /// The name of the type, as Kurt code sees it.
+    fn type_name(&self) -> &'static str;
+
+    /// The methods Kurt code can call, like `object[:name](args)`.
+    ///
+    /// These are usually made by `#[kurt::class]`. They're looked up before
+    /// [`HostObject::index`] is called.
+    fn methods(&self) -> &'static [BuiltinMethod] {
+        &[]
+    }
+
+    /// Subscript the object, like `object[key]`.
+    fn index(&self, key: Value, vm: &mut VirtualMachine) -> Result<Value> {
+        let _ = (key, vm);
+        Err(Error::OperationNotSupported {
+            type_name: self.type_name(),
+            op_name: "index",
+        })
+    }
+
+    /// Assign through a subscript, like `object[key] = value`.
+    ///
+    /// If this stores `value`, it needs to call
+    /// [`VirtualMachine::foreign_write_barrier`].
+    fn set_index(
+        &self,
+        key: Value,
+        value: Value,
+        vm: &mut VirtualMachine,
+    ) -> Result<()> {
+        let _ = (key, value, vm);
+        Err(Error::OperationNotSupported {
+            type_name: self.type_name(),
+            op_name: "set_index",
+        })
+    }
+
+    /// Call the object like a function, like `object(args)`.
+    ///
+    /// The arguments stay alive until this returns.
+    fn call(&self, args: &[Value], vm: &mut VirtualMachine) -> Result<Value> {
+        let _ = (args, vm);
+        Err(Error::OperationNotSupported {
+            type_name: self.type_name(),
+            op_name: "call",
+        })
+    }
+
+    /// Is this equal to `other`, which is the value in another foreign
+    /// object?
+    ///
+    /// This is only called for two different objects, an object is always
+    /// equal to itself.
+    fn eq(&self, other: &dyn Any) -> bool {
+        let _ = other;
+        false
+    }
+
+    /// Add any [`Value`]s this holds to the work list, so that the collector
+    /// keeps them alive.
+    ///
+    /// Values stored after the object is made also need a
+    /// [`VirtualMachine::foreign_write_barrier`].
+    fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        let _ = worklist;
+    }
+}
+
+#[repr(C, align(8))]
+pub struct Foreign {
+    base: Object,
+    value: Box<dyn HostObject>,
+}
+
+impl Foreign {
+    /// The host value, if it's a `T`.
+    pub fn downcast_ref<T: HostObject>(&self) -> Option<&T> {
+        let value: &dyn Any = self.value.as_ref();
+        value.downcast_ref()
+    }
+
+    /// Is the host value a `T`?
+    pub fn is<T: HostObject>(&self) -> bool {
+        self.downcast_ref::<T>().is_some()
+    }
+
+    /// Call the host value as a function.
+    pub(crate) fn call(
+        &self,
+        args: &[Value],
+        vm: &mut VirtualMachine,
+    ) -> Result<Value> {
+        self.value.call(args, vm)
+    }
+}
+
+impl Class for Foreign {
+    const ID: ClassId = ClassId::Foreign;
+}
+
+impl Trace for Foreign {
+    fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        self.value.enqueue_gc_references(worklist);
+    }
+}
+
+impl PartialEq for Foreign {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+            || HostObject::eq(self.value.as_ref(), other.value.as_ref())
+    }
+}
+
+impl PartialOrd for Foreign {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        if self == other {
+            Some(std::cmp::Ordering::Equal)
+        } else {
+            None
+        }
+    }
+}
+
+impl Debug for Foreign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}>", self.value.type_name())
+    }
+}
+
+impl InitFrom<Box<dyn HostObject>> for Foreign {
+    fn extra_size(_arg: &Box<dyn HostObject>) -> usize {
+        0
+    }
+
+    unsafe fn init(ptr: *mut Self, arg: Box<dyn HostObject>) {
+        addr_of_mut!((*ptr).value).write(arg);
+    }
+}
+
+impl PrimitiveOperations for Foreign {
+    fn type_name(&self) -> &'static str {
+        self.value.type_name()
+    }
+
+    fn index(&self, key: Value, vm: &mut VirtualMachine) -> Result<Value> {
+        let method = self
+            .value
+            .methods()
+            .iter()
+            .find(|method| native::is_named(key, method.name));
+
+        if let Some(method) = method {
+            let receiver = GcAny::from(&self.base)
+                .as_a::<Foreign>()
+                .expect("a foreign object is a foreign object");
+
+            let bound = vm.make_foreign(BoundMethod { receiver, method })?;
+            return Ok(Value::gc(bound));
+        }
+
+        self.value.index(key, vm)
+    }
+
+    fn set_index(
+        &self,
+        key: Value,
+        new: Value,
+        vm: &mut VirtualMachine,
+    ) -> Result<()> {
+        self.value.set_index(key, new, vm)
+    }
+
+    fn cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        PartialOrd::partial_cmp(self, other)
+    }
+}
+
+impl VirtualMachine {
+    /// Put a host value in a new [`Foreign`] object.
+    pub fn make_foreign<T: HostObject>(
+        &mut self,
+        value: T,
+    ) -> Result<Gc<Foreign>> {
+        self.make_from(Box::new(value) as Box<dyn HostObject>)
+    }
+
+    /// Let the collector know that a host value stored `value` after its
+    /// [`Foreign`] object was made.
+    pub fn foreign_write_barrier(
+        &mut self,
+        foreign: Gc<Foreign>,
+        value: Value,
+    ) {
+        self.write_barrier(&foreign.base, value);
+    }
+}
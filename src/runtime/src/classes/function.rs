@@ -3,7 +3,6 @@
 use std::{
     cell::RefCell,
     fmt::{self, Debug},
-    ops::DerefMut,
     ptr::addr_of_mut,
 };
 
@@ -15,7 +14,7 @@ use crate::{
     classes::{CaptureCell, Module},
     memory::*,
     primitives::PrimitiveOperations,
-    Value,
+    Result, Value, VirtualMachine,
 };
 
 use super::Prototype;
@@ -45,8 +44,15 @@ impl Function {
         self.prototype().name()
     }
 
-    pub(crate) fn push_capture_cell(&self, cell: Gc<CaptureCell>) {
-        self.captures.borrow_mut().deref_mut().push(cell);
+    /// Add a capture to the closure, while it's being made.
+    ///
+    /// This can collect garbage, so the closure must be rooted.
+    pub(crate) fn push_capture_cell(
+        &self,
+        cell: Gc<CaptureCell>,
+        vm: &mut VirtualMachine,
+    ) -> Result<()> {
+        vm.push_owned(&self.base, &self.captures, cell)
     }
 
     pub fn get_capture_cell(&self, index: Index<Capture>) -> Gc<CaptureCell> {
@@ -72,6 +78,11 @@ impl Function {
 
 impl Class for Function {
     const ID: ClassId = ClassId::Closure;
+
+    fn owned_size(&self) -> usize {
+        let capacity = self.captures.borrow().capacity();
+        capacity * std::mem::size_of::<Gc<CaptureCell>>()
+    }
 }
 
 impl PartialOrd for Function {
@@ -98,8 +109,11 @@ impl PartialEq for Function {
 
 impl Trace for Function {
     fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        worklist.enqueue(self.prototype);
+
+        // The cells themselves need to be marked, not just their contents.
         for capture in self.captures.borrow().iter() {
-            capture.enqueue_gc_references(worklist);
+            worklist.enqueue(*capture);
         }
     }
 }
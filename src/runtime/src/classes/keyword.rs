@@ -1,8 +1,16 @@
 //! Lisp-style `:foo` keywords
+//!
+//! Keywords are interned, so there's only ever one [`Keyword`] with a given
+//! name at a time, and two keywords are the same if they're at the same
+//! address. The VM only holds on to them weakly, so keywords which aren't used
+//! anymore are still collected.
 
 use std::fmt::{self, Debug};
 
-use crate::{classes::String, memory::*, primitives::PrimitiveOperations};
+use crate::{
+    classes::String, memory::*, primitives::PrimitiveOperations, Result,
+    VirtualMachine,
+};
 
 /// In many dynamic languages, strings are used both to represent text and also
 /// to serve as token values for things like enumerations and dictionaries.
@@ -19,7 +27,8 @@ impl Class for Keyword {
 
 impl PartialEq for Keyword {
     fn eq(&self, other: &Self) -> bool {
-        self.as_str() == other.as_str()
+        // Keywords are interned, so there's no need to look at the strings.
+        std::ptr::eq(self, other)
     }
 }
 
@@ -69,6 +78,20 @@ impl Keyword {
     }
 }
 
+impl VirtualMachine {
+    /// The keyword named `name`, which is only allocated if there isn't one
+    /// already.
+    pub fn keyword(&mut self, name: &str) -> Result<Gc<Keyword>> {
+        if let Some(keyword) = self.gc_state.keywords.get(name) {
+            return Ok(*keyword);
+        }
+
+        let keyword: Gc<Keyword> = self.make_from(name)?;
+        self.gc_state.keywords.insert(name.into(), keyword);
+        Ok(keyword)
+    }
+}
+
 impl Debug for Keyword {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, ":{}", self.string.as_str())
@@ -7,7 +7,10 @@ use std::{
     ptr::addr_of_mut,
 };
 
-use crate::{memory::*, primitives::PrimitiveOperations, value::Value, Error};
+use crate::{
+    memory::*, primitives::PrimitiveOperations, value::Value, Error,
+    VirtualMachine,
+};
 
 #[repr(C, align(8))]
 pub struct List {
@@ -44,6 +47,22 @@ impl List {
         }
     }
 
+    /// A copy of the list's elements.
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.elements.borrow().clone()
+    }
+
+    /// Add an element to the end of the list.
+    ///
+    /// This can collect garbage if the list grows, so the list must be rooted.
+    pub(crate) fn push(
+        &self,
+        value: Value,
+        vm: &mut VirtualMachine,
+    ) -> Result<(), Error> {
+        vm.push_owned(&self.base, &self.elements, value)
+    }
+
     /// Subscript the list by a value.
     pub fn index(&self, index: Value) -> Result<Value, Error> {
         let slot = self.slot(index)?;
@@ -51,19 +70,28 @@ impl List {
         Ok(value)
     }
 
+    /// Replace an element of the list.
+    ///
+    /// This needs the VM the list belongs to, so it can tell the collector.
     pub fn set_index(
         &self,
         index: Value,
         new_value: Value,
+        vm: &mut VirtualMachine,
     ) -> Result<(), Error> {
         let slot = self.slot(index)?;
         self.elements.borrow_mut()[slot] = new_value;
+        vm.write_barrier(&self.base, new_value);
         Ok(())
     }
 }
 
 impl Class for List {
     const ID: ClassId = ClassId::List;
+
+    fn owned_size(&self) -> usize {
+        self.elements.borrow().capacity() * std::mem::size_of::<Value>()
+    }
 }
 
 impl PartialEq for List {
@@ -100,6 +128,10 @@ impl InitFrom<Vec<Value>> for List {
         0
     }
 
+    fn owned_size_from(arg: &Vec<Value>) -> usize {
+        arg.capacity() * std::mem::size_of::<Value>()
+    }
+
     unsafe fn init(ptr: *mut Self, arg: Vec<Value>) {
         addr_of_mut!((*ptr).elements).write(RefCell::new(arg));
     }
@@ -113,7 +145,7 @@ impl PrimitiveOperations for List {
     fn index(
         &self,
         key: Value,
-        _: &mut crate::VirtualMachine,
+        _: &mut VirtualMachine,
     ) -> Result<Value, Error> {
         self.index(key)
     }
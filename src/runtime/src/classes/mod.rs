@@ -1,4 +1,5 @@
 mod capture;
+mod foreign;
 mod function;
 mod keyword;
 mod list;
@@ -6,9 +7,11 @@ mod module;
 mod prototype;
 mod string;
 mod tuple;
+mod weak;
 
 pub use self::{
     capture::{CaptureCell, CaptureCellContents},
+    foreign::{Foreign, HostObject},
     function::Function,
     keyword::Keyword,
     list::List,
@@ -16,4 +19,5 @@ pub use self::{
     prototype::Prototype,
     string::String,
     tuple::Tuple,
+    weak::Weak,
 };
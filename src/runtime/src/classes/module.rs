@@ -1,10 +1,15 @@
 //! Runtime representation of a module.
 //!
-//! For now it's just a GC wrapper around [`compiler::Module`].
+//! The code itself is a [`compiler::Module`] behind an [`Arc`], so any number
+//! of VMs can share it. Each VM only makes what it needs of its own: a
+//! [`Prototype`] for each function, which points back into the shared code,
+//! and the values of the constants, which are inflated the first time they're
+//! used.
 
 use std::{
     fmt::{self, Debug, Formatter},
     ptr::addr_of_mut,
+    sync::Arc,
 };
 
 use common::{Get, Index};
@@ -15,7 +20,7 @@ use crate::{
     classes::Prototype,
     memory::{Class, ClassId, Gc, GcAny, InitFrom, Object, Trace},
     primitives::PrimitiveOperations,
-    Value, VirtualMachine,
+    Result, Value, VirtualMachine,
 };
 
 #[derive(PartialEq)]
@@ -23,9 +28,11 @@ use crate::{
 pub struct Module {
     base: Object,
 
-    id: Option<InputId>,
     name: Value,
-    constants: Vec<Value>,
+    code: Arc<compiler::Module>,
+
+    /// The constants which have been inflated so far.
+    constants: Vec<Option<Value>>,
     prototypes: Vec<Gc<Prototype>>,
 }
 
@@ -37,11 +44,10 @@ impl Module {
     ///
     /// This sucks. I'm _really_ hoping that eventual cleaning up of the whole
     /// GC makes this a normal part of initialization.
-    pub(crate) unsafe fn destructively_set_up_from_compiler_module(
+    pub(crate) unsafe fn destructively_set_up_prototypes(
         gc: Gc<Module>,
-        module: compiler::Module,
         vm: &mut VirtualMachine,
-    ) {
+    ) -> Result<()> {
         let live_module = gc.deref_mut();
 
         debug_assert!(
@@ -49,20 +55,53 @@ impl Module {
             "modules should only be set up once"
         );
 
-        debug_assert!(
-            live_module.constants.is_empty(),
-            "modules should only be set up once"
-        );
+        let code = live_module.code.clone();
+
+        for (i, function) in code.functions().iter().enumerate() {
+            // Names are needed without a VM to inflate them, for debugging.
+            if let Some(name) = function.name() {
+                vm.module_constant(gc, name)?;
+            }
 
-        for constant in module.constants() {
-            let value = vm.inflate(constant);
-            live_module.constants.push(value);
+            let index = Index::new(i as u32);
+            let prototype = vm.make_from((gc, index))?;
+            live_module.prototypes.push(prototype);
+            vm.write_barrier(&live_module.base, Value::from(prototype));
         }
 
-        for function in module.functions() {
-            live_module
-                .prototypes
-                .push(vm.make_from((gc, function.to_owned())))
+        Ok(())
+    }
+
+    /// Swap the code, constants and prototypes of two modules, moving the
+    /// prototypes along with their code.
+    ///
+    /// # Safety
+    ///
+    /// This (unsafely) mutates both [`Module`] objects and all their
+    /// prototypes, so nothing else can be looking at them.
+    pub(crate) unsafe fn destructively_swap(
+        a: Gc<Module>,
+        b: Gc<Module>,
+        vm: &mut VirtualMachine,
+    ) {
+        assert!(!std::ptr::eq(&*a, &*b), "a module can't swap with itself");
+
+        let (live_a, live_b) = (a.deref_mut(), b.deref_mut());
+        std::mem::swap(&mut live_a.code, &mut live_b.code);
+        std::mem::swap(&mut live_a.constants, &mut live_b.constants);
+        std::mem::swap(&mut live_a.prototypes, &mut live_b.prototypes);
+
+        for gc in [a, b] {
+            let live_module = gc.deref_mut();
+
+            for &value in live_module.constants.iter().flatten() {
+                vm.write_barrier(&live_module.base, value);
+            }
+
+            for &prototype in &live_module.prototypes {
+                vm.write_barrier(&live_module.base, Value::from(prototype));
+                Prototype::destructively_move_to(prototype, gc, vm);
+            }
         }
     }
 
@@ -80,11 +119,52 @@ impl Module {
     }
 
     pub fn id(&self) -> Option<InputId> {
-        self.id
+        self.code.input()
+    }
+
+    /// The compiled code, which might be shared with other VMs.
+    pub fn code(&self) -> &Arc<compiler::Module> {
+        &self.code
     }
 
+    /// The value of a constant, if it's been inflated yet.
+    ///
+    /// Use [`VirtualMachine::module_constant`] to inflate it if it hasn't.
     pub fn constant(&self, index: Index<Constant>) -> Option<Value> {
-        self.constants.get(index.as_usize()).copied()
+        self.constants.get(index.as_usize()).copied().flatten()
+    }
+}
+
+impl VirtualMachine {
+    /// The value of one of `module`'s constants, inflating it the first time
+    /// it's needed.
+    pub(crate) fn module_constant(
+        &mut self,
+        module: Gc<Module>,
+        index: Index<Constant>,
+    ) -> Result<Value> {
+        if let Some(value) = module.constant(index) {
+            return Ok(value);
+        }
+
+        let value = self.inflate(&module.code[index])?;
+        self.set_module_constant(module, index, value);
+
+        Ok(value)
+    }
+
+    /// Set the value of one of `module`'s constants.
+    pub(crate) fn set_module_constant(
+        &mut self,
+        module: Gc<Module>,
+        index: Index<Constant>,
+        value: Value,
+    ) {
+        // SAFETY: Nothing else is looking at the constants, and the module's
+        //         reachable from whatever code is setting its constant.
+        let live_module = unsafe { module.deref_mut() };
+        live_module.constants[index.as_usize()] = Some(value);
+        self.write_barrier(&live_module.base, value);
     }
 }
 
@@ -112,7 +192,7 @@ impl Trace for Module {
             worklist.enqueue(GcAny::from(*p));
         }
 
-        for v in self.constants.iter() {
+        for v in self.constants.iter().flatten() {
             v.enqueue_gc_references(worklist);
         }
     }
@@ -128,15 +208,17 @@ impl PrimitiveOperations for Module {
     }
 }
 
-impl InitFrom<()> for Module {
-    fn extra_size(_arg: &()) -> usize {
+impl InitFrom<Arc<compiler::Module>> for Module {
+    fn extra_size(_arg: &Arc<compiler::Module>) -> usize {
         0 // none
     }
 
-    unsafe fn init(ptr: *mut Self, _args: ()) {
-        addr_of_mut!((*ptr).id).write(None);
+    unsafe fn init(ptr: *mut Self, code: Arc<compiler::Module>) {
+        let constants = vec![None; code.constants().len()];
+
         addr_of_mut!((*ptr).name).write(Value::UNIT);
-        addr_of_mut!((*ptr).constants).write(Vec::new());
+        addr_of_mut!((*ptr).code).write(code);
+        addr_of_mut!((*ptr).constants).write(constants);
         addr_of_mut!((*ptr).prototypes).write(Vec::new());
     }
 }
@@ -13,9 +13,11 @@ use std::{
 
 use common::{Get, Index};
 use compiler::{Capture, FunctionDebug, Op};
+use diagnostic::Span;
 
 use crate::{
     classes::Module, memory::*, primitives::PrimitiveOperations, Value,
+    VirtualMachine,
 };
 
 #[derive(PartialEq)]
@@ -25,7 +27,7 @@ pub struct Prototype {
     base: Object,
 
     module: Gc<Module>,
-    inner: compiler::Function,
+    index: Index<compiler::Function>,
 }
 
 impl Prototype {
@@ -33,35 +35,69 @@ impl Prototype {
         self.module
     }
 
+    /// Move the prototype to `module`, which has its code now.
+    ///
+    /// # Safety
+    ///
+    /// This (unsafely) mutates the [`Prototype`], so nothing else can be
+    /// looking at it.
+    pub(crate) unsafe fn destructively_move_to(
+        gc: Gc<Prototype>,
+        module: Gc<Module>,
+        vm: &mut VirtualMachine,
+    ) {
+        let live_prototype = gc.deref_mut();
+        live_prototype.module = module;
+        vm.write_barrier(&live_prototype.base, Value::from(module));
+    }
+
+    /// Which of the module's functions this is.
+    pub(crate) fn index(&self) -> Index<compiler::Function> {
+        self.index
+    }
+
+    /// The compiled function, from the module's shared code.
+    fn inner(&self) -> &compiler::Function {
+        &self.module.code().functions()[self.index.as_usize()]
+    }
+
     pub fn name(&self) -> Value {
-        self.inner
+        self.inner()
             .name()
             .and_then(|i| self.module.constant(i))
             .unwrap_or_default()
     }
 
+    pub fn span(&self) -> Span {
+        self.inner().span()
+    }
+
     pub fn debug_info(&self) -> Option<&FunctionDebug> {
-        self.inner.debug_info()
+        self.inner().debug_info()
+    }
+
+    pub(crate) fn code(&self) -> &[Op] {
+        self.inner().code()
     }
 
     pub(crate) fn capture_count(&self) -> u32 {
-        self.inner.capture_count()
+        self.inner().capture_count()
     }
 
     pub(crate) fn parameter_count(&self) -> u32 {
-        self.inner.parameter_count()
+        self.inner().parameter_count()
     }
 }
 
 impl Get<Op> for Prototype {
     fn get(&self, index: Index<Op>) -> Option<&Op> {
-        self.inner.get(index)
+        self.inner().get(index)
     }
 }
 
 impl Get<Capture> for Prototype {
     fn get(&self, index: Index<Capture>) -> Option<&Capture> {
-        self.inner.get(index)
+        self.inner().get(index)
     }
 }
 
@@ -97,16 +133,16 @@ impl PrimitiveOperations for Prototype {
     }
 }
 
-impl InitFrom<(Gc<Module>, compiler::Function)> for Prototype {
-    fn extra_size(_arg: &(Gc<Module>, compiler::Function)) -> usize {
+impl InitFrom<(Gc<Module>, Index<compiler::Function>)> for Prototype {
+    fn extra_size(_arg: &(Gc<Module>, Index<compiler::Function>)) -> usize {
         0 // none
     }
 
     unsafe fn init(
         ptr: *mut Self,
-        (module, inner): (Gc<Module>, compiler::Function),
+        (module, index): (Gc<Module>, Index<compiler::Function>),
     ) {
-        addr_of_mut!((*ptr).inner).write(inner);
+        addr_of_mut!((*ptr).index).write(index);
         addr_of_mut!((*ptr).module).write(module);
     }
 }
@@ -3,7 +3,9 @@ use std::{
     ptr::addr_of_mut,
 };
 
-use crate::{memory::*, primitives::PrimitiveOperations};
+use crate::{
+    memory::*, primitives::PrimitiveOperations, Result, Value, VirtualMachine,
+};
 
 #[repr(C, align(8))]
 pub struct String {
@@ -134,6 +136,21 @@ impl String {
     }
 }
 
+impl VirtualMachine {
+    /// Make a string [`Value`] with the contents of `s`.
+    ///
+    /// Short strings are stored inline, so this only allocates if `s` doesn't
+    /// fit in a [`Value::inline_string`].
+    pub fn string(&mut self, s: &str) -> Result<Value> {
+        if let Some(value) = Value::inline_string(s) {
+            return Ok(value);
+        }
+
+        let string: Gc<String> = self.make_from(s)?;
+        Ok(Value::gc(string))
+    }
+}
+
 #[cfg(test)]
 mod string_tests {
     use super::*;
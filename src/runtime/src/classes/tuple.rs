@@ -8,7 +8,10 @@ use std::{
     ptr::addr_of_mut,
 };
 
-use crate::{memory::*, primitives::PrimitiveOperations, value::Value};
+use crate::{
+    memory::*, primitives::PrimitiveOperations, value::Value, Result,
+    VirtualMachine,
+};
 
 use super::Keyword;
 
@@ -27,10 +30,35 @@ impl Tuple {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// The tuple's tag, if it has one.
+    pub fn tag(&self) -> Option<Gc<Keyword>> {
+        self.tag
+    }
+
+    /// A copy of the tuple's elements.
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.elements.borrow().clone()
+    }
+
+    /// Add an element to the end of the tuple, while it's being built.
+    ///
+    /// This can collect garbage if the tuple grows, so it must be rooted.
+    pub(crate) fn push(
+        &self,
+        value: Value,
+        vm: &mut VirtualMachine,
+    ) -> Result<()> {
+        vm.push_owned(&self.base, &self.elements, value)
+    }
 }
 
 impl Class for Tuple {
     const ID: ClassId = ClassId::Tuple;
+
+    fn owned_size(&self) -> usize {
+        self.elements.borrow().capacity() * std::mem::size_of::<Value>()
+    }
 }
 
 impl Trace for Tuple {
@@ -67,6 +95,12 @@ impl InitFrom<(Vec<Value>, Option<Gc<Keyword>>)> for Tuple {
         0
     }
 
+    fn owned_size_from(
+        (elements, _): &(Vec<Value>, Option<Gc<Keyword>>),
+    ) -> usize {
+        elements.capacity() * std::mem::size_of::<Value>()
+    }
+
     unsafe fn init(
         ptr: *mut Self,
         (elements, tag): (Vec<Value>, Option<Gc<Keyword>>),
@@ -78,7 +112,7 @@ impl InitFrom<(Vec<Value>, Option<Gc<Keyword>>)> for Tuple {
 
 impl PartialEq for Tuple {
     fn eq(&self, other: &Self) -> bool {
-        self.elements == other.elements && self.tag == other.tag
+        self.tag == other.tag && self.elements == other.elements
     }
 }
 
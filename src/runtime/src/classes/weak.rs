@@ -0,0 +1,104 @@
+//! Weak references, which don't keep what they point to alive.
+//!
+//! The collector doesn't trace through a [`Weak`], and once the value it
+//! points to is collected the reference is cleared.
+
+use std::{
+    cell::Cell,
+    fmt::{self, Debug},
+    ptr::addr_of_mut,
+};
+
+use crate::{
+    memory::*, primitives::PrimitiveOperations, value::Value, Result,
+    VirtualMachine,
+};
+
+#[repr(C, align(8))]
+pub struct Weak {
+    base: Object,
+    target: Cell<Option<Value>>,
+}
+
+impl Weak {
+    /// The value this points to, if it hasn't been collected yet.
+    ///
+    /// Values which aren't on the heap, like numbers, are never collected.
+    pub fn get(&self) -> Option<Value> {
+        self.target.get()
+    }
+
+    /// The referent if it's on the heap, so it could be collected.
+    pub(crate) fn target(&self) -> Option<GcAny> {
+        self.target.get().and_then(|value| value.as_gc_any())
+    }
+
+    /// Forget the referent, because it's being collected.
+    pub(crate) fn clear(&self) {
+        self.target.set(None);
+    }
+
+    /// Point somewhere else, or nowhere.
+    pub(crate) fn set(&self, target: Option<Value>) {
+        self.target.set(target);
+    }
+}
+
+impl Class for Weak {
+    const ID: ClassId = ClassId::Weak;
+}
+
+impl Trace for Weak {
+    fn enqueue_gc_references(&self, _: &mut WorkList) {
+        // This is the whole point, the target isn't kept alive.
+    }
+}
+
+impl PartialEq for Weak {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Weak {
+    fn partial_cmp(&self, _: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl Debug for Weak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(_) => write!(f, "<weak>"),
+            None => write!(f, "<weak (cleared)>"),
+        }
+    }
+}
+
+impl InitFrom<Value> for Weak {
+    fn extra_size(_arg: &Value) -> usize {
+        0
+    }
+
+    unsafe fn init(ptr: *mut Self, arg: Value) {
+        addr_of_mut!((*ptr).target).write(Cell::new(Some(arg)));
+    }
+}
+
+impl PrimitiveOperations for Weak {
+    fn type_name(&self) -> &'static str {
+        "Weak"
+    }
+}
+
+impl VirtualMachine {
+    /// Make a weak reference to `value`.
+    ///
+    /// Making the reference can collect garbage, so `value` has to be
+    /// reachable from the VM until this returns.
+    pub fn make_weak(&mut self, value: Value) -> Result<Gc<Weak>> {
+        let weak: Gc<Weak> = self.make_from(value)?;
+        self.gc_state.weaks.push(weak);
+        Ok(weak)
+    }
+}
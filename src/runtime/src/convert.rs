@@ -0,0 +1,279 @@
+//! Converting between Rust values and [`Value`]s.
+//!
+//! This is what a host uses to pass its own data into Kurt code, and to get
+//! results back out.
+//!
+//! Most Rust types map onto the Kurt type you'd expect. Integers of any size
+//! become an `Int`, as long as they fit in 48 bits. A [`Vec`] becomes a `List`
+//! and a Rust tuple becomes an untagged `Tuple`. There's no map type yet, so a
+//! [`HashMap`] becomes a `List` of key-value pairs, in no particular order.
+
+use std::{collections::HashMap, hash::Hash};
+
+use common::i48;
+
+use crate::{
+    classes::{List, Tuple},
+    error::CastError,
+    memory::Gc,
+    primitives::PrimitiveOperations,
+    Error, Result, Value, VirtualMachine,
+};
+
+/// Types which can be turned into a [`Value`].
+///
+/// Making the value can allocate, so this needs the [`VirtualMachine`] it's
+/// going to be used in. Like anything else allocated, the value needs to be
+/// put somewhere the collector can find it, like a [`Local`] handle, before
+/// allocating anything else.
+///
+/// [`Local`]: crate::memory::Local
+pub trait IntoValue {
+    fn into_value(self, vm: &mut VirtualMachine) -> Result<Value>;
+}
+
+/// Types which can be read out of a [`Value`].
+///
+/// If the value isn't the right type this fails with a [`CastError`] naming
+/// the value's type and the Kurt type that was expected. If the value is the
+/// right type but doesn't fit, like an `Int` which is too big for a `u8`, the
+/// Rust type is named instead.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError>;
+}
+
+/// The error for `value` not being a `to`.
+fn cast_error(value: Value, to: &'static str) -> CastError {
+    CastError {
+        from: value.type_name(),
+        to,
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self, _: &mut VirtualMachine) -> Result<Value> {
+        Ok(self)
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self, _: &mut VirtualMachine) -> Result<Value> {
+        Ok(Value::UNIT)
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        value.as_unit().ok_or_else(|| cast_error(value, "()"))
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self, _: &mut VirtualMachine) -> Result<Value> {
+        Ok(Value::bool(self))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        value.as_bool().ok_or_else(|| cast_error(value, "Bool"))
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self, _: &mut VirtualMachine) -> Result<Value> {
+        Ok(Value::char(self))
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        value.as_char().ok_or_else(|| cast_error(value, "Char"))
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self, _: &mut VirtualMachine) -> Result<Value> {
+        Ok(Value::float(self))
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        value.as_float().ok_or_else(|| cast_error(value, "Float"))
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($t: ty),*) => {$(
+        impl IntoValue for $t {
+            fn into_value(self, _: &mut VirtualMachine) -> Result<Value> {
+                i64::try_from(self)
+                    .ok()
+                    .and_then(i48::from_i64)
+                    .map(Value::int)
+                    .ok_or(Error::NumberTooBig)
+            }
+        }
+
+        impl FromValue for $t {
+            fn from_value(
+                value: Value,
+            ) -> std::result::Result<Self, CastError> {
+                let i = value.as_int().ok_or_else(|| cast_error(value, "Int"))?;
+
+                <$t>::try_from(i.as_i64()).map_err(|_| CastError {
+                    from: "Int",
+                    to: stringify!($t),
+                })
+            }
+        }
+    )*};
+}
+
+integer_conversions!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoValue for &str {
+    fn into_value(self, vm: &mut VirtualMachine) -> Result<Value> {
+        vm.string(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self, vm: &mut VirtualMachine) -> Result<Value> {
+        vm.string(&self)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        value
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| cast_error(value, "String"))
+    }
+}
+
+/// Make a list and `fill` it, then `finish` turning it into whatever's being
+/// made.
+///
+/// Converting the elements can collect garbage, so the list is kept on the
+/// stack until it's finished, which keeps the elements already added alive.
+fn with_list(
+    vm: &mut VirtualMachine,
+    fill: impl FnOnce(&mut VirtualMachine, Gc<List>) -> Result<()>,
+    finish: impl FnOnce(&mut VirtualMachine, Gc<List>) -> Result<Value>,
+) -> Result<Value> {
+    let list: Gc<List> = vm.make_from(Vec::new())?;
+    vm.stack_mut().push(Value::gc(list));
+
+    let result = fill(vm, list).and_then(|()| finish(vm, list));
+
+    vm.stack_mut().pop();
+    result
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self, vm: &mut VirtualMachine) -> Result<Value> {
+        with_list(
+            vm,
+            |vm, list| {
+                for element in self {
+                    let value = element.into_value(vm)?;
+                    list.push(value, vm)?;
+                }
+                Ok(())
+            },
+            |_, list| Ok(Value::gc(list)),
+        )
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        let list = value
+            .as_gc::<List>()
+            .ok_or_else(|| cast_error(value, "List"))?;
+
+        list.to_vec().into_iter().map(T::from_value).collect()
+    }
+}
+
+impl<K, V> IntoValue for HashMap<K, V>
+where
+    K: IntoValue,
+    V: IntoValue,
+{
+    fn into_value(self, vm: &mut VirtualMachine) -> Result<Value> {
+        self.into_iter().collect::<Vec<_>>().into_value(vm)
+    }
+}
+
+impl<K, V> FromValue for HashMap<K, V>
+where
+    K: FromValue + Eq + Hash,
+    V: FromValue,
+{
+    fn from_value(value: Value) -> std::result::Result<Self, CastError> {
+        let pairs: Vec<(K, V)> = FromValue::from_value(value)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+macro_rules! tuple_conversions {
+    ($len: literal, $($t: ident $i: tt),*) => {
+        impl<$($t: IntoValue),*> IntoValue for ($($t,)*) {
+            fn into_value(self, vm: &mut VirtualMachine) -> Result<Value> {
+                with_list(
+                    vm,
+                    |vm, list| {
+                        $(
+                            let value = self.$i.into_value(vm)?;
+                            list.push(value, vm)?;
+                        )*
+                        Ok(())
+                    },
+                    |vm, list| {
+                        let tuple: Gc<Tuple> =
+                            vm.make_from((list.to_vec(), None))?;
+                        Ok(Value::gc(tuple))
+                    },
+                )
+            }
+        }
+
+        impl<$($t: FromValue),*> FromValue for ($($t,)*) {
+            fn from_value(
+                value: Value,
+            ) -> std::result::Result<Self, CastError> {
+                let tuple = value
+                    .as_gc::<Tuple>()
+                    .ok_or_else(|| cast_error(value, "Tuple"))?;
+
+                let elements = tuple.to_vec();
+
+                if elements.len() != $len {
+                    return Err(CastError {
+                        from: "Tuple",
+                        to: std::any::type_name::<Self>(),
+                    });
+                }
+
+                Ok(($($t::from_value(elements[$i])?,)*))
+            }
+        }
+    };
+}
+
+tuple_conversions!(1, A 0);
+tuple_conversions!(2, A 0, B 1);
+tuple_conversions!(3, A 0, B 1, C 2);
+tuple_conversions!(4, A 0, B 1, C 2, D 3);
+tuple_conversions!(5, A 0, B 1, C 2, D 3, E 4);
+tuple_conversions!(6, A 0, B 1, C 2, D 3, E 4, F 5);
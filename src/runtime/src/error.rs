@@ -4,12 +4,24 @@ use std::{error, fmt};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug)]
+/// A value wasn't the type it needed to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CastError {
+    /// The type of the value.
     pub from: &'static str,
+
+    /// The type it was supposed to be.
     pub to: &'static str,
 }
 
+impl error::Error for CastError {}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error casting a {} to {}", self.from, self.to)
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     NumberTooBig,
@@ -19,6 +31,16 @@ pub enum Error {
     NoMainModule,
     NoMainFunction,
 
+    InvalidModule(compiler::VerifyError),
+    InvalidExtension,
+    InvalidReload,
+
+    /// The VM can't be saved as an [`Image`][crate::Image], and why.
+    CannotSaveImage(&'static str),
+
+    /// An [`Image`][crate::Image] can't be restored, and what's wrong with it.
+    InvalidImage(&'static str),
+
     InvalidArgCount {
         found: u32,
         expected: u32,
@@ -26,6 +48,7 @@ pub enum Error {
 
     CanOnlyCallClosures,
     SubscriptIndexOutOfRange,
+    UndefinedMember,
 
     OperationNotSupported {
         type_name: &'static str,
@@ -33,6 +56,17 @@ pub enum Error {
     },
 
     Cast(CastError),
+
+    /// An error from the host, with its message.
+    Host(std::string::String),
+
+    OutOfFuel,
+    NothingToResume,
+    StackLimitExceeded,
+    StackOverflow,
+    OutOfMemory,
+
+    Interrupted,
 }
 
 impl error::Error for Error {}
@@ -48,6 +82,19 @@ impl fmt::Display for Error {
             NoMainModule => write!(f, "no main module is loaded"),
             NoMainFunction => write!(f, "no main function"),
 
+            InvalidModule(e) => write!(f, "cannot load invalid module, {e}"),
+            InvalidExtension => {
+                write!(f, "module doesn't extend the halted top-level code")
+            }
+            InvalidReload => write!(f, "no halted top-level code to reload"),
+
+            CannotSaveImage(reason) => {
+                write!(f, "cannot save the VM image, {reason}")
+            }
+            InvalidImage(reason) => {
+                write!(f, "cannot restore invalid VM image, {reason}")
+            }
+
             InvalidArgCount { found, expected } => {
                 write!(f, "a function which expected {} arguments was called with {} arguments",
                 expected, found
@@ -58,17 +105,31 @@ impl fmt::Display for Error {
             SubscriptIndexOutOfRange => {
                 write!(f, "subscript index out of range")
             }
+            UndefinedMember => write!(f, "no member with that name"),
 
-            Cast(c) => {
-                write!(f, "error casting a {} to {}", c.from, c.to)
-            }
+            Cast(c) => write!(f, "{c}"),
+            Host(message) => write!(f, "{message}"),
             OperationNotSupported { type_name, op_name } => {
                 write!(f, "cannot {} with type {}", op_name, type_name)
             }
+
+            OutOfFuel => write!(f, "ran out of fuel"),
+            NothingToResume => write!(f, "nothing is waiting to be resumed"),
+            StackLimitExceeded => write!(f, "stack limit exceeded"),
+            StackOverflow => write!(f, "stack overflow"),
+            OutOfMemory => write!(f, "out of memory"),
+
+            Interrupted => write!(f, "interrupted"),
         }
     }
 }
 
+impl From<compiler::VerifyError> for Error {
+    fn from(e: compiler::VerifyError) -> Self {
+        Error::InvalidModule(e)
+    }
+}
+
 impl From<CastError> for Error {
     fn from(e: CastError) -> Self {
         Error::Cast(e)
@@ -2,7 +2,9 @@
 
 pub mod classes;
 pub mod memory;
+pub mod native;
 
+mod convert;
 mod error;
 mod primitives;
 mod value;
@@ -12,7 +14,15 @@ mod vm;
 mod tracing;
 
 pub use crate::{
-    error::{Error, Result},
+    convert::{FromValue, IntoValue},
+    error::{CastError, Error, Result},
+    memory::{
+        GcConfig, GcStats, Global, HandleScope, HeapAnalysis, HeapSnapshot,
+        Local,
+    },
     value::Value,
-    vm::{Stack, VirtualMachine},
+    vm::{
+        Cost, Debugger, EvaluateError, FunctionProfile, Image, InterruptHandle,
+        Limits, LineProfile, Pause, Profile, Resume, Stack, VirtualMachine,
+    },
 };
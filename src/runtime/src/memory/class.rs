@@ -8,6 +8,8 @@
 
 use std::fmt::Debug;
 
+use serde::{Deserialize, Serialize};
+
 use crate::memory::trace::Trace;
 
 /// Class IDs are used as type tags.
@@ -17,16 +19,18 @@ use crate::memory::trace::Trace;
 /// keep things exhaustive and safe.
 ///
 /// [1]: crate::memory::object::dispatch
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum ClassId {
     CaptureCell,
     Closure,
+    Foreign,
     Keyword,
     List,
     Module,
     Prototype,
     String,
     Tuple,
+    Weak,
 }
 
 impl ClassId {
@@ -34,12 +38,14 @@ impl ClassId {
         match self {
             ClassId::CaptureCell => "CaptureCell",
             ClassId::Closure => "Closure",
+            ClassId::Foreign => "Foreign",
             ClassId::Keyword => "Keyword",
             ClassId::List => "List",
             ClassId::Module => "Module",
             ClassId::Prototype => "Prototype",
             ClassId::String => "String",
             ClassId::Tuple => "Tuple",
+            ClassId::Weak => "Weak",
         }
     }
 }
@@ -71,4 +77,10 @@ pub trait Class: Debug + Sized + Trace {
     fn identity(&self) -> usize {
         self as *const Self as usize
     }
+
+    /// The bytes this object owns outside of its allocation, like the storage
+    /// of a [`Vec`]. These count against the heap as well.
+    fn owned_size(&self) -> usize {
+        0
+    }
 }
@@ -1,25 +1,56 @@
-//! A simple garbage collector.
+//! A simple generational garbage collector.
 //!
 //! The basic design is the same as the garbage collector in [Crafting
 //! Interpreters][ci]. It's a super simple mark-sweep collector that keeps all
-//! objects in a big linked list. This is basically what Lua does too.
+//! objects in linked lists. This is basically what Lua does too.
 //!
-//! The runtime includes reference to the head of a linked list of all allocated
-//! objects that's maintained.
+//! Most objects die young, so objects start out in a small _nursery_, which is
+//! collected on its own whenever it fills up. Anything that survives one of
+//! these minor collections is promoted to the _old generation_, which is only
+//! collected when it grows past its capacity. Objects never move, so promoting
+//! is just moving the object from one list to the other.
+//!
+//! A minor collection doesn't trace through the old generation, so it needs to
+//! know about any old objects which point to young ones. Any code which stores
+//! a value in an object after it's created must call
+//! [`VirtualMachine::write_barrier`] so that the object is remembered.
+//!
+//! Marking the whole heap can take a while, so full collections are
+//! _incremental_. Once one starts, each allocation marks a few more objects
+//! until there's nothing left to mark, and only then is the heap swept. This
+//! uses the usual tri-color scheme: objects are _white_ if they haven't been
+//! found yet, _gray_ if they're in the work list waiting to be traced, and
+//! _black_ if they're marked and everything they point to is at least gray.
+//! The program keeps running between slices, so the write barrier also makes
+//! sure a black object never points to a white one by shading the new value
+//! gray. The roots aren't behind a barrier, so they're traced again right
+//! before sweeping.
+//!
+//! The nursery isn't collected on its own while marking is in progress, it's
+//! swept along with everything else when marking finishes.
+//!
+//! [`Weak`] references aren't traced at all. Instead the collector keeps a list
+//! of them, and right before sweeping clears any whose referent wasn't marked.
+//! The table of interned [`Keyword`]s is weak in the same way.
+//!
+//! A host can also ask to hear about objects being freed, by setting a
+//! [`Finalizer`] and calling [`VirtualMachine::finalize_on_collect`] on the
+//! objects it cares about.
 //!
 //! [ci]: http://craftinginterpreters.com/garbage-collection.html
 
 // TODO: We could be clever and allocate the worklist upfront when we increase
 //       the max heap size.
 
-use std::cell::Cell;
+use std::{cell::Cell, collections::HashMap};
 
 use crate::{
+    classes::{Keyword, Weak},
     memory::{
         trace::{Trace, WorkList},
-        GcAny,
+        Gc, GcAny, GcConfig, GcStats, Object,
     },
-    VirtualMachine,
+    Value, VirtualMachine,
 };
 
 pub(crate) struct GCHeader {
@@ -28,6 +59,15 @@ pub(crate) struct GCHeader {
 
     /// Was this object marked as reachable by the last mark phase?
     mark: Cell<bool>,
+
+    /// Has this object been promoted to the old generation?
+    old: Cell<bool>,
+
+    /// Is this object in the remembered set?
+    remembered: Cell<bool>,
+
+    /// Should the finalizer be called when this object is freed?
+    finalize: Cell<bool>,
 }
 
 impl Default for GCHeader {
@@ -35,6 +75,9 @@ impl Default for GCHeader {
         GCHeader {
             next: Cell::new(None),
             mark: Cell::new(false),
+            old: Cell::new(false),
+            remembered: Cell::new(false),
+            finalize: Cell::new(false),
         }
     }
 }
@@ -48,50 +91,161 @@ impl GCHeader {
     fn clear_mark(&self) {
         self.mark.set(false);
     }
+
+    /// Is this object in the old generation?
+    pub(crate) fn is_old(&self) -> bool {
+        self.old.get()
+    }
+
+    /// The next object in the same generation.
+    pub(crate) fn next(&self) -> Option<GcAny> {
+        self.next.get()
+    }
 }
 
+/// A function the host wants called on objects which are being freed, see
+/// [`VirtualMachine::set_finalizer`].
+pub type Finalizer = Box<dyn FnMut(&Object)>;
+
 #[derive(Debug)]
 pub(crate) struct GcState {
-    heap_head: Option<GcAny>,
+    pub(crate) config: GcConfig,
+    pub(crate) stats: GcStats,
+
+    pub(crate) heap_head: Option<GcAny>,
     capacity: usize,
     pub(crate) used: usize,
     tracked_allocations: usize,
+
+    /// The young objects, and how many bytes they use.
+    pub(crate) nursery_head: Option<GcAny>,
+    nursery_used: usize,
+
+    /// Old objects which might point to young ones.
+    remembered: Vec<GcAny>,
+
+    /// Every [`Weak`] reference which hasn't been collected.
+    pub(crate) weaks: Vec<Gc<Weak>>,
+
+    /// The interned keywords. These are held weakly, like [`Weak`]s.
+    pub(crate) keywords: HashMap<Box<str>, Gc<Keyword>>,
+
+    /// Is a full collection part way through marking?
+    marking: bool,
+
+    /// The gray objects, if `marking` is true.
+    gray: WorkList,
+
+    /// The most bytes which can be in use, if there's a limit.
+    limit: Option<usize>,
 }
 
 impl Default for GcState {
     fn default() -> Self {
+        let config = GcConfig::default();
+
         GcState {
+            config,
+            stats: GcStats::default(),
             heap_head: None,
-            capacity: GcState::DEFAULT_CAPACITY,
+            capacity: config.initial_capacity,
             used: 0,
             tracked_allocations: 0,
+            nursery_head: None,
+            nursery_used: 0,
+            remembered: Vec::new(),
+            weaks: Vec::new(),
+            keywords: HashMap::new(),
+            marking: false,
+            gray: WorkList::default(),
+            limit: None,
         }
     }
 }
 
 impl GcState {
-    // These numbers were pulled from thin air.
-    const DEFAULT_CAPACITY: usize = 4096;
-    const GROWTH_FACTOR: usize = 2;
+    /// Change the settings. This should only be done before anything is
+    /// allocated.
+    pub(crate) fn configure(&mut self, config: GcConfig) {
+        debug_assert_eq!(self.tracked_allocations, 0);
+        self.config = config;
+        self.capacity = config.initial_capacity.max(1);
+    }
 
-    const GETTING_FULL: f64 = 0.80;
-    const STILL_TOO_FULL: f64 = 0.50;
+    /// Does the next allocation need to do any collecting?
+    #[inline(always)]
+    pub fn collection_is_needed(&mut self) -> bool {
+        self.config.stress
+            || self.marking
+            || self.nursery_collection_is_needed()
+            || self.garbage_collection_is_needed()
+    }
 
     /// Is it time to run a full GC cycle?
     #[inline(always)]
     // TODO: write something smarter than this!
     pub fn garbage_collection_is_needed(&mut self) -> bool {
-        self.used_percent() > GcState::GETTING_FULL
+        self.used_percent() > self.config.collect_when
     }
 
+    /// Is it time to collect the nursery?
+    #[inline(always)]
+    pub fn nursery_collection_is_needed(&self) -> bool {
+        self.nursery_used > self.config.nursery_capacity
+    }
+
+    /// How full the old generation is.
     pub fn used_percent(&self) -> f64 {
-        self.used as f64 / self.capacity as f64
+        (self.used - self.nursery_used) as f64 / self.capacity as f64
     }
 
     pub fn grow_if_needed(&mut self) {
-        if self.used_percent() > GcState::STILL_TOO_FULL {
-            self.capacity *= GcState::GROWTH_FACTOR;
+        if self.used_percent() > self.config.grow_when {
+            self.capacity *= self.config.growth_factor;
         }
+
+        // There's no point waiting to collect until we're past the limit.
+        if let Some(limit) = self.limit {
+            self.capacity = self.capacity.min(limit.max(1));
+        }
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    /// Would allocating `size` more bytes go over the limit?
+    #[inline(always)]
+    pub(crate) fn would_exceed_limit(&self, size: usize) -> bool {
+        self.limit.is_some_and(|limit| self.used + size > limit)
+    }
+
+    /// Count bytes an object has grown by outside of its allocation.
+    pub(crate) fn grew(&mut self, header: &GCHeader, bytes: usize) {
+        self.used += bytes;
+        self.stats.bytes_allocated += bytes;
+
+        if !header.is_old() {
+            self.nursery_used += bytes;
+        }
+    }
+
+    /// Stop counting bytes an object no longer owns outside its allocation.
+    pub(crate) fn shrank(&mut self, header: &GCHeader, bytes: usize) {
+        self.used -= bytes;
+        self.stats.bytes_freed += bytes;
+
+        if !header.is_old() {
+            self.nursery_used = self.nursery_used.saturating_sub(bytes);
+        }
+    }
+
+    /// Put an object at the front of the old generation's list.
+    fn push_old(&mut self, ptr: GcAny) {
+        let header = ptr.deref().gc_header();
+        header.old.set(true);
+        header.next.set(self.heap_head);
+        self.heap_head = Some(ptr);
     }
 }
 
@@ -99,19 +253,78 @@ impl VirtualMachine {
     /// Collect garbage, but only if needed.
     #[inline(always)] // inline the 'fast' check, not slow collection.
     pub fn collect_garbage(&mut self) {
-        if self.gc_state.garbage_collection_is_needed() {
-            self.force_collect_garbage();
-            self.gc_state.grow_if_needed();
+        if self.gc_state.collection_is_needed() {
+            self.pause(VirtualMachine::collection_step);
         }
     }
 
     /// Force a full garbage collection cycle, even if it's not needed.
+    ///
+    /// If a collection is already in progress, it's finished first. Things
+    /// can die after they're marked, so then another whole collection is run
+    /// to make sure all the garbage is gone.
     #[inline(never)] // Collecting is always the slow path
     pub fn force_collect_garbage(&mut self) {
+        self.pause(VirtualMachine::full_collection);
+    }
+
+    /// Force a collection of only the young objects, even if it's not needed.
+    ///
+    /// Everything in the nursery that's still reachable is promoted to the old
+    /// generation. If a full collection is in progress, it's finished instead,
+    /// since that collects the nursery too.
+    #[inline(never)] // Collecting is always the slow path
+    pub fn force_collect_nursery(&mut self) {
+        self.pause(VirtualMachine::minor_collection);
+    }
+
+    /// Is a full collection part way done?
+    pub fn is_collecting(&self) -> bool {
+        self.gc_state.marking
+    }
+
+    /// Do whatever collecting the next allocation needs.
+    #[inline(never)]
+    fn collection_step(&mut self) {
+        if self.gc_state.config.stress {
+            // Each allocation marks one object, and when there's nothing left
+            // to mark a new full collection starts right away.
+            if !self.gc_state.marking {
+                self.minor_collection();
+                self.start_marking();
+            }
+
+            self.mark_slice(1);
+        } else if self.gc_state.marking {
+            self.mark_slice(self.gc_state.config.mark_slice);
+        } else if self.gc_state.nursery_collection_is_needed() {
+            self.minor_collection();
+        } else {
+            self.start_marking();
+        }
+    }
+
+    fn full_collection(&mut self) {
+        if self.gc_state.marking {
+            self.finish_marking();
+        }
+
+        self.start_marking();
+        self.finish_marking();
+    }
+
+    fn minor_collection(&mut self) {
+        if self.gc_state.marking {
+            self.finish_marking();
+            return;
+        }
+
         #[cfg(feature = "gc_trace")]
-        eprintln!("starting garbage collection");
-        self.mark();
-        self.sweep();
+        eprintln!("starting nursery collection");
+        self.mark_young();
+        self.clear_weak_references(true);
+        self.sweep_nursery();
+        self.gc_state.stats.minor_collections += 1;
     }
 
     /// Register a [`Gc`] pointer to be tracked by the runtime.
@@ -120,46 +333,161 @@ impl VirtualMachine {
     ///
     /// The GC must not be tracked by any runtime yet. This should only be
     /// called as part of object creation, after initialization.
+    ///
+    /// [`Gc`]: crate::memory::Gc
     pub(crate) unsafe fn register_gc_ptr(&mut self, ptr: GcAny) {
         let header = ptr.deref().gc_header();
 
-        let old_heap_head = self.gc_state.heap_head;
-
         debug_assert!(header.next.get().is_none());
 
         self.gc_state.tracked_allocations += 1;
 
-        header.next.set(old_heap_head);
-        self.gc_state.heap_head = Some(ptr);
+        // Without a nursery, everything starts out old.
+        if self.gc_state.config.nursery_capacity == 0 {
+            self.gc_state.push_old(ptr);
+            return;
+        }
+
+        self.gc_state.nursery_used +=
+            ptr.deref().size() + ptr.deref().owned_size();
+
+        header.next.set(self.gc_state.nursery_head);
+        self.gc_state.nursery_head = Some(ptr);
+    }
+
+    /// Let the collector know that `value` was stored in `object`, after it
+    /// was created.
+    ///
+    /// This must be called whenever an existing object is changed to point to
+    /// something else, otherwise a minor collection could miss the new value.
+    #[inline]
+    pub(crate) fn write_barrier(&mut self, object: &Object, value: Value) {
+        let ptr = match value.as_gc_any() {
+            Some(ptr) => ptr,
+            None => return,
+        };
+
+        let header = object.gc_header();
+
+        // A black object can't point to a white one, so the value is shaded.
+        if self.gc_state.marking && header.is_marked() {
+            self.gc_state.gray.enqueue(ptr);
+        }
+
+        if header.is_old()
+            && !header.remembered.get()
+            && !ptr.deref().gc_header().is_old()
+        {
+            header.remembered.set(true);
+            self.gc_state.remembered.push(GcAny::from(object));
+        }
+    }
+
+    /// Start a full collection by making the roots gray.
+    fn start_marking(&mut self) {
+        #[cfg(feature = "gc_trace")]
+        eprintln!("starting garbage collection");
+
+        let mut gray = std::mem::take(&mut self.gc_state.gray);
+        self.enqueue_gc_references(&mut gray);
+        self.gc_state.gray = gray;
+        self.gc_state.marking = true;
+    }
+
+    /// Mark up to `budget` gray objects, finishing the collection if there
+    /// aren't any left.
+    fn mark_slice(&mut self, budget: usize) {
+        for _ in 0..budget {
+            match self.gc_state.gray.pop() {
+                Some(ptr) => blacken(ptr, &mut self.gc_state.gray),
+                None => {
+                    self.finish_marking();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Mark everything that's still gray, including anything the roots point
+    /// to now, and then sweep.
+    fn finish_marking(&mut self) {
+        let mut gray = std::mem::take(&mut self.gc_state.gray);
+        self.enqueue_gc_references(&mut gray);
+
+        while let Some(ptr) = gray.pop() {
+            blacken(ptr, &mut gray);
+        }
+
+        self.gc_state.gray = gray;
+        self.gc_state.marking = false;
+
+        self.clear_weak_references(false);
+        self.sweep();
+        self.gc_state.grow_if_needed();
+        self.gc_state.stats.major_collections += 1;
     }
 
     /// Using [`Runtime`] to access the root set of live objects, we visit every
-    /// reachable object and mark it so we can identify the unreachable objects
-    /// which must be garbage.
-    fn mark(&mut self) {
+    /// reachable young object and mark it so we can identify the unreachable
+    /// young objects which must be garbage.
+    ///
+    /// Old objects are only traced through if they're remembered.
+    fn mark_young(&mut self) {
         #[cfg(feature = "gc_trace")]
         eprintln!("starting mark phase");
 
-        let mut worklist = WorkList::default();
+        let mut worklist = WorkList::young_only();
 
         // This adds the root set.
         self.enqueue_gc_references(&mut worklist);
 
+        // Old objects which point to young ones are roots too, for now.
+        for ptr in &self.gc_state.remembered {
+            ptr.deref().enqueue_gc_references(&mut worklist);
+        }
+
         // trace
         while let Some(ptr) = worklist.pop() {
-            ptr.deref().gc_header().mark.set(true);
-            ptr.deref().enqueue_gc_references(&mut worklist);
+            blacken(ptr, &mut worklist);
         }
     }
 
     /// Deallocate any objects managed by this runtime which are currently
     /// not marked. All objects which remain alive also have their mark cleared.
+    ///
+    /// Everything left is in the old generation afterwards.
     fn sweep(&mut self) {
         #[cfg(feature = "gc_trace")]
         eprintln!("starting sweep phase");
 
-        let mut list = self.gc_state.heap_head.take();
+        // This has to happen first, since some remembered objects might not
+        // survive the sweep.
+        self.forget_remembered();
+
+        let old = self.gc_state.heap_head.take();
+        let young = self.gc_state.nursery_head.take();
+        self.gc_state.nursery_used = 0;
+
+        self.sweep_list(old);
+        self.sweep_list(young);
+    }
+
+    /// Like [`VirtualMachine::sweep`] but only for the nursery.
+    fn sweep_nursery(&mut self) {
+        #[cfg(feature = "gc_trace")]
+        eprintln!("starting nursery sweep phase");
+
+        let young = self.gc_state.nursery_head.take();
+        self.gc_state.nursery_used = 0;
+
+        self.sweep_list(young);
 
+        // Nothing is young any more, so the old objects can't point to any
+        // young ones.
+        self.forget_remembered();
+    }
+
+    fn sweep_list(&mut self, mut list: Option<GcAny>) {
         while let Some(ptr) = list {
             // update list to be the tail.
             let header = ptr.deref().gc_header();
@@ -167,13 +495,84 @@ impl VirtualMachine {
 
             if header.is_marked() {
                 header.clear_mark();
-                unsafe { self.register_gc_ptr(ptr) };
+                self.gc_state.push_old(ptr);
             } else {
+                if header.finalize.get() {
+                    if let Some(finalizer) = &mut self.finalizer {
+                        finalizer(ptr.deref());
+                    }
+                }
+
                 unsafe { self.deallocate(ptr) };
                 self.gc_state.tracked_allocations -= 1;
             }
         }
     }
+
+    /// Clear any [`Weak`] references to objects which are about to be swept,
+    /// and forget about any weak references or interned keywords which are
+    /// about to be swept.
+    ///
+    /// This must be called after marking is finished. If `minor` is true only
+    /// young objects are being swept.
+    fn clear_weak_references(&mut self, minor: bool) {
+        let survives = |ptr: GcAny| {
+            let header = ptr.deref().gc_header();
+            header.is_marked() || (minor && header.is_old())
+        };
+
+        self.gc_state.weaks.retain(|weak| {
+            if !survives(GcAny::from(*weak)) {
+                return false;
+            }
+
+            if weak.target().is_some_and(|target| !survives(target)) {
+                weak.clear();
+            }
+
+            true
+        });
+
+        self.gc_state
+            .keywords
+            .retain(|_, keyword| survives(GcAny::from(*keyword)));
+    }
+
+    /// Set the function called with objects that are being freed, if they
+    /// were passed to [`VirtualMachine::finalize_on_collect`].
+    ///
+    /// The object is about to be freed when it's called, so the finalizer
+    /// can't keep it or anything it points to around.
+    pub fn set_finalizer(&mut self, finalizer: Option<Finalizer>) {
+        self.finalizer = finalizer;
+    }
+
+    /// Call the [finalizer][VirtualMachine::set_finalizer] when the object
+    /// `value` points to is collected.
+    ///
+    /// Does nothing if `value` isn't an object on the heap.
+    pub fn finalize_on_collect(&mut self, value: Value) {
+        if let Some(ptr) = value.as_gc_any() {
+            ptr.deref().gc_header().finalize.set(true);
+        }
+    }
+
+    fn forget_remembered(&mut self) {
+        for ptr in self.gc_state.remembered.drain(..) {
+            ptr.deref().gc_header().remembered.set(false);
+        }
+    }
+}
+
+/// Mark an object, and make everything it points to gray.
+fn blacken(ptr: GcAny, worklist: &mut WorkList) {
+    let header = ptr.deref().gc_header();
+
+    // Things can end up in the work list more than once.
+    if !header.is_marked() {
+        header.mark.set(true);
+        ptr.deref().enqueue_gc_references(worklist);
+    }
 }
 
 impl Trace for VirtualMachine {
@@ -183,6 +582,10 @@ impl Trace for VirtualMachine {
             value.enqueue_gc_references(worklist);
         }
 
+        if let Some(value) = self.checkpoint().and_then(|c| c.value()) {
+            value.enqueue_gc_references(worklist);
+        }
+
         for module in self.modules() {
             worklist.enqueue(GcAny::from(*module));
         }
@@ -190,5 +593,31 @@ impl Trace for VirtualMachine {
         for cell in self.open_captures.iter() {
             worklist.enqueue(GcAny::from(*cell));
         }
+
+        // Values the host is holding on to.
+        self.handles.borrow().enqueue_gc_references(worklist);
+    }
+}
+
+impl Drop for VirtualMachine {
+    /// Free everything on the heap, so anything objects own (like the code of
+    /// a shared module) is dropped with the VM.
+    ///
+    /// Handles the host still has can't point into the heap after this, so
+    /// they're cleared and will panic if they're used.
+    fn drop(&mut self) {
+        self.handles.borrow_mut().vm_dropped();
+
+        let old = self.gc_state.heap_head.take();
+        let young = self.gc_state.nursery_head.take();
+
+        for mut list in [old, young] {
+            while let Some(ptr) = list {
+                list = ptr.deref().gc_header().next.replace(None);
+
+                // SAFETY: The VM's going away, so nothing can reach anything.
+                unsafe { self.deallocate(ptr) };
+            }
+        }
     }
 }
@@ -0,0 +1,75 @@
+//! Tuning the garbage collector.
+//!
+//! The defaults were pulled from thin air, so a host that knows what its
+//! programs look like can pick something better by creating its
+//! [`VirtualMachine`] with [`VirtualMachine::with_gc_config`].
+
+use crate::VirtualMachine;
+
+/// Settings for the garbage collector.
+///
+/// See the [collector][crate::memory::collector] for how these fit together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GcConfig {
+    /// How many bytes the old generation has room for before its first full
+    /// collection.
+    pub initial_capacity: usize,
+
+    /// How much the old generation's capacity is multiplied by when it needs
+    /// to grow.
+    pub growth_factor: usize,
+
+    /// How full (from `0.0` to `1.0`) the old generation gets before a full
+    /// collection is started.
+    pub collect_when: f64,
+
+    /// How full (from `0.0` to `1.0`) the old generation can still be after
+    /// a full collection before it grows.
+    pub grow_when: f64,
+
+    /// How many bytes of young objects are allowed before the nursery is
+    /// collected.
+    ///
+    /// A capacity of `0` turns off the nursery, so that every collection is a
+    /// full collection.
+    pub nursery_capacity: usize,
+
+    /// How many objects each allocation marks, while a full collection is in
+    /// progress. Bigger slices mean fewer, longer pauses.
+    pub mark_slice: usize,
+
+    /// Should every allocation do a step of garbage collection?
+    ///
+    /// This is very slow, but it's good at finding values which aren't
+    /// rooted, or places that are missing a write barrier. It's on by default
+    /// if the `gc_stress` feature is enabled.
+    pub stress: bool,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        GcConfig {
+            initial_capacity: 4096,
+            growth_factor: 2,
+            collect_when: 0.80,
+            grow_when: 0.50,
+            nursery_capacity: 64 * 1024,
+            mark_slice: 128,
+            stress: cfg!(feature = "gc_stress"),
+        }
+    }
+}
+
+impl VirtualMachine {
+    /// Create a new VM with its garbage collector tuned by `config`.
+    pub fn with_gc_config(config: GcConfig) -> VirtualMachine {
+        let mut vm = VirtualMachine::default();
+        vm.gc_state.configure(config);
+        vm
+    }
+
+    /// The settings the garbage collector is using.
+    pub fn gc_config(&self) -> GcConfig {
+        self.gc_state.config
+    }
+}
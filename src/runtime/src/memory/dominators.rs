@@ -0,0 +1,305 @@
+//! Finding out what's keeping memory alive, from a [`HeapSnapshot`].
+//!
+//! Object `a` _dominates_ object `b` if every path from the roots to `b` goes
+//! through `a`, so if `a` were freed, `b` would be too. The _retained size_ of
+//! an object is its own size plus the size of everything it dominates. Big
+//! retained sizes are a good place to start looking for leaks.
+//!
+//! The dominators are found with the iterative algorithm from Cooper, Harvey
+//! and Kennedy's [A Simple, Fast Dominance Algorithm][paper], treating all the
+//! roots as coming from one extra node.
+//!
+//! [paper]: https://www.cs.rice.edu/~keith/EMBED/dom.pdf
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::memory::{heap_snapshot::HeapSnapshot, ClassId};
+
+/// The dominators and retained sizes of everything in a [`HeapSnapshot`].
+#[derive(Debug, Clone)]
+pub struct HeapAnalysis {
+    /// Every object, in the same order as the snapshot.
+    pub objects: Vec<ObjectAnalysis>,
+}
+
+/// What the analysis found out about one object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectAnalysis {
+    pub id: usize,
+    pub class: ClassId,
+    pub size: usize,
+
+    /// The object's immediate dominator. This is `None` for objects only
+    /// dominated by the roots, or which aren't reachable.
+    pub dominator: Option<usize>,
+
+    /// This object's size, plus the size of everything it dominates.
+    pub retained_size: usize,
+
+    /// Can this object be reached from the roots?
+    pub reachable: bool,
+}
+
+impl HeapAnalysis {
+    /// How many objects are shown by the [`Display`] implementation.
+    pub const SHOWN: usize = 20;
+
+    /// The reachable objects, from biggest retained size to smallest.
+    pub fn largest(&self) -> Vec<&ObjectAnalysis> {
+        let mut objects: Vec<_> =
+            self.objects.iter().filter(|o| o.reachable).collect();
+
+        objects.sort_by(|a, b| {
+            b.retained_size.cmp(&a.retained_size).then(a.id.cmp(&b.id))
+        });
+
+        objects
+    }
+}
+
+impl HeapSnapshot {
+    /// Find the dominators and retained size of every object.
+    pub fn analyze(&self) -> HeapAnalysis {
+        let count = self.objects.len();
+
+        // The extra node which points to all the roots.
+        let root = count;
+
+        let successors = |node: usize| -> Box<dyn Iterator<Item = usize> + '_> {
+            if node == root {
+                Box::new(self.roots.iter().map(|r| r.object()))
+            } else {
+                Box::new(self.objects[node].references.iter().copied())
+            }
+        };
+
+        // Find the reverse post-order, without recursing since the heap can
+        // be very deep.
+        let mut order = Vec::with_capacity(count + 1);
+        let mut visited = vec![false; count + 1];
+        let mut stack = vec![(root, successors(root))];
+        visited[root] = true;
+
+        while let Some((node, children)) = stack.last_mut() {
+            match children.find(|&child| !visited[child]) {
+                Some(child) => {
+                    visited[child] = true;
+                    stack.push((child, successors(child)));
+                }
+                None => {
+                    order.push(*node);
+                    stack.pop();
+                }
+            }
+        }
+
+        order.reverse();
+
+        let mut position = vec![usize::MAX; count + 1];
+        for (i, &node) in order.iter().enumerate() {
+            position[node] = i;
+        }
+
+        let mut predecessors = vec![Vec::new(); count + 1];
+        for &node in &order {
+            for successor in successors(node) {
+                predecessors[successor].push(node);
+            }
+        }
+
+        let mut dominators = vec![None; count + 1];
+        dominators[root] = Some(root);
+
+        let intersect =
+            |dominators: &[Option<usize>], mut a: usize, mut b: usize| {
+                while a != b {
+                    while position[a] > position[b] {
+                        a = dominators[a].unwrap();
+                    }
+                    while position[b] > position[a] {
+                        b = dominators[b].unwrap();
+                    }
+                }
+                a
+            };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &node in &order[1..] {
+                let mut new = None;
+
+                for &predecessor in &predecessors[node] {
+                    if dominators[predecessor].is_none() {
+                        continue;
+                    }
+
+                    new = Some(match new {
+                        None => predecessor,
+                        Some(other) => {
+                            intersect(&dominators, predecessor, other)
+                        }
+                    });
+                }
+
+                if new.is_some() && dominators[node] != new {
+                    dominators[node] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        // Children always come after their dominators in the order, so going
+        // backwards adds everything up from the bottom.
+        let mut retained: Vec<usize> =
+            self.objects.iter().map(|o| o.size).collect();
+
+        for &node in order[1..].iter().rev() {
+            let dominator = dominators[node].unwrap();
+            if dominator != root {
+                retained[dominator] += retained[node];
+            }
+        }
+
+        let objects = self
+            .objects
+            .iter()
+            .map(|object| {
+                let dominator = dominators[object.id];
+
+                ObjectAnalysis {
+                    id: object.id,
+                    class: object.class,
+                    size: object.size,
+                    dominator: dominator.filter(|&d| d != root),
+                    retained_size: retained[object.id],
+                    reachable: dominator.is_some(),
+                }
+            })
+            .collect();
+
+        HeapAnalysis { objects }
+    }
+}
+
+impl Display for HeapAnalysis {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (reachable, garbage): (Vec<_>, Vec<_>) =
+            self.objects.iter().partition(|o| o.reachable);
+
+        let bytes = |objects: &[&ObjectAnalysis]| -> usize {
+            objects.iter().map(|o| o.size).sum()
+        };
+
+        writeln!(
+            f,
+            "{:>12} objects {:>12} bytes  reachable",
+            reachable.len(),
+            bytes(&reachable)
+        )?;
+        writeln!(
+            f,
+            "{:>12} objects {:>12} bytes  unreachable",
+            garbage.len(),
+            bytes(&garbage)
+        )?;
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>8} {:>11}  class",
+            "retained", "size", "id", "dominator"
+        )?;
+
+        for object in self.largest().into_iter().take(HeapAnalysis::SHOWN) {
+            let dominator = match object.dominator {
+                Some(id) => id.to_string(),
+                None => "root".into(),
+            };
+
+            writeln!(
+                f,
+                "{:>12} {:>12} {:>8} {:>11}  {}",
+                object.retained_size,
+                object.size,
+                object.id,
+                dominator,
+                object.class.name()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::heap_snapshot::{ObjectSnapshot, Root};
+
+    /// A snapshot of lists with the given references, each 10 bytes, with the
+    /// first object as the only root.
+    fn snapshot(references: &[&[usize]]) -> HeapSnapshot {
+        let objects = references
+            .iter()
+            .enumerate()
+            .map(|(id, references)| ObjectSnapshot {
+                id,
+                class: ClassId::List,
+                size: 10,
+                references: references.to_vec(),
+                root: None,
+            })
+            .collect();
+
+        HeapSnapshot {
+            version: HeapSnapshot::VERSION,
+            objects,
+            roots: vec![Root::Stack {
+                index: 0,
+                object: 0,
+            }],
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let analysis = snapshot(&[&[1], &[2], &[]]).analyze();
+        let dominators: Vec<_> =
+            analysis.objects.iter().map(|o| o.dominator).collect();
+        let retained: Vec<_> =
+            analysis.objects.iter().map(|o| o.retained_size).collect();
+
+        assert_eq!(dominators, [None, Some(0), Some(1)]);
+        assert_eq!(retained, [30, 20, 10]);
+    }
+
+    #[test]
+    fn diamond() {
+        // 0 -> 1 -> 3
+        // 0 -> 2 -> 3
+        let analysis = snapshot(&[&[1, 2], &[3], &[3], &[]]).analyze();
+
+        assert_eq!(analysis.objects[3].dominator, Some(0));
+        assert_eq!(analysis.objects[1].retained_size, 10);
+        assert_eq!(analysis.objects[0].retained_size, 40);
+    }
+
+    #[test]
+    fn cycle() {
+        let analysis = snapshot(&[&[1], &[2], &[1]]).analyze();
+
+        assert_eq!(analysis.objects[2].dominator, Some(1));
+        assert_eq!(analysis.objects[1].retained_size, 20);
+    }
+
+    #[test]
+    fn unreachable() {
+        let analysis = snapshot(&[&[], &[0]]).analyze();
+
+        assert!(!analysis.objects[1].reachable);
+        assert_eq!(analysis.objects[1].dominator, None);
+        assert_eq!(analysis.largest().len(), 1);
+    }
+}
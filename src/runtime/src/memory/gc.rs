@@ -90,6 +90,17 @@ impl GcAny {
         unsafe { self.ptr.as_ref() }
     }
 
+    /// View the pointer as a mutable reference to an [`Object`].
+    ///
+    /// # Safety
+    ///
+    /// Nothing else can be using the object.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn deref_mut(&self) -> &mut Object {
+        self.ptr.cast().as_mut()
+    }
+
     #[inline]
     pub fn is_a<T: Class>(self) -> bool {
         self.deref().class_id() == T::ID
@@ -109,6 +120,17 @@ impl GcAny {
     }
 }
 
+impl From<&Object> for GcAny {
+    /// Every [`Object`] is managed by the collector, so a reference to one
+    /// can be turned back into a pointer.
+    #[inline]
+    fn from(object: &Object) -> Self {
+        GcAny {
+            ptr: NonNull::from(object),
+        }
+    }
+}
+
 impl Deref for GcAny {
     type Target = Object;
 
@@ -0,0 +1,216 @@
+//! Handles which keep values alive while the host holds on to them.
+//!
+//! The collector only knows about values it can find from the VM, so a
+//! [`Value`] kept in a Rust variable or data structure can be freed out from
+//! under it by the next allocation. Putting the value in a handle makes it a
+//! root until the handle goes away.
+//!
+//! There are two kinds of handle:
+//!
+//! - A [`Local`] is for values which are only needed for a little while. They
+//!   belong to a [`HandleScope`], and they all stop being roots at once when
+//!   the scope is dropped.
+//!
+//! - A [`Global`] is for values which need to live as long as the host wants,
+//!   like a callback kept in a host object. It's a root until it's dropped.
+//!
+//! Handles don't borrow the [`VirtualMachine`], so it can still be used while
+//! they're around. They can outlive it, but the values they point to are freed
+//! along with it, so using a handle after its VM is dropped panics.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    memory::{Trace, WorkList},
+    Value, VirtualMachine,
+};
+
+/// The values the host is holding on to, which the VM shares with the handles.
+#[derive(Debug, Default)]
+pub(crate) struct Handles {
+    /// The values held by [`Local`]s, in the order they were made.
+    locals: Vec<Value>,
+
+    /// The values held by [`Global`]s, with `None` for slots which are free.
+    globals: Vec<Option<Value>>,
+
+    /// The free slots in `globals`.
+    free: Vec<usize>,
+
+    /// Set once the VM is dropped, after which there's nothing to hold.
+    dropped: bool,
+}
+
+impl Handles {
+    /// Let go of every value, since the VM they're in is going away.
+    pub(crate) fn vm_dropped(&mut self) {
+        self.locals.clear();
+        self.globals.iter_mut().for_each(|slot| *slot = None);
+        self.dropped = true;
+    }
+
+    /// Panic if the VM is gone, instead of handing out a dangling value.
+    fn check_vm(&self) {
+        assert!(!self.dropped, "handle used after its VM was dropped");
+    }
+
+    /// The values held by locals.
+    pub(crate) fn locals(&self) -> &[Value] {
+        &self.locals
+    }
+
+    /// The values held by globals, along with their slot.
+    pub(crate) fn globals(&self) -> impl Iterator<Item = (usize, Value)> + '_ {
+        self.globals
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.map(|value| (i, value)))
+    }
+}
+
+impl Trace for Handles {
+    fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        for value in &self.locals {
+            value.enqueue_gc_references(worklist);
+        }
+
+        for (_, value) in self.globals() {
+            value.enqueue_gc_references(worklist);
+        }
+    }
+}
+
+/// A scope that [`Local`] handles can be made in, from
+/// [`VirtualMachine::handle_scope`].
+///
+/// Scopes can be nested, but they need to be dropped in the opposite order
+/// they were made in.
+pub struct HandleScope {
+    handles: Rc<RefCell<Handles>>,
+
+    /// How many locals there were when the scope was made.
+    base: usize,
+}
+
+impl HandleScope {
+    /// Keep `value` alive until this scope is dropped.
+    pub fn local(&self, value: Value) -> Local<'_> {
+        let mut handles = self.handles.borrow_mut();
+        handles.check_vm();
+        let index = handles.locals.len();
+        handles.locals.push(value);
+
+        Local { scope: self, index }
+    }
+}
+
+impl Drop for HandleScope {
+    fn drop(&mut self) {
+        let mut handles = self.handles.borrow_mut();
+
+        debug_assert!(
+            handles.dropped || handles.locals.len() >= self.base,
+            "handle scopes dropped out of order"
+        );
+
+        handles.locals.truncate(self.base);
+    }
+}
+
+/// A value kept alive by a [`HandleScope`].
+#[derive(Clone, Copy)]
+pub struct Local<'scope> {
+    scope: &'scope HandleScope,
+    index: usize,
+}
+
+impl Local<'_> {
+    /// The value this handle is keeping alive.
+    pub fn get(&self) -> Value {
+        let handles = self.scope.handles.borrow();
+        handles.check_vm();
+        handles.locals[self.index]
+    }
+
+    /// Change the value this handle is keeping alive.
+    pub fn set(&self, value: Value) {
+        let mut handles = self.scope.handles.borrow_mut();
+        handles.check_vm();
+        handles.locals[self.index] = value;
+    }
+}
+
+/// A value kept alive until the handle is dropped, from
+/// [`VirtualMachine::global`].
+pub struct Global {
+    handles: Rc<RefCell<Handles>>,
+    slot: usize,
+}
+
+impl Global {
+    /// The value this handle is keeping alive.
+    pub fn get(&self) -> Value {
+        let handles = self.handles.borrow();
+        handles.check_vm();
+        handles.globals[self.slot]
+            .expect("global handle slots are only freed on drop")
+    }
+
+    /// Change the value this handle is keeping alive.
+    pub fn set(&self, value: Value) {
+        let mut handles = self.handles.borrow_mut();
+        handles.check_vm();
+        handles.globals[self.slot] = Some(value);
+    }
+}
+
+impl Clone for Global {
+    /// Make another handle to the same value, which keeps it alive on its own.
+    fn clone(&self) -> Self {
+        global(&self.handles, self.get())
+    }
+}
+
+impl Drop for Global {
+    fn drop(&mut self) {
+        let mut handles = self.handles.borrow_mut();
+        handles.globals[self.slot] = None;
+        handles.free.push(self.slot);
+    }
+}
+
+fn global(handles: &Rc<RefCell<Handles>>, value: Value) -> Global {
+    let mut table = handles.borrow_mut();
+    table.check_vm();
+
+    let slot = match table.free.pop() {
+        Some(slot) => {
+            table.globals[slot] = Some(value);
+            slot
+        }
+        None => {
+            table.globals.push(Some(value));
+            table.globals.len() - 1
+        }
+    };
+
+    Global {
+        handles: handles.clone(),
+        slot,
+    }
+}
+
+impl VirtualMachine {
+    /// Start a new scope for [`Local`] handles.
+    pub fn handle_scope(&self) -> HandleScope {
+        HandleScope {
+            handles: self.handles.clone(),
+            base: self.handles.borrow().locals.len(),
+        }
+    }
+
+    /// Keep `value` alive until the returned handle is dropped.
+    pub fn global(&self, value: Value) -> Global {
+        global(&self.handles, value)
+    }
+}
@@ -0,0 +1,300 @@
+//! Snapshots of everything on the heap, for tracking down leaks.
+//!
+//! A [`HeapSnapshot`] lists every object the collector knows about, what it
+//! points to, and the roots which keep things alive. It can be written out as
+//! JSON to look at later, and [analyzed][HeapSnapshot::analyze] to find out
+//! which objects are keeping the most memory alive.
+//!
+//! # Format
+//!
+//! The JSON looks like this:
+//!
+//! ```json
+//! {
+//!   "version": 2,
+//!   "objects": [
+//!     { "id": 0, "class": "List", "size": 48, "references": [1], "root": 0 },
+//!     { "id": 1, "class": "String", "size": 40, "references": [], "root": 0 },
+//!     { "id": 2, "class": "Tuple", "size": 64, "references": [], "root": null }
+//!   ],
+//!   "roots": [
+//!     { "kind": "stack", "index": 3, "object": 0 },
+//!     { "kind": "module", "index": 0, "object": 7 },
+//!     { "kind": "open_capture", "index": 0, "object": 9 },
+//!     { "kind": "checkpoint", "object": 4 },
+//!     { "kind": "local", "index": 0, "object": 2 },
+//!     { "kind": "global", "index": 5, "object": 1 }
+//!   ]
+//! }
+//! ```
+//!
+//! - `version` is [`HeapSnapshot::VERSION`], which changes whenever the format
+//!   does.
+//!
+//! - Each object's `id` is its position in `objects`. Ids are only meaningful
+//!   inside one snapshot.
+//!
+//! - `class` is the [`ClassId`] name, and `size` is in bytes.
+//!
+//! - `references` are the ids of the objects it points to, in the order the
+//!   collector would find them. The same id can show up more than once.
+//!
+//! - `root` is the position in `roots` of the first root the object can be
+//!   reached from, or `null` if it can't be reached at all. Unreachable
+//!   objects are garbage which hasn't been collected yet.
+//!
+//! - Each root has a `kind`, the `object` it points to, and except for the
+//!   `checkpoint` an `index`. For `stack` roots that's the stack slot, for
+//!   `module` roots it's the order modules were loaded in, and for
+//!   `open_capture` roots it's the position in the open captures list, most
+//!   recent first. For `local` and `global` roots, which are [handles] held by
+//!   the host, it's the handle's slot.
+//!
+//! [handles]: crate::memory::handles
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    memory::{ClassId, GcAny, Object, WorkList},
+    VirtualMachine,
+};
+
+/// Everything on the heap at one point in time, see the [module
+/// documentation][self] for details.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeapSnapshot {
+    pub version: u32,
+    pub objects: Vec<ObjectSnapshot>,
+    pub roots: Vec<Root>,
+}
+
+/// One object in a [`HeapSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectSnapshot {
+    pub id: usize,
+    pub class: ClassId,
+    pub size: usize,
+    pub references: Vec<usize>,
+    pub root: Option<usize>,
+}
+
+/// Something outside the heap which keeps an object alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Root {
+    /// A value on the stack.
+    Stack { index: usize, object: usize },
+
+    /// A loaded module.
+    Module { index: usize, object: usize },
+
+    /// A capture cell which still points into the stack.
+    OpenCapture { index: usize, object: usize },
+
+    /// The top-level closure saved by [`VirtualMachine::extend`].
+    Checkpoint { object: usize },
+
+    /// A value held by a [`Local`][crate::memory::Local] handle.
+    Local { index: usize, object: usize },
+
+    /// A value held by a [`Global`][crate::memory::Global] handle.
+    Global { index: usize, object: usize },
+}
+
+impl Root {
+    /// The id of the object this root keeps alive.
+    pub fn object(&self) -> usize {
+        match self {
+            Root::Stack { object, .. }
+            | Root::Module { object, .. }
+            | Root::OpenCapture { object, .. }
+            | Root::Checkpoint { object }
+            | Root::Local { object, .. }
+            | Root::Global { object, .. } => *object,
+        }
+    }
+}
+
+impl HeapSnapshot {
+    /// The version of the format snapshots are written in.
+    pub const VERSION: u32 = 2;
+
+    /// Write the snapshot as JSON.
+    pub fn write_json(&self, writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Read a snapshot written by [`HeapSnapshot::write_json`].
+    ///
+    /// This fails if the JSON isn't a snapshot, if it's from a different
+    /// version, or if any of the ids in it are wrong.
+    pub fn read_json(reader: impl Read) -> io::Result<HeapSnapshot> {
+        let snapshot: HeapSnapshot = serde_json::from_reader(reader)?;
+        snapshot.validate()?;
+        Ok(snapshot)
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let invalid = |message: &str| {
+            Err(io::Error::new(io::ErrorKind::InvalidData, message))
+        };
+
+        if self.version != HeapSnapshot::VERSION {
+            return invalid("unsupported heap snapshot version");
+        }
+
+        let count = self.objects.len();
+
+        for (i, object) in self.objects.iter().enumerate() {
+            if object.id != i {
+                return invalid("object ids must match their position");
+            }
+
+            if object.references.iter().any(|&id| id >= count) {
+                return invalid("object references an unknown id");
+            }
+
+            if object.root.is_some_and(|root| root >= self.roots.len()) {
+                return invalid("object retained by an unknown root");
+            }
+        }
+
+        if self.roots.iter().any(|root| root.object() >= count) {
+            return invalid("root points to an unknown id");
+        }
+
+        Ok(())
+    }
+}
+
+impl VirtualMachine {
+    /// Take a snapshot of everything on the heap.
+    ///
+    /// This doesn't collect garbage first, so anything unreachable which
+    /// hasn't been collected yet shows up too.
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        // Objects don't move, so they're looked up by address.
+        let mut ids: HashMap<*const Object, usize> = HashMap::new();
+        let mut objects = Vec::new();
+
+        for head in [self.gc_state.heap_head, self.gc_state.nursery_head] {
+            let mut list = head;
+
+            while let Some(ptr) = list {
+                ids.insert(ptr.deref(), objects.len());
+                objects.push(ptr);
+                list = ptr.deref().gc_header().next();
+            }
+        }
+
+        let references: Vec<Vec<usize>> = objects
+            .iter()
+            .map(|ptr| {
+                let mut worklist = WorkList::everything();
+                ptr.deref().enqueue_gc_references(&mut worklist);
+
+                let mut references = Vec::new();
+                while let Some(reference) = worklist.pop() {
+                    references.push(ids[&(reference.deref() as *const _)]);
+                }
+                references
+            })
+            .collect();
+
+        let roots = self.snapshot_roots(&ids);
+
+        // Work out which root gets to each object first.
+        let mut retained_by = vec![None; objects.len()];
+        let mut queue = VecDeque::new();
+
+        for (i, root) in roots.iter().enumerate() {
+            if retained_by[root.object()].is_none() {
+                retained_by[root.object()] = Some(i);
+                queue.push_back(root.object());
+            }
+
+            while let Some(id) = queue.pop_front() {
+                for &reference in &references[id] {
+                    if retained_by[reference].is_none() {
+                        retained_by[reference] = Some(i);
+                        queue.push_back(reference);
+                    }
+                }
+            }
+        }
+
+        let objects = objects
+            .iter()
+            .zip(references)
+            .enumerate()
+            .map(|(id, (ptr, references))| ObjectSnapshot {
+                id,
+                class: ptr.deref().class_id(),
+                size: ptr.deref().size(),
+                references,
+                root: retained_by[id],
+            })
+            .collect();
+
+        HeapSnapshot {
+            version: HeapSnapshot::VERSION,
+            objects,
+            roots,
+        }
+    }
+
+    /// The roots, in the same order the collector uses.
+    fn snapshot_roots(&self, ids: &HashMap<*const Object, usize>) -> Vec<Root> {
+        let id = |ptr: GcAny| ids[&(ptr.deref() as *const _)];
+        let mut roots = Vec::new();
+
+        for (index, value) in self.stack().as_slice().iter().enumerate() {
+            if let Some(ptr) = value.as_gc_any() {
+                let object = id(ptr);
+                roots.push(Root::Stack { index, object });
+            }
+        }
+
+        if let Some(ptr) = self
+            .checkpoint()
+            .and_then(|c| c.value())
+            .and_then(|v| v.as_gc_any())
+        {
+            roots.push(Root::Checkpoint { object: id(ptr) });
+        }
+
+        for (index, module) in self.modules().iter().enumerate() {
+            let object = id(GcAny::from(*module));
+            roots.push(Root::Module { index, object });
+        }
+
+        for (index, cell) in self.open_captures.iter().enumerate() {
+            let object = id(GcAny::from(*cell));
+            roots.push(Root::OpenCapture { index, object });
+        }
+
+        let handles = self.handles.borrow();
+
+        for (index, value) in handles.locals().iter().enumerate() {
+            if let Some(ptr) = value.as_gc_any() {
+                let object = id(ptr);
+                roots.push(Root::Local { index, object });
+            }
+        }
+
+        for (index, value) in handles.globals() {
+            if let Some(ptr) = value.as_gc_any() {
+                let object = id(ptr);
+                roots.push(Root::Global { index, object });
+            }
+        }
+
+        roots
+    }
+}
@@ -1,31 +1,49 @@
-//! A stub for the garbage collector.
+//! Allocating and freeing runtime values.
 //!
-//! I'm not sure what I want this to look like yet, and I've been indecisive to
-//! too long. So I'm just making it a blank.
+//! Every value which doesn't fit in a [`Value`] is an [`Object`] of some
+//! [`Class`], allocated straight from the system allocator and referred to by
+//! a [`Gc`] pointer. The collector keeps track of every object and frees the
+//! ones which can't be reached any more. It's generational and incremental,
+//! see `collector.rs` for how it works.
 //!
-//! Right now any GC value that's allocated is actually just a leaked `*mut`
-//! pointer.
-
-// TODO: How does allocation fail? Probably just adding a `-> Result<()>` to a
-//       bunch of these. Look at Rust's [Allocator][1].
-//
-//       [1] https://doc.rust-lang.org/std/alloc/trait.Allocator.html
+//! Allocating can fail. Before each allocation the collector gets a chance to
+//! run, and if the VM has a heap [limit][crate::Limits] which the new object
+//! would go over, a full collection is forced first. If it still doesn't fit,
+//! [`Error::OutOfMemory`] is returned and nothing is changed. Storage an
+//! object owns outside its allocation, like a list's elements, counts towards
+//! the limit too, both when it's allocated and whenever it grows.
+//!
+//! Values only held by the host aren't found by the collector, so they need
+//! to be kept in a [`Local`] or [`Global`] handle to stay alive.
 
-use std::{alloc::Layout, ptr::NonNull};
+use std::{alloc::Layout, cell::RefCell, ptr::NonNull};
 
 mod class;
+mod config;
+mod dominators;
 mod gc;
+mod handles;
+mod heap_snapshot;
 mod object;
+mod stats;
 mod trace;
 
 pub(crate) mod collector;
 
-use crate::VirtualMachine;
+pub(crate) use self::handles::Handles;
+
+use crate::{Error, Result, Value, VirtualMachine};
 
 pub use self::{
     class::{Class, ClassId},
+    collector::Finalizer,
+    config::GcConfig,
+    dominators::{HeapAnalysis, ObjectAnalysis},
     gc::{Gc, GcAny},
+    handles::{Global, HandleScope, Local},
+    heap_snapshot::{HeapSnapshot, ObjectSnapshot, Root},
     object::Object,
+    stats::GcStats,
     trace::{Trace, WorkList},
 };
 
@@ -41,6 +59,12 @@ where
     /// from the given argument. This is above whatever `size_of::<Class>` is.
     fn extra_size(arg: &A) -> usize;
 
+    /// The bytes the [`Class`] will own outside its allocation when
+    /// initialized from the given argument, see [`Class::owned_size`].
+    fn owned_size_from(_arg: &A) -> usize {
+        0
+    }
+
     /// Initialize the pointer using the given value.
     ///
     /// # Safety
@@ -77,7 +101,7 @@ impl VirtualMachine {
     /// Allocate a new [`Object`] and initialize it using a [`Default`]
     /// value it can be initiated from.
     #[allow(dead_code)]
-    pub(crate) fn make<C, A>(&mut self) -> Gc<C>
+    pub(crate) fn make<C, A>(&mut self) -> Result<Gc<C>>
     where
         C: Class + InitFrom<A>,
         A: Default,
@@ -86,16 +110,21 @@ impl VirtualMachine {
     }
 
     /// Allocate a new [`Object`], initializing it from the given argument.
-    pub(crate) fn make_from<C, A>(&mut self, arg: A) -> Gc<C>
+    ///
+    /// This fails with [`Error::OutOfMemory`] if there's no room for the
+    /// object, even after collecting garbage. Nothing is changed if it fails,
+    /// so the VM can keep going.
+    pub(crate) fn make_from<C, A>(&mut self, arg: A) -> Result<Gc<C>>
     where
         C: Class + InitFrom<A>,
     {
         // find the layout needed for the object.
         let extra = C::extra_size(&arg);
         let layout = VirtualMachine::object_layout_with_extra::<C>(extra);
+        let owned = C::owned_size_from(&arg);
 
         unsafe {
-            let raw = self.allocate(layout);
+            let raw = self.allocate(layout, owned)?;
 
             // SAFETY: For both parts of initialization, raw is uninitialized
             //         and points to something that `layout` fits in, because
@@ -116,7 +145,7 @@ impl VirtualMachine {
             #[cfg(feature = "gc_trace")]
             eprintln!("initialized {:?} as {:?}", raw, gc.deref());
 
-            gc.cast_unchecked()
+            Ok(gc.cast_unchecked())
         }
     }
 
@@ -130,13 +159,17 @@ impl VirtualMachine {
         unsafe { Layout::from_size_align_unchecked(base_size + extra, align) }
     }
 
-    /// Allocate for the given layout.
+    /// Allocate for the given layout, counting `owned` bytes the object will
+    /// own outside the allocation too.
     ///
     /// # Safety
     ///
     /// The pointer returned satisfies the layout, is not null, but is not
     /// initialized either.
     ///
+    /// If the heap limit would be passed even after a full collection, or the
+    /// system allocator fails, this returns [`Error::OutOfMemory`] instead.
+    ///
     /// This memory isn't tracked in any way, and it will leak.
     ///
     /// If the object is to be managed by the collector, the caller is
@@ -145,18 +178,36 @@ impl VirtualMachine {
     ///
     /// Otherwise it's the caller's responsibility to ensure it is freed
     /// appropriately.
-    unsafe fn allocate(&mut self, layout: Layout) -> *mut Object {
+    unsafe fn allocate(
+        &mut self,
+        layout: Layout,
+        owned: usize,
+    ) -> Result<*mut Object> {
+        let size = layout.size() + owned;
+
         self.collect_garbage();
 
-        #[allow(clippy::let_and_return)]
-        let ptr = std::alloc::alloc(layout) as _;
+        if self.gc_state.would_exceed_limit(size) {
+            self.force_collect_garbage();
+
+            if self.gc_state.would_exceed_limit(size) {
+                return Err(Error::OutOfMemory);
+            }
+        }
+
+        let ptr: *mut Object = std::alloc::alloc(layout) as _;
+
+        if ptr.is_null() {
+            return Err(Error::OutOfMemory);
+        }
 
-        self.gc_state.used += layout.size();
+        self.gc_state.used += size;
+        self.gc_state.stats.bytes_allocated += size;
 
         #[cfg(feature = "gc_trace")]
         eprintln!("allocating {:?}", ptr);
 
-        ptr
+        Ok(ptr)
     }
 
     /// Deallocate the memory used by a GC pointer.
@@ -170,8 +221,56 @@ impl VirtualMachine {
 
         let layout =
             Layout::from_size_align_unchecked(gc.deref().size(), Object::ALIGN);
+        let size = layout.size() + gc.deref().owned_size();
+        gc.deref_mut().drop_in_place();
         let ptr = std::mem::transmute(gc);
         std::alloc::dealloc(ptr, layout);
-        self.gc_state.used -= layout.size();
+        self.gc_state.used -= size;
+        self.gc_state.stats.bytes_freed += size;
+    }
+
+    /// Push onto a [`Vec`] belonging to `owner`, counting whatever it grows
+    /// by against the heap.
+    ///
+    /// This can collect garbage, so `owner` must be rooted. If the heap limit
+    /// is still passed after a full collection, the push is undone and this
+    /// returns [`Error::OutOfMemory`].
+    pub(crate) fn push_owned<T>(
+        &mut self,
+        owner: &Object,
+        vec: &RefCell<Vec<T>>,
+        item: T,
+    ) -> Result<()>
+    where
+        T: Copy,
+        Value: From<T>,
+    {
+        let before = vec.borrow().capacity();
+        vec.borrow_mut().push(item);
+        self.write_barrier(owner, Value::from(item));
+
+        let after = vec.borrow().capacity();
+        if after == before {
+            return Ok(());
+        }
+
+        let bytes = |capacity: usize| capacity * std::mem::size_of::<T>();
+        self.gc_state.grew(owner.gc_header(), bytes(after - before));
+
+        if self.gc_state.would_exceed_limit(0) {
+            self.force_collect_garbage();
+
+            if self.gc_state.would_exceed_limit(0) {
+                let mut elements = vec.borrow_mut();
+                elements.pop();
+                elements.shrink_to(before);
+                let freed = bytes(after - elements.capacity());
+                self.gc_state.shrank(owner.gc_header(), freed);
+
+                return Err(Error::OutOfMemory);
+            }
+        }
+
+        Ok(())
     }
 }
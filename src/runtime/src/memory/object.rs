@@ -23,12 +23,14 @@ macro_rules! dispatch {
         match $obj.class_id {
             ClassId::CaptureCell => $f( $obj.downcast::<CaptureCell>().unwrap(), $( $arg, )*),
             ClassId::Closure => $f( $obj.downcast::<Function>().unwrap(), $( $arg, )*),
+            ClassId::Foreign => $f( $obj.downcast::<Foreign>().unwrap(), $( $arg, )*),
             ClassId::Keyword => $f( $obj.downcast::<Keyword>().unwrap(), $( $arg, )*),
             ClassId::List    => $f( $obj.downcast::<List>().unwrap(), $( $arg, )*),
             ClassId::Module => $f( $obj.downcast::<Module>().unwrap(), $( $arg, )* ),
             ClassId::Prototype => $f( $obj.downcast::<Prototype>().unwrap(), $( $arg, )* ),
             ClassId::String  => $f( $obj.downcast::<String>().unwrap(), $( $arg, )*),
             ClassId::Tuple  => $f( $obj.downcast::<Tuple>().unwrap(), $( $arg, )*),
+            ClassId::Weak  => $f( $obj.downcast::<Weak>().unwrap(), $( $arg, )*),
 
         }
     };
@@ -113,10 +115,42 @@ impl Object {
         })
     }
 
+    /// Run the destructor for the object's class, so anything it owns outside
+    /// the heap is freed.
+    ///
+    /// # Safety
+    ///
+    /// The object can't be used at all after this, it should only be called
+    /// right before it's deallocated.
+    pub(crate) unsafe fn drop_in_place(&mut self) {
+        unsafe fn drop<C: Class>(object: &mut Object) {
+            std::ptr::drop_in_place(object as *mut Object as *mut C);
+        }
+
+        match self.class_id {
+            ClassId::CaptureCell => drop::<CaptureCell>(self),
+            ClassId::Closure => drop::<Function>(self),
+            ClassId::Foreign => drop::<Foreign>(self),
+            ClassId::Keyword => drop::<Keyword>(self),
+            ClassId::List => drop::<List>(self),
+            ClassId::Module => drop::<Module>(self),
+            ClassId::Prototype => drop::<Prototype>(self),
+            ClassId::String => drop::<String>(self),
+            ClassId::Tuple => drop::<Tuple>(self),
+            ClassId::Weak => drop::<Weak>(self),
+        }
+    }
+
     /// The size of the object's underlying allocation, in bytes.
     pub(crate) fn size(&self) -> usize {
         self.size
     }
+
+    /// The bytes the object owns outside its allocation, see
+    /// [`Class::owned_size`].
+    pub(crate) fn owned_size(&self) -> usize {
+        dispatch!(Class::owned_size, self,)
+    }
 }
 
 impl PartialEq for Object {
@@ -172,10 +206,10 @@ impl PrimitiveOperations for Object {
         &self,
         key: Value,
         new: Value,
-        _rt: &mut VirtualMachine,
+        rt: &mut VirtualMachine,
     ) -> Result<(), Error> {
         if let Some(list) = self.downcast::<List>() {
-            list.set_index(key, new)
+            list.set_index(key, new, rt)
         } else {
             Err(Error::OperationNotSupported {
                 type_name: self.type_name(),
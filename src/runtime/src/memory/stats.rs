@@ -0,0 +1,120 @@
+//! Statistics about what the garbage collector has been up to.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    memory::{ClassId, GcAny},
+    VirtualMachine,
+};
+
+/// A summary of the garbage collector's work so far, from
+/// [`VirtualMachine::gc_stats`].
+#[derive(Debug, Clone, Default)]
+pub struct GcStats {
+    /// How many times the nursery was collected on its own.
+    pub minor_collections: usize,
+
+    /// How many full collections have finished.
+    pub major_collections: usize,
+
+    /// The total number of bytes ever allocated.
+    pub bytes_allocated: usize,
+
+    /// The total number of bytes freed by collections.
+    pub bytes_freed: usize,
+
+    /// The number of bytes allocated right now, including the storage objects
+    /// own outside their allocations.
+    pub bytes_in_use: usize,
+
+    /// How many times the program was stopped to collect garbage.
+    pub pauses: usize,
+
+    /// The total time the program was stopped to collect garbage.
+    pub total_pause: Duration,
+
+    /// The longest the program was stopped to collect garbage.
+    pub longest_pause: Duration,
+
+    /// How many objects of each class are allocated right now.
+    ///
+    /// Anything that's become garbage since the last collection is still
+    /// counted, so right after [`VirtualMachine::force_collect_garbage`]
+    /// these are exactly the live objects.
+    pub live_objects: HashMap<ClassId, usize>,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12}  minor collections\n{:>12}  full collections",
+            self.minor_collections, self.major_collections
+        )?;
+
+        writeln!(
+            f,
+            "{:>12}  bytes allocated\n{:>12}  bytes freed\n{:>12}  bytes in use",
+            self.bytes_allocated, self.bytes_freed, self.bytes_in_use
+        )?;
+
+        writeln!(
+            f,
+            "{:>12}  pauses\n{:>12}  total pause time\n{:>12}  longest pause",
+            self.pauses,
+            format!("{:.2?}", self.total_pause),
+            format!("{:.2?}", self.longest_pause),
+        )?;
+
+        let mut classes: Vec<_> = self.live_objects.iter().collect();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.name().cmp(b.0.name())));
+
+        writeln!(f)?;
+        writeln!(f, "{:>12}  class", "objects")?;
+
+        for (class, count) in classes {
+            writeln!(f, "{:>12}  {}", count, class.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl VirtualMachine {
+    /// What the garbage collector has done so far, and what's on the heap.
+    pub fn gc_stats(&self) -> GcStats {
+        let mut stats = self.gc_state.stats.clone();
+        stats.bytes_in_use = self.gc_state.used;
+
+        let mut count = |mut list: Option<GcAny>| {
+            while let Some(ptr) = list {
+                *stats
+                    .live_objects
+                    .entry(ptr.deref().class_id())
+                    .or_default() += 1;
+                list = ptr.deref().gc_header().next();
+            }
+        };
+
+        count(self.gc_state.heap_head);
+        count(self.gc_state.nursery_head);
+
+        stats
+    }
+
+    /// Run `collect`, counting the time it takes as a pause.
+    pub(crate) fn pause(&mut self, collect: impl FnOnce(&mut VirtualMachine)) {
+        let start = Instant::now();
+        collect(self);
+        let pause = start.elapsed();
+
+        let stats = &mut self.gc_state.stats;
+        stats.pauses += 1;
+        stats.total_pause += pause;
+        stats.longest_pause = stats.longest_pause.max(pause);
+    }
+}
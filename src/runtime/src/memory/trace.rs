@@ -2,18 +2,58 @@ use std::collections::VecDeque;
 
 use super::GcAny;
 
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct WorkList {
     list: VecDeque<GcAny>,
+    filter: Filter,
+}
+
+/// Which objects are left out of a [`WorkList`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+    /// Marked objects are left out, for a full collection.
+    #[default]
+    Marked,
+
+    /// Marked and old objects are left out, for a minor collection.
+    MarkedOrOld,
+
+    /// Nothing is left out, for looking at the heap without collecting.
+    Nothing,
 }
 
 impl WorkList {
+    /// A work list which ignores objects in the old generation.
+    pub(crate) fn young_only() -> WorkList {
+        WorkList {
+            list: VecDeque::new(),
+            filter: Filter::MarkedOrOld,
+        }
+    }
+
+    /// A work list which takes everything, marked or not.
+    pub(crate) fn everything() -> WorkList {
+        WorkList {
+            list: VecDeque::new(),
+            filter: Filter::Nothing,
+        }
+    }
+
     /// Add a [`GcAny`] value to the work list.
     ///
-    /// The value is only actually added it's not marked.
+    /// The value is only actually added it's not marked, and it's not old
+    /// when only young objects are wanted.
     pub fn enqueue(&mut self, ptr: impl Into<GcAny>) {
         let any = ptr.into();
-        if !any.deref().gc_header().is_marked() {
+        let header = any.deref().gc_header();
+
+        let skip = match self.filter {
+            Filter::Marked => header.is_marked(),
+            Filter::MarkedOrOld => header.is_marked() || header.is_old(),
+            Filter::Nothing => false,
+        };
+
+        if !skip {
             self.list.push_back(any);
         }
     }
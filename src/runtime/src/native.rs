@@ -0,0 +1,275 @@
+//! Functions and types written in Rust, for Kurt code to use.
+//!
+//! These are usually made with the `kurt-macros` crate. `#[kurt::function]`
+//! implements [`NativeFunction`] for a Rust function, and `#[kurt::class]`
+//! implements [`NativeClass`] for a type with methods. A [`Library`] collects
+//! them together so a host can [install][VirtualMachine::install] the lot at
+//! once.
+//!
+//! Without `import`, an installed library is a value which Kurt code indexes
+//! by name, like `math[:add](1, 2)`. The host passes it in, usually as an
+//! argument to [`VirtualMachine::call`], or finds it again with
+//! [`VirtualMachine::library`].
+
+use std::{any::Any, fmt::Debug};
+
+use crate::{
+    classes::{Foreign, HostObject, Keyword},
+    memory::{Gc, Trace, WorkList},
+    Error, IntoValue, Result, Value, VirtualMachine,
+};
+
+/// A Rust function which Kurt code can call.
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    /// The name it's installed under.
+    pub name: &'static str,
+
+    /// How many arguments it takes.
+    pub arity: u32,
+
+    /// The function, which gets exactly `arity` arguments.
+    pub call: fn(&[Value], &mut VirtualMachine) -> Result<Value>,
+}
+
+/// A Rust method which Kurt code can call on a [`Foreign`] object.
+#[derive(Clone, Copy)]
+pub struct BuiltinMethod {
+    /// The name it's looked up by.
+    pub name: &'static str,
+
+    /// How many arguments it takes, not counting the receiver.
+    pub arity: u32,
+
+    /// The method, which gets the receiver and exactly `arity` arguments.
+    pub call: fn(&Foreign, &[Value], &mut VirtualMachine) -> Result<Value>,
+}
+
+/// Rust functions which can be installed, implemented by `#[kurt::function]`.
+pub trait NativeFunction {
+    const BUILTIN: Builtin;
+}
+
+/// Rust types which Kurt code can use, implemented by `#[kurt::class]`.
+pub trait NativeClass: HostObject {
+    /// The name of the type, and of its constructor.
+    const NAME: &'static str;
+
+    /// The function which makes a new object, if there is one.
+    const CONSTRUCTOR: Option<Builtin>;
+
+    /// The methods objects of this type have.
+    const METHODS: &'static [BuiltinMethod];
+}
+
+/// What native functions can return: anything which can be turned into a
+/// [`Value`], or a [`Result`] of one.
+pub trait NativeReturn {
+    fn into_result(self, vm: &mut VirtualMachine) -> Result<Value>;
+}
+
+impl<T: IntoValue> NativeReturn for T {
+    fn into_result(self, vm: &mut VirtualMachine) -> Result<Value> {
+        self.into_value(vm)
+    }
+}
+
+impl<T: IntoValue> NativeReturn for Result<T> {
+    fn into_result(self, vm: &mut VirtualMachine) -> Result<Value> {
+        self?.into_value(vm)
+    }
+}
+
+/// What a native class's constructor can return: a new object, or a
+/// [`Result`] of one.
+pub trait NewObject<C> {
+    fn new_object(self, vm: &mut VirtualMachine) -> Result<Value>;
+}
+
+impl<C: NativeClass> NewObject<C> for C {
+    fn new_object(self, vm: &mut VirtualMachine) -> Result<Value> {
+        Ok(Value::gc(vm.make_foreign(self)?))
+    }
+}
+
+impl<C: NativeClass> NewObject<C> for Result<C> {
+    fn new_object(self, vm: &mut VirtualMachine) -> Result<Value> {
+        self?.new_object(vm)
+    }
+}
+
+/// Is `key` a keyword or string called `name`?
+pub(crate) fn is_named(key: Value, name: &str) -> bool {
+    if let Some(keyword) = key.as_gc::<Keyword>() {
+        keyword.as_str() == name
+    } else {
+        key.as_str() == Some(name)
+    }
+}
+
+fn check_arity(found: usize, expected: u32) -> Result<()> {
+    if found == expected as usize {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgCount {
+            found: found as u32,
+            expected,
+        })
+    }
+}
+
+impl HostObject for Builtin {
+    fn type_name(&self) -> &'static str {
+        "Builtin"
+    }
+
+    fn call(&self, args: &[Value], vm: &mut VirtualMachine) -> Result<Value> {
+        check_arity(args.len(), self.arity)?;
+        (self.call)(args, vm)
+    }
+
+    fn eq(&self, other: &dyn Any) -> bool {
+        other
+            .downcast_ref::<Builtin>()
+            .is_some_and(|other| std::ptr::fn_addr_eq(self.call, other.call))
+    }
+}
+
+/// A method along with the object it was looked up on, from indexing a
+/// [`Foreign`] object.
+pub(crate) struct BoundMethod {
+    pub(crate) receiver: Gc<Foreign>,
+    pub(crate) method: &'static BuiltinMethod,
+}
+
+impl HostObject for BoundMethod {
+    fn type_name(&self) -> &'static str {
+        "Method"
+    }
+
+    fn call(&self, args: &[Value], vm: &mut VirtualMachine) -> Result<Value> {
+        check_arity(args.len(), self.method.arity)?;
+
+        // The bound method is what's being called, so it's on the stack and
+        // keeps the receiver alive until this returns.
+        (self.method.call)(&self.receiver, args, vm)
+    }
+
+    fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        Value::gc(self.receiver).enqueue_gc_references(worklist);
+    }
+}
+
+/// A collection of native functions and classes, to be installed together.
+#[derive(Clone)]
+pub struct Library {
+    name: String,
+    members: Vec<Builtin>,
+}
+
+impl Library {
+    /// Start a new, empty library called `name`.
+    pub fn new(name: &str) -> Library {
+        Library {
+            name: name.into(),
+            members: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Add a function made by `#[kurt::function]`.
+    pub fn function<F: NativeFunction>(self) -> Library {
+        self.builtin(F::BUILTIN)
+    }
+
+    /// Add a class made by `#[kurt::class]`. Its constructor is installed
+    /// under the name of the class.
+    pub fn class<C: NativeClass>(self) -> Library {
+        match C::CONSTRUCTOR {
+            Some(constructor) => self.builtin(Builtin {
+                name: C::NAME,
+                ..constructor
+            }),
+            None => self,
+        }
+    }
+
+    /// Add a function, replacing any other member with the same name.
+    pub fn builtin(mut self, builtin: Builtin) -> Library {
+        self.members.retain(|member| member.name != builtin.name);
+        self.members.push(builtin);
+        self
+    }
+}
+
+impl Debug for Library {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.members.iter().map(|m| m.name).collect();
+        f.debug_struct("Library")
+            .field("name", &self.name)
+            .field("members", &names)
+            .finish()
+    }
+}
+
+/// An installed [`Library`], as Kurt code sees it.
+struct Installed {
+    members: Vec<(&'static str, Value)>,
+}
+
+impl HostObject for Installed {
+    fn type_name(&self) -> &'static str {
+        "Library"
+    }
+
+    fn index(&self, key: Value, _: &mut VirtualMachine) -> Result<Value> {
+        self.members
+            .iter()
+            .find(|(name, _)| is_named(key, name))
+            .map(|(_, value)| *value)
+            .ok_or(Error::UndefinedMember)
+    }
+
+    fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        for (_, value) in &self.members {
+            value.enqueue_gc_references(worklist);
+        }
+    }
+}
+
+impl VirtualMachine {
+    /// Install `library`, so it can be found by name with
+    /// [`VirtualMachine::library`].
+    ///
+    /// This returns the library's value, which Kurt code can index to find
+    /// its members. Installing a library with the same name as another
+    /// replaces it.
+    pub fn install(&mut self, library: &Library) -> Result<Value> {
+        let scope = self.handle_scope();
+        let mut members = Vec::with_capacity(library.members.len());
+
+        for builtin in &library.members {
+            let value = Value::gc(self.make_foreign(*builtin)?);
+            members.push((builtin.name, scope.local(value)));
+        }
+
+        let members = members
+            .into_iter()
+            .map(|(name, local)| (name, local.get()))
+            .collect();
+
+        let value = Value::gc(self.make_foreign(Installed { members })?);
+        let global = self.global(value);
+        self.libraries.insert(library.name.clone(), global);
+
+        Ok(value)
+    }
+
+    /// The installed library called `name`.
+    pub fn library(&self, name: &str) -> Option<Value> {
+        self.libraries.get(name).map(|global| global.get())
+    }
+}
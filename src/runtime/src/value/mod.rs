@@ -37,6 +37,11 @@
 //! the lower 48 bits to tag the type tag the remaining smaller value like a
 //! small integer, boolean or unicode code point.
 //!
+//! Short strings are common enough that they get a tag too. Any string of up
+//! to [`Value::INLINE_STRING_LEN`] bytes is packed right into the payload,
+//! padded with nul bytes, so only longer strings (or ones containing a nul)
+//! need a heap-allocated [`String`][crate::classes::String].
+//!
 //! One important thing to keep in mind here is that `f64`s can do 53-bit
 //! integers without a loss of precision, so when we do pack 48-bit integers and
 //! natural numbers they're actually smaller than what we could represent with
@@ -50,6 +55,7 @@ mod primitives;
 use common::{i48, u48};
 
 use crate::{
+    classes::String,
     error::CastError,
     memory::{Class, Gc, GcAny, Object, Trace, WorkList},
     primitives::PrimitiveOperations,
@@ -99,6 +105,9 @@ impl Value {
     /// A "safe" non-signaling NaN value.
     pub const NAN: Value = Value(Value::SAFE_NAN_BITS);
 
+    /// The longest string, in bytes, which can be stored inline.
+    pub const INLINE_STRING_LEN: usize = 6;
+
     /// Do the bits of this value represent some other value packed inside a
     /// NaN, or is it a floating point number?
     #[inline(always)]
@@ -146,6 +155,27 @@ impl Value {
         )
     }
 
+    /// Store a short string inline as a [`Value`], if it fits.
+    ///
+    /// It fits if it's at most [`Value::INLINE_STRING_LEN`] bytes and has no
+    /// nul bytes, since those are used to mark where it ends. Anything else
+    /// needs to be allocated, see [`VirtualMachine::string`].
+    ///
+    /// [`VirtualMachine::string`]: crate::VirtualMachine::string
+    #[inline]
+    pub fn inline_string(s: &str) -> Option<Value> {
+        if s.len() > Value::INLINE_STRING_LEN || s.as_bytes().contains(&0) {
+            return None;
+        }
+
+        let mut payload = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            payload |= (byte as u64) << (8 * i);
+        }
+
+        Some(Value(Value::PACKED_MASK | Tag::String as u64 | payload))
+    }
+
     /// Store a [`f64`] as a [`Value`].
     ///
     /// Note that due to how [`Value`] is stored, any NaN value is converted
@@ -269,6 +299,54 @@ impl Value {
         }
     }
 
+    /// Is this value a string stored inline?
+    #[inline]
+    pub const fn is_inline_string(&self) -> bool {
+        self.is_packed_value()
+            && self.0 & Value::TAG_BITS_MASK == Tag::String as u64
+    }
+
+    /// View this value as a [`str`] if it's a string stored inline.
+    #[inline]
+    pub fn as_inline_string(&self) -> Option<&str> {
+        if !self.is_inline_string() {
+            return None;
+        }
+
+        // The payload is the low bytes, which come first in memory.
+        #[cfg(not(target_endian = "little"))]
+        std::compile_error!("Inline strings assume a little-endian target");
+
+        let len = (0..Value::INLINE_STRING_LEN)
+            .find(|i| (self.0 >> (8 * i)) as u8 == 0)
+            .unwrap_or(Value::INLINE_STRING_LEN);
+
+        // SAFETY: The bytes were copied from a `str` by `inline_string`, and
+        //         they're borrowed from `self` so they can't change.
+        unsafe {
+            let bytes = std::slice::from_raw_parts(
+                &self.0 as *const u64 as *const u8,
+                len,
+            );
+            Some(std::str::from_utf8_unchecked(bytes))
+        }
+    }
+
+    /// View this value as a [`str`] if it's a string, whether it's stored
+    /// inline or on the heap.
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        if let Some(s) = self.as_inline_string() {
+            return Some(s);
+        }
+
+        let string = self.as_gc::<String>()?;
+
+        // SAFETY: The string is on the heap, not in the `Gc`, so this is
+        //         valid for as long as the value keeps it alive.
+        unsafe { Some(&*(string.as_str() as *const str)) }
+    }
+
     /// Is this value an [`f64`]?
     #[inline]
     pub const fn is_float(&self) -> bool {
@@ -393,6 +471,9 @@ impl PartialEq for Value {
     ///
     /// If they're both floats, we compare as floats.
     ///
+    /// Strings are equal if they have the same contents, whether or not
+    /// they're stored inline.
+    ///
     /// We defer to self as an [`Object`] to decide equality if they're both
     /// objects.
     ///
@@ -402,6 +483,8 @@ impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         if self.is_float() && other.is_float() {
             self.as_float() == other.as_float()
+        } else if self.is_inline_string() || other.is_inline_string() {
+            self.as_str() == other.as_str()
         } else if self.is_gc_any() && other.is_gc_any() {
             PartialEq::eq(
                 self.as_gc_any().unwrap().deref(),
@@ -428,8 +511,9 @@ impl std::fmt::Debug for Value {
             Tag::Nat => write!(f, "{:?}", self.as_nat().unwrap()),
             Tag::Int => write!(f, "{:?}", self.as_int().unwrap()),
             Tag::Float => write!(f, "{:?}", self.as_float().unwrap()),
+            Tag::String => write!(f, "{}", self.as_inline_string().unwrap()),
             Tag::Object => write!(f, "{:?}", self.as_gc_any().unwrap().deref()),
-            Tag::_Reserved0 | Tag::_Reserved1 => {
+            Tag::_Reserved1 => {
                 write!(f, "<invalid value>")
             }
         }
@@ -589,7 +673,7 @@ enum Tag {
     Char = 0x0002_0000_0000_0000,
     Nat = 0x0003_0000_0000_0000,
     Int = 0x0004_0000_0000_0000,
-    _Reserved0 = 0x0005_0000_0000_0000,
+    String = 0x0005_0000_0000_0000,
     _Reserved1 = 0x0006_0000_0000_0000,
     Object = 0x0007_0000_0000_0000,
 
@@ -609,7 +693,7 @@ impl Value {
             0x0002_0000_0000_0000 => Tag::Char,
             0x0003_0000_0000_0000 => Tag::Nat,
             0x0004_0000_0000_0000 => Tag::Int,
-            0x0005_0000_0000_0000 => Tag::_Reserved0,
+            0x0005_0000_0000_0000 => Tag::String,
             0x0006_0000_0000_0000 => Tag::_Reserved1,
             0x0007_0000_0000_0000 => Tag::Object,
             _ => unreachable!("All legal values are covered"),
@@ -659,6 +743,30 @@ mod tests {
         assert_eq!(a.as_bool(), None);
     }
 
+    #[test]
+    fn packing_inline_string() {
+        for s in ["", "a", "🥳", "abcdef"] {
+            let value = Value::inline_string(s).unwrap();
+            assert!(value.is_inline_string());
+            assert_eq!(value.as_inline_string(), Some(s));
+            assert_eq!(value.as_str(), Some(s));
+            assert!(!value.is_gc_any());
+        }
+
+        assert_eq!(Value::inline_string("abcdefg"), None);
+        assert_eq!(Value::inline_string("a\0b"), None);
+        assert_eq!(Value::UNIT.as_inline_string(), None);
+    }
+
+    #[test]
+    fn inline_string_eq() {
+        let a = Value::inline_string("ab").unwrap();
+        assert_eq!(a, Value::inline_string("ab").unwrap());
+        assert_ne!(a, Value::inline_string("abc").unwrap());
+        assert_ne!(a, Value::inline_string("").unwrap());
+        assert_ne!(a, Value::char('a'));
+    }
+
     #[test]
     fn packing_nat_max() {
         let large = Value::nat(u48::MAX);
@@ -11,6 +11,7 @@
 use common::{i48, u48};
 
 use crate::{
+    classes::Foreign,
     primitives::PrimitiveOperations,
     value::{Tag, Value},
     Error, VirtualMachine,
@@ -32,10 +33,13 @@ macro_rules! dispatch {
             Tag::Float => $f( &$value.as_float().unwrap(), $( $arg, )* ),
             Tag::Int => $f( &$value.as_int().unwrap(), $( $arg, )* ),
             Tag::Nat => $f( &$value.as_nat().unwrap(), $( $arg, )* ),
+            Tag::String => {
+                $f( &$value.as_inline_string().unwrap(), $( $arg, )* )
+            },
             Tag::Object => {
                 $f( $value.as_gc_any().unwrap().deref(), $( $arg, )* )
             },
-            Tag::_Reserved0 | Tag::_Reserved1 => {
+            Tag::_Reserved1 => {
                 unreachable!("cannot use value with reserved tag")
             }
         }
@@ -172,6 +176,17 @@ impl PrimitiveOperations for f64 {
     }
 }
 
+/// Strings short enough to be stored inline in a [`Value`].
+impl PrimitiveOperations for &str {
+    fn type_name(&self) -> &'static str {
+        "String"
+    }
+
+    fn cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        PartialOrd::partial_cmp(self, other)
+    }
+}
+
 impl PrimitiveOperations for u48 {
     fn type_name(&self) -> &'static str {
         "Nat"
@@ -293,8 +308,9 @@ impl PrimitiveOperations for Value {
             Tag::Float => 1f64.type_name(),
             Tag::Int => i48::MAX.type_name(),
             Tag::Nat => u48::MAX.type_name(),
+            Tag::String => "".type_name(),
             Tag::Object => self.as_gc_any().unwrap().deref().type_name(),
-            Tag::_Reserved0 | Tag::_Reserved1 => "<invalid value>",
+            Tag::_Reserved1 => "<invalid value>",
         }
     }
 
@@ -422,7 +438,28 @@ impl PrimitiveOperations for Value {
         dispatch!(PrimitiveOperations::is_truthy, self,)
     }
 
+    fn eq(&self, other: &Self) -> Option<bool> {
+        // Host values can be equal without being ordered.
+        if let (Some(a), Some(b)) =
+            (self.as_gc::<Foreign>(), other.as_gc::<Foreign>())
+        {
+            return Some(*a == *b);
+        }
+
+        PrimitiveOperations::cmp(self, other).map(std::cmp::Ordering::is_eq)
+    }
+
+    fn ne(&self, other: &Self) -> Option<bool> {
+        PrimitiveOperations::eq(self, other).map(|eq| !eq)
+    }
+
     fn cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        // A short string can be compared to a longer one on the heap.
+        if self.is_inline_string() || other.is_inline_string() {
+            let (a, b) = (self.as_str()?, other.as_str()?);
+            return a.partial_cmp(b);
+        }
+
         let tag = self.tag();
         if other.tag() != tag {
             return None;
@@ -0,0 +1,68 @@
+//! Calling Kurt closures from Rust.
+//!
+//! This is how a host runs callbacks. A script hands the host a closure,
+//! usually as the result of loading a module, and the host calls it whenever
+//! it likes with [`VirtualMachine::call`].
+
+use common::Index;
+
+use crate::{vm::CallStack, Error, Result, Value, VirtualMachine};
+
+impl VirtualMachine {
+    /// Call the closure `f` with `args`, and return its result.
+    ///
+    /// The closure runs on top of whatever the VM was doing, and once it
+    /// returns everything is back the way it was. This is true even if it
+    /// fails, so the VM can be used again after an error. That means a call
+    /// which runs out of fuel or is interrupted can't be
+    /// [resumed][VirtualMachine::resume], it has to be called again.
+    ///
+    /// The result isn't kept alive by anything, so it has to be put somewhere
+    /// the collector can find it, like a [`Local`] handle, before anything
+    /// else is allocated.
+    ///
+    /// Calls from inside native code called by Kurt can only be nested
+    /// [`CallStack::MAX_NATIVE_DEPTH`] deep, after which this fails with
+    /// [`Error::StackOverflow`].
+    ///
+    /// [`Local`]: crate::memory::Local
+    pub fn call(&mut self, f: Value, args: &[Value]) -> Result<Value> {
+        if self.native_depth >= CallStack::MAX_NATIVE_DEPTH {
+            return Err(Error::StackOverflow);
+        }
+
+        self.native_depth += 1;
+
+        let height = self.stack.len();
+        let depth = self.call_stack.len();
+        let return_depth = self.return_depth.replace(depth);
+        let suspended = self.suspended;
+
+        for value in std::iter::once(&f).chain(args) {
+            self.stack.push(*value);
+        }
+
+        let result = self.push_call(args.len() as u32).and_then(|()| {
+            // Foreign objects are called right away, without a frame.
+            if self.call_stack.len() > depth {
+                self.run()?;
+            }
+
+            Ok(self.stack.as_slice()[height])
+        });
+
+        // Put things back, even if the call failed part way through.
+        while self.call_stack.len() > depth {
+            self.call_stack.pop();
+        }
+
+        self.close_captures_above(Index::new(height as u32));
+        self.stack.truncate(height);
+
+        self.return_depth = return_depth;
+        self.suspended = suspended;
+        self.native_depth -= 1;
+
+        result
+    }
+}
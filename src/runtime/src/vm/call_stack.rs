@@ -59,6 +59,22 @@ pub struct CallStack {
 }
 
 impl CallStack {
+    /// The most frames the call stack can hold. Calling a function when it's
+    /// full is an [`Error::StackOverflow`][crate::Error::StackOverflow].
+    ///
+    /// Frames are small, but this keeps runaway recursion from using up all
+    /// the host's memory.
+    pub const MAX_DEPTH: usize = 1 << 16;
+
+    /// The most calls from the host which can be running inside each other,
+    /// like a Rust function called from Kurt which calls back into Kurt.
+    /// Going past this is an [`Error::StackOverflow`][crate::Error::StackOverflow]
+    /// too.
+    ///
+    /// Each of these runs the VM again on the host's own stack, which is much
+    /// smaller than [`CallStack::MAX_DEPTH`] frames would need.
+    pub const MAX_NATIVE_DEPTH: usize = 64;
+
     /// The number of frames on the call stack.
     pub fn len(&self) -> usize {
         if self.current.is_none() {
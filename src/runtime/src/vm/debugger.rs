@@ -0,0 +1,409 @@
+//! Pausing a running program to look around.
+//!
+//! A [`Debugger`] is a hook that the [`VirtualMachine`] calls whenever
+//! execution pauses, either because a breakpoint was reached or a step
+//! finished. While paused, the hook has the whole VM available, and can look
+//! at the call stack, the locals in each frame, and evaluate expressions as if
+//! they were written in a frame.
+//!
+//! Pauses only ever happen at the start of a line, and lines where only
+//! bookkeeping happens (like the `;` closing a multi-line `let`) are skipped.
+//! Lines are numbered starting at 1, the same way they're shown in diagnostics.
+
+use std::{collections::BTreeSet, fmt};
+
+use common::Index;
+use compiler::{ModuleBuilder, Op};
+use diagnostic::{Diagnostic, Span};
+
+use crate::{
+    classes::Function,
+    memory::Gc,
+    vm::{CallFrame, Stack, VirtualMachine},
+    Error, Value,
+};
+
+/// Why execution paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pause {
+    /// A breakpoint on this line was reached.
+    Breakpoint(u32),
+
+    /// A step finished.
+    Step,
+}
+
+/// How to carry on after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Run until a breakpoint is reached.
+    Continue,
+
+    /// Pause at the next line, even if it's in a function being called.
+    StepInto,
+
+    /// Pause at the next line in this frame, running any calls to completion.
+    StepOver,
+
+    /// Pause at the next line after the current frame returns.
+    StepOut,
+}
+
+/// A hook which is called whenever execution pauses.
+pub trait Debugger {
+    /// Execution has paused, for the given reason.
+    ///
+    /// The VM is stopped just before running the first op of a line. The
+    /// returned [`Resume`] says what to do next.
+    fn paused(&mut self, vm: &mut VirtualMachine, pause: Pause) -> Resume;
+}
+
+/// A problem with [`VirtualMachine::evaluate`].
+#[derive(Debug)]
+pub enum EvaluateError {
+    /// There's no frame with that index.
+    NoSuchFrame,
+
+    /// The expression didn't compile.
+    Compile(Diagnostic),
+
+    /// The expression compiled, but had an error while running.
+    Runtime(Error),
+}
+
+impl fmt::Display for EvaluateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvaluateError::NoSuchFrame => write!(f, "no such frame"),
+            EvaluateError::Compile(d) => write!(f, "{}", d.get_text()),
+            EvaluateError::Runtime(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for EvaluateError {}
+
+/// The debugger's state, kept by the VM.
+#[derive(Default)]
+pub(crate) struct Debugging {
+    hook: Option<Box<dyn Debugger>>,
+    breakpoints: BTreeSet<u32>,
+    resume: Option<Resume>,
+
+    /// The call stack depth and line of the last op which ran.
+    last: Option<(usize, u32)>,
+
+    /// Where execution was when it last resumed.
+    origin: Option<(usize, u32)>,
+}
+
+impl Debugging {
+    /// Is there a debugger attached?
+    #[inline]
+    pub(crate) fn is_attached(&self) -> bool {
+        self.hook.is_some()
+    }
+
+    /// Decide if we should pause at a new `location`.
+    fn should_pause(
+        &mut self,
+        location: Option<(usize, u32)>,
+    ) -> Option<Pause> {
+        let (depth, line) = location?;
+
+        // Only the start of a line is interesting.
+        if self.last.replace((depth, line)) == Some((depth, line)) {
+            return None;
+        }
+
+        if self.breakpoints.contains(&line) {
+            return Some(Pause::Breakpoint(line));
+        }
+
+        let (origin_depth, origin_line) = match self.origin {
+            Some(origin) => origin,
+            None => return Some(Pause::Step).filter(|_| self.is_stepping()),
+        };
+
+        let pause = match self.resume? {
+            Resume::Continue => false,
+            Resume::StepInto => (depth, line) != (origin_depth, origin_line),
+            Resume::StepOver => {
+                depth < origin_depth
+                    || (depth == origin_depth && line != origin_line)
+            }
+            Resume::StepOut => depth < origin_depth,
+        };
+
+        Some(Pause::Step).filter(|_| pause)
+    }
+
+    fn is_stepping(&self) -> bool {
+        !matches!(self.resume, None | Some(Resume::Continue))
+    }
+}
+
+impl VirtualMachine {
+    /// Attach a debugger, starting execution as if it had just returned
+    /// `resume`. Use [`Resume::StepInto`] to pause on the very first line.
+    ///
+    /// This replaces any existing debugger, but keeps the breakpoints.
+    pub fn set_debugger(
+        &mut self,
+        debugger: impl Debugger + 'static,
+        resume: Resume,
+    ) {
+        self.debugging.hook = Some(Box::new(debugger));
+        self.debugging.resume = Some(resume);
+        self.debugging.origin = None;
+        self.debugging.last = None;
+    }
+
+    /// Detach the current debugger, if there is one.
+    ///
+    /// This can't be used while paused, since the debugger is what's running.
+    pub fn remove_debugger(&mut self) -> Option<Box<dyn Debugger>> {
+        self.debugging.hook.take()
+    }
+
+    /// Pause whenever execution reaches `line`.
+    pub fn set_breakpoint(&mut self, line: u32) {
+        self.debugging.breakpoints.insert(line);
+    }
+
+    /// Remove a breakpoint, returning whether there was one on `line`.
+    pub fn clear_breakpoint(&mut self, line: u32) -> bool {
+        self.debugging.breakpoints.remove(&line)
+    }
+
+    /// The lines with breakpoints, in order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+        self.debugging.breakpoints.iter().cloned()
+    }
+
+    /// Check if we need to pause before the next op, and if so call the
+    /// debugger.
+    ///
+    /// This is called by the dispatch loop before each op is fetched, when a
+    /// debugger is attached.
+    pub(crate) fn debug_hook(&mut self) {
+        // Ops which only tidy up after a statement, and those which span
+        // multiple lines don't say much about where we are, so we only look at
+        // the others.
+        let location = self
+            .frame_span(0)
+            .filter(|span| span.start().line() == span.end().line())
+            .filter(|_| !is_bookkeeping(self.op()))
+            .map(|span| (self.call_stack.len(), span.start().line() + 1));
+
+        let pause = match self.debugging.should_pause(location) {
+            Some(pause) => pause,
+            None => return,
+        };
+
+        let mut hook = self
+            .debugging
+            .hook
+            .take()
+            .expect("debug_hook is only called with a debugger attached");
+
+        let resume = hook.paused(self, pause);
+
+        self.debugging.hook = Some(hook);
+        self.debugging.resume = Some(resume);
+        self.debugging.origin = location;
+    }
+}
+
+/// Is this op just tidying up the stack or control flow around a statement?
+fn is_bookkeeping(op: Op) -> bool {
+    matches!(
+        op,
+        Op::Nop
+            | Op::Pop
+            | Op::Close(_)
+            | Op::DefineLocal
+            | Op::Return
+            | Op::Halt
+    )
+}
+
+// Inspection
+impl VirtualMachine {
+    /// The number of call frames.
+    pub fn frame_count(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// The closure running in a frame, where frame `0` is the one currently
+    /// running, and higher frames are its callers.
+    pub fn frame_closure(&self, frame: usize) -> Option<Gc<Function>> {
+        let frame = self.call_stack.get(frame)?;
+        self.stack[frame.bp()].as_gc()
+    }
+
+    /// The span of the code being run in a frame.
+    ///
+    /// For the current frame, this is the op about to run. For the others it's
+    /// the call which is waiting to return.
+    pub fn frame_span(&self, frame: usize) -> Option<Span> {
+        let pc = self.call_stack.get(frame)?.pc();
+        let pc = if frame == 0 {
+            pc
+        } else {
+            pc.saturating_previous()
+        };
+
+        self.frame_closure(frame)?
+            .prototype()
+            .debug_info()?
+            .span_of(pc)
+    }
+
+    /// The names and values of the locals in scope in a frame, from most to
+    /// least recently bound. Shadowed locals are left out.
+    ///
+    /// The values are only safe to use until the VM next runs code.
+    pub fn frame_locals(&self, frame: usize) -> Vec<(String, Value)> {
+        let (closure, call_frame) =
+            match (self.frame_closure(frame), self.call_stack.get(frame)) {
+                (Some(closure), Some(call_frame)) => (closure, call_frame),
+                _ => return Vec::new(),
+            };
+
+        let prototype = closure.prototype();
+
+        let debug = match prototype.debug_info() {
+            Some(debug) => debug,
+            None => return Vec::new(),
+        };
+
+        // Callers are paused on the op after their call.
+        let pc = if frame == 0 {
+            call_frame.pc()
+        } else {
+            call_frame.pc().saturating_previous()
+        };
+
+        let mut locals: Vec<(String, Value)> = Vec::new();
+
+        for local in debug.locals_at(pc) {
+            if locals.iter().any(|(name, _)| name == local.name()) {
+                continue;
+            }
+
+            let index = Stack::from_local(call_frame.bp(), local.index());
+
+            if let Some(value) = self.stack.as_slice().get(index.as_usize()) {
+                locals.push((local.name().to_owned(), *value));
+            }
+        }
+
+        locals
+    }
+
+    /// The value of a local by name in a frame, if it's in scope.
+    pub fn frame_local(&self, frame: usize, name: &str) -> Option<Value> {
+        self.frame_locals(frame)
+            .into_iter()
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Evaluate some input as if it were written in a frame, so it can use
+    /// that frame's locals.
+    ///
+    /// The locals are copies, so assigning to them has no effect on the frame.
+    /// The result is only safe to use until the VM next runs code.
+    pub fn evaluate(
+        &mut self,
+        frame: usize,
+        input: &str,
+    ) -> Result<Value, EvaluateError> {
+        if frame >= self.call_stack.len() {
+            return Err(EvaluateError::NoSuchFrame);
+        }
+
+        let locals = self.frame_locals(frame);
+
+        // The locals are declared up front, and we skip over that code after
+        // putting the actual values where it would have put them.
+        let mut builder = ModuleBuilder::default();
+
+        let prelude: std::string::String = locals
+            .iter()
+            .map(|(name, _)| format!("let {name} = ();"))
+            .collect();
+
+        builder
+            .push_input(&prelude)
+            .map_err(EvaluateError::Compile)?;
+
+        let start = builder.entry_point();
+
+        builder.push_input(input).map_err(EvaluateError::Compile)?;
+
+        let depth = self.call_stack.len();
+        let height = self.stack.len();
+        let loaded = self.modules.len();
+
+        // The evaluated code shouldn't pause.
+        let hook = self.debugging.hook.take();
+
+        let result = self.evaluate_module(
+            builder.build(),
+            locals.iter().map(|(_, value)| *value),
+            start,
+        );
+
+        // Clean up whatever was left, even after an error.
+        while self.call_stack.len() > depth {
+            self.call_stack.pop();
+        }
+
+        self.close_captures_above(Index::new(height as u32));
+        self.stack.truncate(height);
+
+        // The evaluated code isn't a module of the program, so it shouldn't
+        // stay loaded once it's done.
+        self.modules.truncate(loaded);
+
+        if self.debugging.hook.is_none() {
+            self.debugging.hook = hook;
+        }
+
+        result.map_err(EvaluateError::Runtime)
+    }
+
+    fn evaluate_module(
+        &mut self,
+        module: compiler::Module,
+        values: impl Iterator<Item = Value>,
+        start: Index<Op>,
+    ) -> crate::Result<Value> {
+        self.load_without_running(module)?;
+
+        let new_module = *self
+            .modules
+            .last()
+            .expect("load_without_running left a module for us");
+
+        let main_closure: Gc<Function> = self.make_from(new_module.main())?;
+
+        self.stack.push(Value::from(main_closure));
+        let bp = self.stack.from_top(Index::START);
+
+        for value in values {
+            self.stack.push(value);
+        }
+
+        // This is the `()` the prelude leaves after its last `;`.
+        self.stack.push(Value::UNIT);
+
+        self.call_stack.push(CallFrame::new(start, bp));
+
+        self.run()?;
+
+        Ok(*self.stack.last().expect("evaluation should leave a result"))
+    }
+}
@@ -0,0 +1,142 @@
+//! Growing the running top-level code, one piece at a time.
+//!
+//! This is what the REPL uses to keep a single [`VirtualMachine`] alive across
+//! inputs. Each new input is compiled onto the end of the same
+//! [`ModuleBuilder`][compiler::ModuleBuilder], and the resulting module picks
+//! up from wherever the last one halted, so earlier bindings are still on the
+//! stack where the new code expects them.
+
+use common::Index;
+use compiler::Op;
+
+use crate::{
+    classes::Function, memory::Gc, vm::VirtualMachine, Error, Result, Value,
+};
+
+/// Where things stood before the last call to [`VirtualMachine::extend`], so
+/// that they can be put back if it fails.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint {
+    /// The stack's length before running.
+    pub(crate) height: usize,
+
+    /// The halted top-level closure and its program counter, if anything had
+    /// been run yet.
+    pub(crate) main: Option<(Value, Index<Op>)>,
+}
+
+impl Checkpoint {
+    /// The value held onto by the checkpoint, so the collector can find it.
+    pub(crate) fn value(&self) -> Option<Value> {
+        self.main.map(|(closure, _)| closure)
+    }
+}
+
+impl VirtualMachine {
+    /// Load a module which extends the top-level code that last halted, and
+    /// run only the new part.
+    ///
+    /// The `module` should come from the same [`ModuleBuilder`] as the running
+    /// code, with more input pushed onto it. If nothing has been run yet, this
+    /// is the same as [`VirtualMachine::load`].
+    ///
+    /// If this returns an error, the VM is left where the error happened so a
+    /// [stack trace][VirtualMachine::stack_trace] can be made. Call
+    /// [`VirtualMachine::rewind`] afterwards to go back to where things were
+    /// before this was called.
+    ///
+    /// [`ModuleBuilder`]: compiler::ModuleBuilder
+    pub fn extend(&mut self, module: compiler::Module) -> Result<()> {
+        if self.call_stack.len() == 0 {
+            self.checkpoint = Some(Checkpoint {
+                height: self.stack.len(),
+                main: None,
+            });
+
+            return self.load(module).map(|()| self.checkpoint = None);
+        }
+
+        let resume = self.resume_point(&module)?;
+
+        let bp = self.bp();
+
+        self.checkpoint = Some(Checkpoint {
+            height: self.stack.len(),
+            main: Some((self.stack[bp], self.pc())),
+        });
+
+        self.load_without_running(module)?;
+
+        let new_module = *self
+            .modules
+            .last()
+            .expect("load_without_running left a module for us");
+
+        let main_closure: Gc<Function> = self.make_from(new_module.main())?;
+
+        self.stack[bp] = Value::from(main_closure);
+        *self.pc_mut() = resume;
+
+        self.run().map(|()| self.checkpoint = None)
+    }
+
+    /// Undo a failed call to [`VirtualMachine::extend`], unwinding any calls
+    /// and throwing away anything it left on the stack.
+    ///
+    /// Does nothing if the last call to `extend` succeeded.
+    pub fn rewind(&mut self) {
+        self.suspended = false;
+
+        let checkpoint = match self.checkpoint.take() {
+            Some(checkpoint) => checkpoint,
+            None => return,
+        };
+
+        let frames = if checkpoint.main.is_some() { 1 } else { 0 };
+
+        while self.call_stack.len() > frames {
+            self.call_stack.pop();
+        }
+
+        // Anything captured from the part of the stack we're dropping needs to
+        // be moved into its cell first.
+        self.close_captures_above(Index::new(checkpoint.height as u32));
+        self.stack.truncate(checkpoint.height);
+
+        if let Some((closure, pc)) = checkpoint.main {
+            let bp = self.bp();
+            self.stack[bp] = closure;
+            *self.pc_mut() = pc;
+        }
+    }
+
+    /// Check that `module` really does extend the halted top-level code, and
+    /// find where its new code starts.
+    ///
+    /// Top-level code always ends with a `Nop` and `Halt`, and when halted the
+    /// program counter is just past them. The new code starts where the `Nop`
+    /// was, and everything before it must be unchanged.
+    fn resume_point(&self, module: &compiler::Module) -> Result<Index<Op>> {
+        if self.call_stack.len() != 1 {
+            return Err(Error::InvalidExtension);
+        }
+
+        let old = self.current_closure().prototype();
+        let old = old.code();
+        let new = module
+            .functions()
+            .get(compiler::Module::MAIN.as_usize())
+            .ok_or(Error::NoMainFunction)?
+            .code();
+
+        let resume = self
+            .pc()
+            .as_usize()
+            .checked_sub(2)
+            .filter(|&i| old.get(i..) == Some(&[Op::Nop, Op::Halt]))
+            .filter(|&i| new.len() > old.len() && new[..i] == old[..i])
+            .ok_or(Error::InvalidExtension)?;
+
+        Ok(Index::new(resume as u32))
+    }
+}
@@ -0,0 +1,973 @@
+//! Saving a whole [`VirtualMachine`] to an image, and restoring it later.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
+use common::{i48, u48, Get, Index};
+use compiler::{Constant, Op};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    classes::{
+        self, CaptureCell, CaptureCellContents, Function, Keyword, List,
+        Module, Prototype, Tuple, Weak,
+    },
+    memory::{ClassId, Gc, GcAny, Object, WorkList},
+    vm::{extend::Checkpoint, CallFrame, Stack},
+    Error, Result, Value, VirtualMachine,
+};
+
+/// A saved [`VirtualMachine`], which can be
+/// [restored][VirtualMachine::restore] later.
+///
+/// It holds everything the VM needs to pick up where it left off:
+/// the code of every loaded module, every object reachable from the VM, the
+/// stack and the call frames. Restoring one into a new VM skips all the work
+/// it took to get there, like running a big script's top-level code.
+///
+/// What isn't saved:
+///
+/// - [`Foreign`][crate::classes::Foreign] objects, since there's no way to
+///   save what the host put in them. A VM which can reach any can't be saved.
+///
+/// - [Handles][crate::HandleScope], which belong to the host. Anything
+///   only kept alive by a handle is left out.
+///
+/// - Settings, like the [`GcConfig`][crate::GcConfig] and
+///   [`Limits`][crate::Limits], along with any debugger or profiler. These
+///   belong to the VM being restored into.
+///
+/// - The [inputs][diagnostic::InputId] modules were compiled from, so spans
+///   can't be traced back to their source after restoring.
+///
+/// # Format
+///
+/// Images are written as JSON like this:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "code": ["module {\n ... \n}"],
+///   "objects": [
+///     { "class": "Module", "code": 0, "constants": [{ "object": 1 }, null] },
+///     { "class": "String", "text": "main" },
+///     { "class": "Prototype", "module": 0, "function": 0 },
+///     { "class": "Closure", "prototype": 2, "captures": [4] },
+///     { "class": "CaptureCell", "contents": { "open": 1 } },
+///     { "class": "Keyword", "name": "ok" },
+///     { "class": "Tuple", "tag": 5, "elements": [{ "int": -1 }, "unit"] },
+///     { "class": "List", "elements": [{ "float": 4614253070214989087 }] },
+///     { "class": "Weak", "target": { "object": 6 } }
+///   ],
+///   "modules": [0],
+///   "stack": [{ "object": 3 }, { "string": "hi" }, { "object": 7 }],
+///   "frames": [{ "bp": 0, "pc": 12 }],
+///   "open_captures": [4],
+///   "checkpoint": { "height": 1, "main": [{ "object": 3 }, 10] },
+///   "suspended": false
+/// }
+/// ```
+///
+/// - `version` is [`Image::VERSION`], which changes whenever the format does.
+///
+/// - `code` has the listing of each compiled module, as read by
+///   [`compiler::Module::assemble`]. Modules loaded from the same code share
+///   it after restoring, like they did before.
+///
+/// - Each object has a `class`, and fields depending on what it is. Objects
+///   are referred to by their position in `objects`. A [`Prototype`] is
+///   the `function`th function of its module, and a module's `constants` are
+///   `null` until they've been inflated.
+///
+/// - Values are `"unit"`, or one of `bool`, `char`, `nat`, `int`, `float` (as
+///   the bits of the number), `string` (for strings short enough to be stored
+///   inline) or `object`.
+///
+/// - `modules` are the loaded modules, in the order they were loaded.
+///
+/// - `frames` are the call frames, oldest first. Each frame's `bp` is the stack
+///   slot holding its closure, and the values above it up to the next frame's
+///   `bp` (or the top of the stack) have to be what its code expects at `pc`.
+///   The exception is the top frame of a VM stopped by an error, which can't
+///   be resumed.
+///
+/// - `open_captures` are the capture cells which still point into the stack,
+///   in the order they were opened.
+///
+/// - `checkpoint` is where to go back to if [`VirtualMachine::extend`] failed,
+///   and `suspended` is whether there's something to
+///   [resume][VirtualMachine::resume].
+///
+/// Images are checked when they're read and again when they're restored, so
+/// a bad image is an error rather than a broken VM.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Image {
+    version: u32,
+    code: Vec<String>,
+    objects: Vec<ImageObject>,
+    modules: Vec<usize>,
+    stack: Vec<ImageValue>,
+    frames: Vec<ImageFrame>,
+    open_captures: Vec<usize>,
+    checkpoint: Option<ImageCheckpoint>,
+    suspended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ImageValue {
+    Unit,
+    Bool(bool),
+    Char(char),
+    Nat(u64),
+    Int(i64),
+    Float(u64),
+    String(String),
+    Object(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "class")]
+enum ImageObject {
+    CaptureCell {
+        contents: ImageCell,
+    },
+    Closure {
+        prototype: usize,
+        captures: Vec<usize>,
+    },
+    Keyword {
+        name: String,
+    },
+    List {
+        elements: Vec<ImageValue>,
+    },
+    Module {
+        code: usize,
+        constants: Vec<Option<ImageValue>>,
+    },
+    Prototype {
+        module: usize,
+        function: u32,
+    },
+    String {
+        text: String,
+    },
+    Tuple {
+        tag: Option<usize>,
+        elements: Vec<ImageValue>,
+    },
+    Weak {
+        target: Option<ImageValue>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ImageCell {
+    Open(usize),
+    Closed(ImageValue),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ImageFrame {
+    bp: usize,
+    pc: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ImageCheckpoint {
+    height: usize,
+    main: Option<(ImageValue, u32)>,
+}
+
+impl ImageObject {
+    fn class_id(&self) -> ClassId {
+        match self {
+            ImageObject::CaptureCell { .. } => ClassId::CaptureCell,
+            ImageObject::Closure { .. } => ClassId::Closure,
+            ImageObject::Keyword { .. } => ClassId::Keyword,
+            ImageObject::List { .. } => ClassId::List,
+            ImageObject::Module { .. } => ClassId::Module,
+            ImageObject::Prototype { .. } => ClassId::Prototype,
+            ImageObject::String { .. } => ClassId::String,
+            ImageObject::Tuple { .. } => ClassId::Tuple,
+            ImageObject::Weak { .. } => ClassId::Weak,
+        }
+    }
+}
+
+impl Image {
+    /// The version of the format images are written in.
+    pub const VERSION: u32 = 1;
+
+    /// Write the image as JSON.
+    pub fn write_json(&self, writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Read an image written by [`Image::write_json`].
+    ///
+    /// This fails if the JSON isn't an image, if it's from a different
+    /// version, or if what's in it doesn't fit together.
+    pub fn read_json(reader: impl Read) -> io::Result<Image> {
+        let image: Image = serde_json::from_reader(reader)?;
+
+        image
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(image)
+    }
+
+    /// Check everything which doesn't need the code assembled.
+    fn validate(&self) -> std::result::Result<(), &'static str> {
+        if self.version != Image::VERSION {
+            return Err("unsupported image version");
+        }
+
+        let class_of = |id: usize| self.objects.get(id).map(|o| o.class_id());
+
+        let expect = |id: usize, class: ClassId| {
+            if class_of(id) == Some(class) {
+                Ok(())
+            } else {
+                Err("object isn't what it should be")
+            }
+        };
+
+        let value = |value: &ImageValue| match value {
+            ImageValue::Nat(n) if u48::from_u64(*n).is_none() => {
+                Err("number too big")
+            }
+            ImageValue::Int(i) if i48::from_i64(*i).is_none() => {
+                Err("number too big")
+            }
+            ImageValue::String(s) if Value::inline_string(s).is_none() => {
+                Err("inline string too long")
+            }
+            ImageValue::Object(id) if *id >= self.objects.len() => {
+                Err("reference to an unknown object")
+            }
+            _ => Ok(()),
+        };
+
+        let closure = |v: &ImageValue| match v {
+            ImageValue::Object(id) => expect(*id, ClassId::Closure),
+            _ => Err("call frame without a closure"),
+        };
+
+        for object in &self.objects {
+            match object {
+                ImageObject::CaptureCell { contents } => match contents {
+                    ImageCell::Open(index) if *index >= self.stack.len() => {
+                        return Err("capture cell past the top of the stack")
+                    }
+                    ImageCell::Open(_) => {}
+                    ImageCell::Closed(v) => value(v)?,
+                },
+
+                ImageObject::Closure {
+                    prototype,
+                    captures,
+                } => {
+                    expect(*prototype, ClassId::Prototype)?;
+                    for cell in captures {
+                        expect(*cell, ClassId::CaptureCell)?;
+                    }
+                }
+
+                ImageObject::Keyword { .. } | ImageObject::String { .. } => {}
+
+                ImageObject::List { elements } => {
+                    elements.iter().try_for_each(value)?
+                }
+
+                ImageObject::Module { code, constants } => {
+                    if *code >= self.code.len() {
+                        return Err("module with unknown code");
+                    }
+                    constants.iter().flatten().try_for_each(value)?;
+                }
+
+                ImageObject::Prototype { module, .. } => {
+                    expect(*module, ClassId::Module)?
+                }
+
+                ImageObject::Tuple { tag, elements } => {
+                    if let Some(tag) = tag {
+                        expect(*tag, ClassId::Keyword)?;
+                    }
+                    elements.iter().try_for_each(value)?;
+                }
+
+                ImageObject::Weak { target } => {
+                    target.iter().try_for_each(value)?
+                }
+            }
+        }
+
+        for module in &self.modules {
+            expect(*module, ClassId::Module)?;
+        }
+
+        self.stack.iter().try_for_each(value)?;
+
+        let mut bottom = None;
+        for frame in &self.frames {
+            if bottom.is_some_and(|bottom| frame.bp <= bottom) {
+                return Err("call frames out of order");
+            }
+            bottom = Some(frame.bp);
+
+            closure(self.stack.get(frame.bp).ok_or("frame past the stack")?)?;
+        }
+
+        for cell in &self.open_captures {
+            match self.objects.get(*cell) {
+                Some(ImageObject::CaptureCell {
+                    contents: ImageCell::Open(_),
+                }) => {}
+                _ => return Err("open capture isn't an open capture cell"),
+            }
+        }
+
+        for (id, object) in self.objects.iter().enumerate() {
+            if let ImageObject::CaptureCell {
+                contents: ImageCell::Open(_),
+            } = object
+            {
+                if !self.open_captures.contains(&id) {
+                    return Err("open capture cell missing from open captures");
+                }
+            }
+        }
+
+        if let Some(checkpoint) = &self.checkpoint {
+            if checkpoint.height > self.stack.len() {
+                return Err("checkpoint past the top of the stack");
+            }
+
+            if let Some((main, _)) = &checkpoint.main {
+                closure(main)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check everything which needs the code, which is `code` assembled.
+    fn validate_code(
+        &self,
+        code: &[Arc<compiler::Module>],
+    ) -> std::result::Result<(), &'static str> {
+        const NO_FUNCTION: &str =
+            "prototype for a function which doesn't exist";
+
+        // The code each prototype's function is in, and which function it is
+        // there. Prototypes have already been checked to be for a module.
+        let location = |prototype: usize| {
+            let ImageObject::Prototype { module, function } =
+                &self.objects[prototype]
+            else {
+                unreachable!("prototypes have been checked");
+            };
+
+            let ImageObject::Module { code: i, .. } = &self.objects[*module]
+            else {
+                unreachable!("modules have been checked");
+            };
+
+            (&code[*i], Index::<compiler::Function>::new(*function))
+        };
+
+        let function = |prototype: usize| {
+            let (module, function) = location(prototype);
+            module.functions().get(function.as_usize())
+        };
+
+        let closure_prototype = |value: &ImageValue| {
+            let ImageValue::Object(id) = value else {
+                unreachable!("closures have been checked");
+            };
+
+            let ImageObject::Closure { prototype, .. } = &self.objects[*id]
+            else {
+                unreachable!("closures have been checked");
+            };
+
+            *prototype
+        };
+
+        let closure_function =
+            |value: &ImageValue| function(closure_prototype(value));
+
+        // How many values a frame's code expects above its closure, when it's
+        // stopped at `pc`. Callers are stopped just after their call, which
+        // has the callee and its arguments on the stack instead.
+        let expected_height = |closure: &ImageValue, pc: u32, is_top: bool| {
+            let (module, index) = location(closure_prototype(closure));
+            let ops = module[index].code();
+            let depths = module
+                .stack_depths(index)
+                .expect("the function has been checked")
+                .map_err(|_| "code doesn't verify")?;
+
+            let pc = pc as usize;
+            let depth = match ops.get(pc.wrapping_sub(1)) {
+                // Top-level code which has finished stays past its `Halt`.
+                Some(Op::Halt) if pc == ops.len() => depths[pc - 1],
+                _ if pc == ops.len() => {
+                    return Err("program counter out of range")
+                }
+
+                Some(Op::Call(n)) if !is_top => {
+                    depths[pc - 1].map(|depth| depth - (*n as usize + 1))
+                }
+                _ if !is_top => return Err("caller isn't stopped at a call"),
+
+                _ => depths[pc],
+            };
+
+            depth.ok_or("program counter at unreachable code")
+        };
+
+        let pc_in_range = |closure: &ImageValue, pc: u32| {
+            let function = closure_function(closure).ok_or(NO_FUNCTION)?;
+
+            if pc as usize <= function.code().len() {
+                Ok(())
+            } else {
+                Err("program counter out of range")
+            }
+        };
+
+        for (id, object) in self.objects.iter().enumerate() {
+            match object {
+                ImageObject::Module { code: i, constants }
+                    if constants.len() != code[*i].constants().len() =>
+                {
+                    return Err("module constants don't match its code");
+                }
+
+                ImageObject::Prototype { .. } if function(id).is_none() => {
+                    return Err(NO_FUNCTION);
+                }
+
+                ImageObject::Closure {
+                    prototype,
+                    captures,
+                } => {
+                    let function = function(*prototype).ok_or(NO_FUNCTION)?;
+
+                    if captures.len() != function.capture_count() as usize {
+                        return Err(
+                            "closure with the wrong number of captures",
+                        );
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            let closure = &self.stack[frame.bp];
+            pc_in_range(closure, frame.pc)?;
+
+            let next = self.frames.get(i + 1);
+            let top = next.map_or(self.stack.len(), |next| next.bp);
+            let height = top - frame.bp - 1;
+
+            // A VM stopped by an error leaves its top frame wherever the error
+            // happened, with whatever the failed op was using still on the
+            // stack. It can't be resumed, so there's nothing to check.
+            let halted = frame.pc as usize
+                == closure_function(closure).map_or(0, |f| f.code().len());
+            if next.is_none() && !self.suspended && !halted {
+                continue;
+            }
+
+            if expected_height(closure, frame.pc, next.is_none())? != height {
+                return Err("stack doesn't match the frame's code");
+            }
+        }
+
+        if let Some(checkpoint) = &self.checkpoint {
+            if let Some((main, pc)) = &checkpoint.main {
+                pc_in_range(main, *pc)?;
+
+                // Rewinding goes back to the first frame, with the stack cut
+                // down to the checkpoint's height.
+                let bp =
+                    self.frames.first().ok_or("checkpoint without a frame")?.bp;
+                let height = checkpoint
+                    .height
+                    .checked_sub(bp + 1)
+                    .ok_or("checkpoint below its frame")?;
+
+                if expected_height(main, *pc, true)? != height {
+                    return Err("stack doesn't match the checkpoint's code");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The objects going into an image, and their ids.
+#[derive(Default)]
+struct Saver {
+    ids: HashMap<*const Object, usize>,
+    objects: Vec<GcAny>,
+}
+
+impl Saver {
+    /// Give `ptr` and everything reachable from it ids.
+    fn discover(&mut self, ptr: GcAny) {
+        let mut worklist = WorkList::everything();
+        worklist.enqueue(ptr);
+
+        while let Some(ptr) = worklist.pop() {
+            let key = ptr.deref() as *const Object;
+            if self.ids.contains_key(&key) {
+                continue;
+            }
+
+            self.ids.insert(key, self.objects.len());
+            self.objects.push(ptr);
+            ptr.deref().enqueue_gc_references(&mut worklist);
+        }
+    }
+
+    fn id(&self, ptr: impl Into<GcAny>) -> Option<usize> {
+        self.ids.get(&(ptr.into().deref() as *const _)).copied()
+    }
+
+    fn value(&self, value: Value) -> ImageValue {
+        self.weak_value(value)
+            .expect("everything reachable has been discovered")
+    }
+
+    /// Like [`Saver::value`], but objects which weren't reachable are `None`.
+    fn weak_value(&self, value: Value) -> Option<ImageValue> {
+        // Floats have to be ruled out first, since their bits can look like
+        // anything else.
+        let value = if let Some(f) = value.as_float() {
+            ImageValue::Float(f.to_bits())
+        } else if let Some(ptr) = value.as_gc_any() {
+            ImageValue::Object(self.id(ptr)?)
+        } else if value.is_unit() {
+            ImageValue::Unit
+        } else if let Some(b) = value.as_bool() {
+            ImageValue::Bool(b)
+        } else if let Some(c) = value.as_char() {
+            ImageValue::Char(c)
+        } else if let Some(n) = value.as_nat() {
+            ImageValue::Nat(n.as_u64())
+        } else if let Some(i) = value.as_int() {
+            ImageValue::Int(i.as_i64())
+        } else if let Some(s) = value.as_inline_string() {
+            ImageValue::String(s.into())
+        } else {
+            unreachable!("every kind of value is covered")
+        };
+
+        Some(value)
+    }
+
+    fn object(
+        &self,
+        ptr: GcAny,
+        code: &mut Vec<Arc<compiler::Module>>,
+    ) -> Result<ImageObject> {
+        let id = |ptr: GcAny| self.id(ptr).expect("reachable objects have ids");
+        let values = |values: Vec<Value>| {
+            values.into_iter().map(|v| self.value(v)).collect()
+        };
+
+        let object = match ptr.deref().class_id() {
+            ClassId::CaptureCell => {
+                let cell = ptr.as_a::<CaptureCell>().unwrap();
+                let contents = match cell.contents() {
+                    CaptureCellContents::Stack(i) => {
+                        ImageCell::Open(i.as_usize())
+                    }
+                    CaptureCellContents::Inline(v) => {
+                        ImageCell::Closed(self.value(v))
+                    }
+                };
+
+                ImageObject::CaptureCell { contents }
+            }
+
+            ClassId::Closure => {
+                let closure = ptr.as_a::<Function>().unwrap();
+                let captures = (0..closure.capture_count())
+                    .map(|i| id(closure.get_capture_cell(Index::new(i)).into()))
+                    .collect();
+
+                ImageObject::Closure {
+                    prototype: id(closure.prototype().into()),
+                    captures,
+                }
+            }
+
+            ClassId::Foreign => {
+                return Err(Error::CannotSaveImage(
+                    "host objects can't be saved",
+                ))
+            }
+
+            ClassId::Keyword => ImageObject::Keyword {
+                name: ptr.as_a::<Keyword>().unwrap().as_str().into(),
+            },
+
+            ClassId::List => ImageObject::List {
+                elements: values(ptr.as_a::<List>().unwrap().to_vec()),
+            },
+
+            ClassId::Module => {
+                let module = ptr.as_a::<Module>().unwrap();
+
+                let index = match code
+                    .iter()
+                    .position(|c| Arc::ptr_eq(c, module.code()))
+                {
+                    Some(index) => index,
+                    None => {
+                        code.push(module.code().clone());
+                        code.len() - 1
+                    }
+                };
+
+                let constants = (0..module.code().constants().len())
+                    .map(|i| {
+                        let i = Index::<Constant>::new(i as u32);
+                        module.constant(i).map(|v| self.value(v))
+                    })
+                    .collect();
+
+                ImageObject::Module {
+                    code: index,
+                    constants,
+                }
+            }
+
+            ClassId::Prototype => {
+                let prototype = ptr.as_a::<Prototype>().unwrap();
+
+                ImageObject::Prototype {
+                    module: id(prototype.module().into()),
+                    function: prototype.index().as_usize() as u32,
+                }
+            }
+
+            ClassId::String => ImageObject::String {
+                text: ptr.as_a::<classes::String>().unwrap().as_str().into(),
+            },
+
+            ClassId::Tuple => {
+                let tuple = ptr.as_a::<Tuple>().unwrap();
+
+                ImageObject::Tuple {
+                    tag: tuple.tag().map(|tag| id(tag.into())),
+                    elements: values(tuple.to_vec()),
+                }
+            }
+
+            ClassId::Weak => ImageObject::Weak {
+                target: ptr
+                    .as_a::<Weak>()
+                    .unwrap()
+                    .get()
+                    .and_then(|v| self.weak_value(v)),
+            },
+        };
+
+        Ok(object)
+    }
+}
+
+impl VirtualMachine {
+    /// Save everything needed to pick up where this VM is now.
+    ///
+    /// This fails if the VM can reach any [`Foreign`][crate::classes::Foreign]
+    /// objects, or if it's in the middle of a [call][VirtualMachine::call].
+    pub fn image(&self) -> Result<Image> {
+        if self.return_depth.is_some() {
+            return Err(Error::CannotSaveImage("the VM is in a call"));
+        }
+
+        let mut saver = Saver::default();
+
+        let checkpoint_value = self.checkpoint.and_then(|c| c.value());
+        let roots = self
+            .stack
+            .as_slice()
+            .iter()
+            .chain(checkpoint_value.iter())
+            .filter_map(|value| value.as_gc_any())
+            .chain(self.modules.iter().map(|&m| m.into()))
+            .chain(self.open_captures.iter().map(|&c| c.into()));
+
+        for root in roots {
+            saver.discover(root);
+        }
+
+        let mut code = Vec::new();
+        let objects = saver
+            .objects
+            .iter()
+            .map(|&ptr| saver.object(ptr, &mut code))
+            .collect::<Result<_>>()?;
+
+        let id = |ptr: GcAny| saver.id(ptr).expect("roots have ids");
+
+        let mut frames: Vec<_> = self
+            .call_stack
+            .iter()
+            .map(|frame| ImageFrame {
+                bp: frame.bp().as_usize(),
+                pc: frame.pc().as_usize() as u32,
+            })
+            .collect();
+        frames.reverse();
+
+        let mut open_captures: Vec<_> =
+            self.open_captures.iter().map(|&c| id(c.into())).collect();
+        open_captures.reverse();
+
+        let checkpoint = self.checkpoint.map(|c| ImageCheckpoint {
+            height: c.height,
+            main: c.main.map(|(v, pc)| (saver.value(v), pc.as_usize() as u32)),
+        });
+
+        Ok(Image {
+            version: Image::VERSION,
+            code: code.iter().map(|c| c.to_string()).collect(),
+            objects,
+            modules: self.modules.iter().map(|&m| id(m.into())).collect(),
+            stack: self
+                .stack
+                .as_slice()
+                .iter()
+                .map(|&v| saver.value(v))
+                .collect(),
+            frames,
+            open_captures,
+            checkpoint,
+            suspended: self.suspended,
+        })
+    }
+
+    /// Restore a VM from an [`Image`], so it picks up where the saved one
+    /// was.
+    ///
+    /// The image is checked before anything is changed, and an invalid one
+    /// is an [`Error::InvalidImage`]. If restoring fails after that, because
+    /// the VM ran out of memory, the VM should be thrown away.
+    ///
+    /// # Panics
+    ///
+    /// This panics if anything has been loaded into the VM already.
+    pub fn restore(&mut self, image: &Image) -> Result<()> {
+        assert!(
+            self.modules.is_empty() && self.stack.is_empty(),
+            "images can only be restored into a new VM"
+        );
+
+        image.validate().map_err(Error::InvalidImage)?;
+
+        let code = image
+            .code
+            .iter()
+            .map(|listing| {
+                let module =
+                    compiler::Module::assemble(listing).map_err(|_| {
+                        Error::InvalidImage("code doesn't assemble")
+                    })?;
+                module.verify()?;
+                Ok(Arc::new(module))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        image.validate_code(&code).map_err(Error::InvalidImage)?;
+
+        let scope = self.handle_scope();
+        let mut made = vec![Value::UNIT; image.objects.len()];
+
+        // Objects are made in a few passes, so that everything's made before
+        // anything needs it. Tuple tags have to be there when the tuple is
+        // made, and prototypes are made along with their modules.
+        for (id, object) in image.objects.iter().enumerate() {
+            let value = match object {
+                ImageObject::Keyword { name } => Value::gc(self.keyword(name)?),
+                ImageObject::String { text } => {
+                    let string: Gc<classes::String> =
+                        self.make_from(text.as_str())?;
+                    Value::gc(string)
+                }
+                ImageObject::Module { code: i, .. } => {
+                    let module: Gc<Module> =
+                        self.make_from(code[*i].clone())?;
+                    scope.local(Value::gc(module));
+
+                    unsafe {
+                        Module::destructively_set_up_prototypes(module, self)?
+                    };
+
+                    Value::gc(module)
+                }
+                _ => continue,
+            };
+
+            scope.local(value);
+            made[id] = value;
+        }
+
+        for (id, object) in image.objects.iter().enumerate() {
+            let value = match object {
+                ImageObject::Prototype { module, function } => {
+                    let module: Gc<Module> = made[*module].as_gc().unwrap();
+                    let prototype: &Gc<Prototype> =
+                        module.get(Index::new(*function)).unwrap();
+                    Value::gc(*prototype)
+                }
+                _ => continue,
+            };
+
+            made[id] = value;
+        }
+
+        for (id, object) in image.objects.iter().enumerate() {
+            let value = match object {
+                ImageObject::CaptureCell { contents } => {
+                    let cell: Gc<CaptureCell> = match contents {
+                        ImageCell::Open(i) => {
+                            self.make_from(Index::<Stack>::new(*i as u32))?
+                        }
+                        ImageCell::Closed(_) => self.make_from(Value::UNIT)?,
+                    };
+                    Value::gc(cell)
+                }
+                ImageObject::Closure { prototype, .. } => {
+                    let prototype: Gc<Prototype> =
+                        made[*prototype].as_gc().unwrap();
+                    let closure: Gc<Function> = self.make_from(prototype)?;
+                    Value::gc(closure)
+                }
+                ImageObject::List { .. } => {
+                    let list: Gc<List> = self.make_from(Vec::new())?;
+                    Value::gc(list)
+                }
+                ImageObject::Tuple { tag, .. } => {
+                    let tag = tag.map(|tag| made[tag].as_gc().unwrap());
+                    let tuple: Gc<Tuple> = self.make_from((Vec::new(), tag))?;
+                    Value::gc(tuple)
+                }
+                ImageObject::Weak { .. } => {
+                    Value::gc(self.make_weak(Value::UNIT)?)
+                }
+                _ => continue,
+            };
+
+            scope.local(value);
+            made[id] = value;
+        }
+
+        // Now everything exists, fill in what they point to.
+        let value = |value: &ImageValue| match value {
+            ImageValue::Unit => Value::UNIT,
+            ImageValue::Bool(b) => Value::bool(*b),
+            ImageValue::Char(c) => Value::char(*c),
+            ImageValue::Nat(n) => Value::nat(u48::from_u64_unchecked(*n)),
+            ImageValue::Int(i) => Value::int(i48::from_i64_unchecked(*i)),
+            ImageValue::Float(bits) => Value::float(f64::from_bits(*bits)),
+            ImageValue::String(s) => Value::inline_string(s).unwrap(),
+            ImageValue::Object(id) => made[*id],
+        };
+
+        for (id, object) in image.objects.iter().enumerate() {
+            match object {
+                ImageObject::CaptureCell {
+                    contents: ImageCell::Closed(v),
+                } => {
+                    let cell: Gc<CaptureCell> = made[id].as_gc().unwrap();
+                    cell.close(value(v), self);
+                }
+                ImageObject::Closure { captures, .. } => {
+                    let closure: Gc<Function> = made[id].as_gc().unwrap();
+                    for &cell in captures {
+                        closure.push_capture_cell(
+                            made[cell].as_gc().unwrap(),
+                            self,
+                        )?;
+                    }
+                }
+                ImageObject::List { elements } => {
+                    let list: Gc<List> = made[id].as_gc().unwrap();
+                    for element in elements {
+                        list.push(value(element), self)?;
+                    }
+                }
+                ImageObject::Module { constants, .. } => {
+                    let module: Gc<Module> = made[id].as_gc().unwrap();
+                    for (i, constant) in constants.iter().enumerate() {
+                        if let Some(constant) = constant {
+                            let index = Index::new(i as u32);
+                            self.set_module_constant(
+                                module,
+                                index,
+                                value(constant),
+                            );
+                        }
+                    }
+                }
+                ImageObject::Tuple { elements, .. } => {
+                    let tuple: Gc<Tuple> = made[id].as_gc().unwrap();
+                    for element in elements {
+                        tuple.push(value(element), self)?;
+                    }
+                }
+                ImageObject::Weak { target } => {
+                    let weak: Gc<Weak> = made[id].as_gc().unwrap();
+                    weak.set(target.as_ref().map(value));
+                }
+                _ => {}
+            }
+        }
+
+        for &module in &image.modules {
+            self.modules.push(made[module].as_gc().unwrap());
+        }
+
+        for v in &image.stack {
+            self.stack.push(value(v));
+        }
+
+        for frame in &image.frames {
+            let pc = Index::<Op>::new(frame.pc);
+            let bp = Index::<Stack>::new(frame.bp as u32);
+            self.call_stack.push(CallFrame::new(pc, bp));
+        }
+
+        for &cell in &image.open_captures {
+            self.open_captures.push(made[cell].as_gc().unwrap());
+        }
+
+        self.checkpoint = image.checkpoint.as_ref().map(|c| Checkpoint {
+            height: c.height,
+            main: c.main.as_ref().map(|(v, pc)| (value(v), Index::new(*pc))),
+        });
+
+        self.suspended = image.suspended;
+
+        Ok(())
+    }
+}
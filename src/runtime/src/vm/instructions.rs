@@ -4,22 +4,39 @@ use common::{i48, Get, Index};
 use compiler::{Capture, Constant, Local, Op};
 
 use crate::{
-    classes::{Function, Keyword, List, Tuple},
+    classes::{Foreign, Function, Keyword, List, Tuple},
     error::Result,
     memory::Gc,
     primitives::PrimitiveOperations,
     value::Value,
     vm::{stack::StackTop, CallFrame, Stack},
-    Error, VirtualMachine,
+    CastError, Error, VirtualMachine,
 };
 
 impl VirtualMachine {
     /// Start the VM up again.
     pub(crate) fn run(&mut self) -> Result<()> {
+        self.suspended = false;
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.resume();
+        }
+
         loop {
+            self.check_limits()?;
+            self.check_interrupt()?;
+
             #[cfg(feature = "trace")]
             self.trace();
 
+            if self.debugging.is_attached() {
+                self.debug_hook();
+            }
+
+            if let Some(profiler) = &mut self.profiler {
+                profiler.record(&self.call_stack, &self.stack);
+            }
+
             match self.fetch() {
                 // control
                 Op::Halt => return Ok(()),
@@ -53,8 +70,14 @@ impl VirtualMachine {
                 Op::SetIndex => self.set_index()?,
 
                 // functions
-                Op::Call(arg_count) => self.call(arg_count)?,
-                Op::Return => self.r#return()?,
+                Op::Call(arg_count) => self.push_call(arg_count)?,
+                Op::Return => {
+                    self.r#return()?;
+
+                    if Some(self.call_stack.len()) == self.return_depth {
+                        return Ok(());
+                    }
+                }
 
                 // branching
                 Op::Jump(i) => self.jump(i)?,
@@ -106,14 +129,14 @@ impl VirtualMachine {
     /// Closes any open upvalues which occur in the open list with a stack index
     /// above `top`.
     #[inline]
-    fn close_captures_above(&mut self, top: Index<Stack>) {
+    pub(crate) fn close_captures_above(&mut self, top: Index<Stack>) {
         while let Some(cell) = self.open_captures.pop_if_above(top) {
             let index = cell.stack_index().expect(
                 "cells in the open list should be open, \
                         and so must have a stack index",
             );
             let value = self.stack[index];
-            cell.close(value);
+            cell.close(value, self);
         }
     }
 }
@@ -160,13 +183,15 @@ impl VirtualMachine {
 
     /// The [`LoadConstant`][Op::LoadConstant] instruction loads a constant from
     /// the current module's constant pool using the given `index` and places it
-    /// on the stop of the stack.
+    /// on the stop of the stack. Constants are inflated the first time they're
+    /// loaded.
     #[inline]
     fn load_constant(&mut self, index: Index<Constant>) -> Result<()> {
-        let constant = self
-            .current_module()
-            .constant(index)
-            .expect("constant index out of range");
+        let module = self.current_module();
+        let constant = match module.constant(index) {
+            Some(constant) => constant,
+            None => self.module_constant(module, index)?,
+        };
 
         self.stack.push(constant);
         Ok(())
@@ -219,7 +244,7 @@ impl VirtualMachine {
         let current_module = current_closure.module();
 
         let prototype = *current_module.get(index).unwrap();
-        let new_closure: Gc<Function> = self.make_from(prototype);
+        let new_closure: Gc<Function> = self.make_from(prototype)?;
         self.stack.push(Value::from(new_closure));
 
         // now we set up the capture cells
@@ -229,7 +254,6 @@ impl VirtualMachine {
             let capture_index: Index<compiler::Capture> = Index::new(i);
 
             let capture = prototype.get(capture_index).unwrap();
-            dbg!(capture);
             let cell = match capture {
                 Capture::Local(local_index) => {
                     // we want to check the open list here first to see if we want to reuse one.
@@ -238,7 +262,7 @@ impl VirtualMachine {
                     if let Some(existing_cell) = self.open_captures.get(index) {
                         existing_cell
                     } else {
-                        let new_cell = self.make_from(index);
+                        let new_cell = self.make_from(index)?;
                         self.open_captures.push(new_cell);
                         new_cell
                     }
@@ -249,7 +273,7 @@ impl VirtualMachine {
                 }
             };
 
-            new_closure.push_capture_cell(cell);
+            new_closure.push_capture_cell(cell, self)?;
         }
 
         Ok(())
@@ -279,7 +303,7 @@ impl VirtualMachine {
 
         match cell.contents() {
             crate::classes::CaptureCellContents::Inline(_) => {
-                cell.close(new_value)
+                cell.close(new_value, self)
             }
             crate::classes::CaptureCellContents::Stack(i) => {
                 self.stack[i] = new_value
@@ -313,9 +337,21 @@ impl VirtualMachine {
     ///
     /// The target of the function call is the value before the arguments on
     /// the stack.
+    ///
+    /// A [`Foreign`] object is called right away, without a new frame, and its
+    /// result replaces it and the arguments on the stack.
     #[inline]
-    fn call(&mut self, arg_count: u32) -> Result<()> {
+    pub(crate) fn push_call(&mut self, arg_count: u32) -> Result<()> {
         let bp = self.stack.from_top(Index::new(arg_count));
+
+        if let Some(foreign) = self.stack[bp].as_gc::<Foreign>() {
+            let args = self.stack().above(Index::new(arg_count)).to_vec();
+            let result = foreign.call(&args, self)?;
+            self.stack[bp] = result;
+            self.stack.truncate_above(bp);
+            return Ok(());
+        }
+
         let target: Gc<Function> = self.stack[bp].try_into()?;
         let parameter_count = target.prototype().parameter_count();
 
@@ -326,6 +362,8 @@ impl VirtualMachine {
             });
         }
 
+        self.check_call_depth()?;
+
         let new_frame = CallFrame::new(Index::START, bp);
         self.call_stack.push(new_frame);
 
@@ -356,7 +394,7 @@ impl VirtualMachine {
         // Empty lists don't have on-stack values to pop, so we have to do
         // things a bit differently.
         if len == 0 {
-            let empty_list: Gc<List> = self.make_from(Vec::new());
+            let empty_list: Gc<List> = self.make_from(Vec::new())?;
             self.stack.push(Value::from(empty_list));
             return Ok(());
         }
@@ -366,7 +404,7 @@ impl VirtualMachine {
         let value = {
             let values = self.stack().above(under_elements).to_vec();
             debug_assert_eq!(values.len(), len as usize);
-            let list: Gc<List> = self.make_from(values);
+            let list: Gc<List> = self.make_from(values)?;
             Value::from(list)
         };
 
@@ -392,28 +430,34 @@ impl VirtualMachine {
             return Ok(());
         }
 
+        let under_elements = Index::<StackTop>::new(len);
+
+        // The compiler only ever tags with a keyword, but the verifier can't
+        // tell what's on the stack, so hand-written code could use anything.
+        let tag = if is_tagged {
+            let tag = self.stack[under_elements];
+            let keyword = tag.as_gc::<Keyword>().ok_or(CastError {
+                from: tag.type_name(),
+                to: "Keyword",
+            })?;
+            Some(keyword)
+        } else {
+            None
+        };
+
         if len == 0 && is_tagged {
             // this is just the bare keyword, and that keyword is on the top of
             // the stack (beneath the 0 elements), so we just leave it.
             return Ok(());
         }
 
-        let under_elements = Index::<StackTop>::new(len);
-
         // here we actually make the new Gc<Tuple>
         let value = {
             let values = self.stack().above(under_elements).to_vec();
 
-            let tag = if is_tagged {
-                self.stack[under_elements].as_gc::<Keyword>()
-            } else {
-                None
-            };
-
             debug_assert_eq!(values.len(), len as usize);
-            debug_assert_eq!(is_tagged, tag.is_some());
 
-            let tup: Gc<Tuple> = self.make_from((values, tag));
+            let tup: Gc<Tuple> = self.make_from((values, tag))?;
 
             Value::from(tup)
         };
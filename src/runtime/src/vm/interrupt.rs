@@ -0,0 +1,62 @@
+//! Stopping a running program from somewhere else.
+//!
+//! A host which runs code it doesn't trust can get an [`InterruptHandle`] from
+//! the [`VirtualMachine`] and hand it to another thread, say one with a timer.
+//! Interrupting the handle makes the VM stop before its next op, returning
+//! [`Error::Interrupted`] from whatever was running it.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{vm::VirtualMachine, Error, Result};
+
+/// A handle which can interrupt a [`VirtualMachine`], from any thread.
+#[derive(Debug, Default, Clone)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// Ask the VM to stop before running its next op.
+    ///
+    /// If the VM isn't running anything, the next thing it runs stops right
+    /// away instead.
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Is there an interrupt the VM hasn't stopped for yet?
+    pub fn is_interrupted(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Take back an interrupt the VM hasn't stopped for yet.
+    pub fn cancel(&self) {
+        self.requested.store(false, Ordering::Relaxed);
+    }
+}
+
+impl VirtualMachine {
+    /// A handle which can be used to stop this VM from another thread.
+    ///
+    /// Every handle from the same VM is the same.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Stop if there's been an interrupt since we last checked.
+    ///
+    /// This is called by the dispatch loop before each op is fetched.
+    #[inline]
+    pub(crate) fn check_interrupt(&mut self) -> Result<()> {
+        if self.interrupt.is_interrupted() {
+            self.interrupt.cancel();
+            self.suspended = true;
+            return Err(Error::Interrupted);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! Limits on the resources a program can use.
+//!
+//! These are for running code that isn't trusted, so that a program can't run
+//! forever or use up all the host's memory. Each limit has its own [`Error`]
+//! when it's reached.
+//!
+//! Fuel is a bit different from the others. Each op run uses up one unit of
+//! fuel, and when it's gone execution stops with [`Error::OutOfFuel`]. This
+//! happens between ops, so after [adding more fuel][VirtualMachine::add_fuel]
+//! it can pick up where it left off with [`VirtualMachine::resume`]. That's
+//! enough to interleave running a few VMs on the same thread.
+
+use crate::{
+    vm::{CallStack, VirtualMachine},
+    Error, Result,
+};
+
+/// Resource limits for a [`VirtualMachine`].
+///
+/// A limit of `None` means there is no limit, which is the default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The number of ops which can be run before more fuel must be added.
+    pub fuel: Option<u64>,
+
+    /// The most values which can be on the [`Stack`][crate::Stack].
+    pub stack: Option<usize>,
+
+    /// The most call frames which can be on the call stack.
+    ///
+    /// This can only lower the limit from [`CallStack::MAX_DEPTH`], which is
+    /// always in place.
+    pub call_stack: Option<usize>,
+
+    /// The most bytes which can be allocated on the heap at once, including
+    /// the elements of lists, tuples and closures' captures.
+    pub heap: Option<usize>,
+}
+
+impl VirtualMachine {
+    /// Create a new VM with some resource limits.
+    pub fn with_limits(limits: Limits) -> VirtualMachine {
+        let mut vm = VirtualMachine::default();
+        vm.set_limits(limits);
+        vm
+    }
+
+    /// The limits currently in place.
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Change the limits, which resets the fuel to `limits.fuel`.
+    ///
+    /// Anything already over the new limits is left alone, but the next op
+    /// run will return an error.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.fuel = limits.fuel;
+        self.gc_state.set_limit(limits.heap);
+    }
+
+    /// The fuel left, or `None` if fuel isn't limited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Top up the fuel. This does nothing if fuel isn't limited.
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(left) = &mut self.fuel {
+            *left = left.saturating_add(fuel);
+        }
+    }
+
+    /// Carry on running after [`Error::OutOfFuel`] was returned, once more
+    /// fuel has been added, or after [`Error::Interrupted`].
+    ///
+    /// It's an [`Error::NothingToResume`] to call this any other time.
+    pub fn resume(&mut self) -> Result<()> {
+        if !self.suspended {
+            return Err(Error::NothingToResume);
+        }
+
+        self.run()
+    }
+
+    /// Check the limits before running another op, using up one unit of fuel.
+    ///
+    /// This is called by the dispatch loop before each op is fetched.
+    #[inline]
+    pub(crate) fn check_limits(&mut self) -> Result<()> {
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                self.suspended = true;
+                return Err(Error::OutOfFuel);
+            }
+
+            *fuel -= 1;
+        }
+
+        if self.limits.stack.is_some_and(|max| self.stack.len() > max) {
+            return Err(Error::StackLimitExceeded);
+        }
+
+        Ok(())
+    }
+
+    /// Check there's room for one more call frame.
+    #[inline]
+    pub(crate) fn check_call_depth(&self) -> Result<()> {
+        let max = self
+            .limits
+            .call_stack
+            .map_or(CallStack::MAX_DEPTH, |max| max.min(CallStack::MAX_DEPTH));
+
+        if self.call_stack.len() >= max {
+            Err(Error::StackOverflow)
+        } else {
+            Ok(())
+        }
+    }
+}
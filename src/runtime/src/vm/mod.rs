@@ -1,25 +1,46 @@
 //! The virtual machine is the heart of how the language executes code.
 
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+
 use common::Index;
 use compiler::{Constant, Op};
 use diagnostic::Span;
 
+mod call;
 mod call_stack;
+mod debugger;
+mod extend;
+mod image;
 mod instructions;
+mod interrupt;
+mod limits;
 mod open_captures;
+mod profiler;
+mod reload;
 mod stack;
 mod stack_trace;
 
 use crate::{
-    classes::{Function, Keyword, Module, String},
-    memory::{collector::GcState, Gc},
+    classes::{Function, Module},
+    memory::{
+        collector::{Finalizer, GcState},
+        Gc, Global, Handles,
+    },
     value::Value,
-    vm::open_captures::OpenCaptures,
+    vm::{
+        debugger::Debugging, extend::Checkpoint, open_captures::OpenCaptures,
+        profiler::Profiler,
+    },
     Result,
 };
 
 pub use self::{
     call_stack::{CallFrame, CallStack},
+    debugger::{Debugger, EvaluateError, Pause, Resume},
+    image::Image,
+    interrupt::InterruptHandle,
+    limits::Limits,
+    profiler::{Cost, FunctionProfile, LineProfile, Profile},
     stack::Stack,
 };
 
@@ -32,14 +53,53 @@ pub struct VirtualMachine {
     stack: Stack,
     call_stack: CallStack,
 
+    /// Where to go back to if extending the top-level code fails, see
+    /// [`VirtualMachine::extend`].
+    checkpoint: Option<Checkpoint>,
+
+    /// The attached [`Debugger`] and breakpoints.
+    debugging: Debugging,
+
+    /// Where time is being spent, while profiling.
+    profiler: Option<Box<Profiler>>,
+
+    /// The resource limits, and the fuel left.
+    limits: Limits,
+    fuel: Option<u64>,
+
+    /// Did the last run stop because it ran out of fuel or was interrupted?
+    suspended: bool,
+
+    /// The call stack's depth to stop running at, once a frame returns to it.
+    /// This is set by [`VirtualMachine::call`].
+    return_depth: Option<usize>,
+
+    /// How many calls to [`VirtualMachine::call`] are running inside each
+    /// other.
+    native_depth: usize,
+
+    /// Set from outside to stop whatever is running.
+    interrupt: InterruptHandle,
+
+    /// The installed native libraries, by name.
+    pub(crate) libraries: HashMap<String, Global>,
+
     // Heap
     pub(crate) open_captures: OpenCaptures,
     pub(crate) gc_state: GcState,
+    pub(crate) finalizer: Option<Finalizer>,
+    pub(crate) handles: Rc<RefCell<Handles>>,
 }
 
 impl VirtualMachine {
     /// Load a module into the runtime and execute its top-level code.
-    pub fn load(&mut self, module: compiler::Module) -> Result<()> {
+    ///
+    /// The module can be shared: any number of VMs can load the same
+    /// [`Arc<compiler::Module>`][Arc] without copying its code.
+    pub fn load(
+        &mut self,
+        module: impl Into<Arc<compiler::Module>>,
+    ) -> Result<()> {
         self.load_without_running(module)?;
 
         let new_module = *self
@@ -47,7 +107,7 @@ impl VirtualMachine {
             .last()
             .expect("load_without_running left a module for us");
 
-        let main_closure: Gc<Function> = self.make_from(new_module.main());
+        let main_closure: Gc<Function> = self.make_from(new_module.main())?;
 
         self.stack.push(Value::from(main_closure));
         let bp = self.stack.from_top(Index::START);
@@ -72,21 +132,28 @@ impl VirtualMachine {
 impl VirtualMachine {
     pub(crate) fn load_without_running(
         &mut self,
-        module: compiler::Module,
+        module: impl Into<Arc<compiler::Module>>,
     ) -> Result<()> {
-        let live_module: Gc<Module> = self.make_from(());
+        let module = module.into();
+
+        // Everything after this point trusts the module's code completely. A
+        // shared module is only checked by the first VM to load it.
+        module.verify()?;
+
+        let live_module: Gc<Module> = self.make_from(module)?;
 
         self.modules.push(live_module);
 
-        unsafe {
-            Module::destructively_set_up_from_compiler_module(
-                live_module,
-                module,
-                self,
-            )
+        let result = unsafe {
+            Module::destructively_set_up_prototypes(live_module, self)
         };
 
-        Ok(())
+        // A module that's only partly set up can't be run.
+        if result.is_err() {
+            self.modules.pop();
+        }
+
+        result
     }
 
     /// A reference to the [`VirtualMachine`]'s [`Stack`].
@@ -94,10 +161,19 @@ impl VirtualMachine {
         &self.stack
     }
 
+    /// A mutable reference to the [`VirtualMachine`]'s [`Stack`].
+    pub(crate) fn stack_mut(&mut self) -> &mut Stack {
+        &mut self.stack
+    }
+
     pub(crate) fn modules(&self) -> &[Gc<Module>] {
         &self.modules
     }
 
+    pub(crate) fn checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoint
+    }
+
     /// The base pointer, or the value which indicates where in the stack values
     /// pertaining to the currently executing closure begin.
     ///
@@ -170,18 +246,17 @@ impl VirtualMachine {
 
 impl VirtualMachine {
     /// Inflate a [`Constant`] into a full-fledged runtime value.
-    pub(crate) fn inflate(&mut self, constant: &Constant) -> Value {
-        match constant {
+    pub(crate) fn inflate(&mut self, constant: &Constant) -> Result<Value> {
+        let value = match constant {
             Constant::Character(c) => Value::char(*c),
             Constant::Float(bits) => Value::float(f64::from_bits(*bits)),
-            Constant::String(s) => {
-                let string: Gc<String> = self.make_from(s.as_str());
-                Value::gc(string)
-            }
+            Constant::String(s) => self.string(s)?,
             Constant::Keyword(kw) => {
-                let keyword: Gc<Keyword> = self.make_from(kw.as_str());
+                let keyword = self.keyword(kw.as_str())?;
                 Value::gc(keyword)
             }
-        }
+        };
+
+        Ok(value)
     }
 }
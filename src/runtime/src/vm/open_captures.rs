@@ -48,6 +48,11 @@ impl OpenCaptures {
         }
     }
 
+    /// Remove all the open capture cells, from least to most recent.
+    pub(crate) fn take(&mut self) -> Vec<Gc<CaptureCell>> {
+        std::mem::take(&mut self.cells)
+    }
+
     /// Iterator over all the open capture cells, from most recent to least recent.
     pub(crate) fn iter(&self) -> impl Iterator<Item = &Gc<CaptureCell>> {
         self.cells.iter().rev()
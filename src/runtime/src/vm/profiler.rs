@@ -0,0 +1,352 @@
+//! Counting where a program spends its time.
+//!
+//! While profiling, every op executed is counted against the function it's in,
+//! the source line it came from, and the whole stack of functions that led to
+//! it. The wall time between ops is measured too, so it's only useful for
+//! comparing parts of a program to each other, since measuring has a cost.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use diagnostic::Span;
+
+use crate::{
+    classes::{Function, Prototype},
+    vm::{CallStack, Stack, VirtualMachine},
+};
+
+/// The ops executed and time spent somewhere.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cost {
+    pub ops: u64,
+    pub time: Duration,
+}
+
+impl std::ops::AddAssign for Cost {
+    fn add_assign(&mut self, other: Cost) {
+        self.ops += other.ops;
+        self.time += other.time;
+    }
+}
+
+/// The cost of a function, not counting any functions it calls.
+#[derive(Debug, Clone)]
+pub struct FunctionProfile {
+    pub name: std::string::String,
+    pub span: Span,
+    pub cost: Cost,
+}
+
+/// The cost of a line of source code in a function.
+///
+/// Code without debug info doesn't have a line.
+#[derive(Debug, Clone)]
+pub struct LineProfile {
+    pub function: std::string::String,
+    pub line: Option<u32>,
+    pub cost: Cost,
+}
+
+/// The results of profiling, with the most expensive things first.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    functions: Vec<FunctionProfile>,
+    lines: Vec<LineProfile>,
+    stacks: Vec<(Vec<std::string::String>, Cost)>,
+}
+
+impl Profile {
+    /// The cost of each function, most expensive first.
+    pub fn functions(&self) -> &[FunctionProfile] {
+        &self.functions
+    }
+
+    /// The cost of each line, most expensive first.
+    pub fn lines(&self) -> &[LineProfile] {
+        &self.lines
+    }
+
+    /// The total cost of everything profiled.
+    pub fn total(&self) -> Cost {
+        let mut total = Cost::default();
+
+        for function in &self.functions {
+            total += function.cost;
+        }
+
+        total
+    }
+
+    /// Write the stacks in the 'folded' format used by flamegraph tools, with
+    /// one line per stack like `main;outer;inner 12`.
+    ///
+    /// Stacks are weighted by the number of ops run, since that doesn't change
+    /// from run to run.
+    pub fn write_folded(&self, w: &mut impl io::Write) -> io::Result<()> {
+        for (stack, cost) in &self.stacks {
+            writeln!(w, "{} {}", stack.join(";"), cost.ops)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{:>12} {:>12}  function", "ops", "time")?;
+
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:>12} {:>12}  {} at {}",
+                function.cost.ops,
+                format!("{:.2?}", function.cost.time),
+                function.name,
+                function.span,
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:>12} {:>12}  line", "ops", "time")?;
+
+        for line in &self.lines {
+            write!(
+                f,
+                "{:>12} {:>12}  ",
+                line.cost.ops,
+                format!("{:.2?}", line.cost.time),
+            )?;
+
+            match line.line {
+                Some(n) => writeln!(f, "{n} in {}", line.function)?,
+                None => writeln!(f, "? in {}", line.function)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A function seen while profiling.
+struct FunctionInfo {
+    name: std::string::String,
+    span: Span,
+
+    /// The code the function is in, which is kept so that its address can't
+    /// be reused for other code while profiling.
+    _code: Arc<compiler::Module>,
+}
+
+/// Which function some code is: the address of the compiled module it's in,
+/// and its index there.
+///
+/// This can't be a prototype's address. Reloading a module retires the old
+/// code's prototypes, which can then be collected and their addresses reused
+/// for other functions.
+type FunctionKey = (*const compiler::Module, u32);
+
+fn function_key(prototype: &Prototype) -> FunctionKey {
+    let code = Arc::as_ptr(prototype.module().code());
+    (code, u32::from(prototype.index()))
+}
+
+/// The profiler's state, kept by the VM while profiling.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    /// Every function seen so far, looked up by its code.
+    functions: Vec<FunctionInfo>,
+    function_ids: HashMap<FunctionKey, usize>,
+
+    /// Costs for each function and line.
+    lines: HashMap<(usize, Option<u32>), Cost>,
+
+    /// Costs for each distinct stack of functions, bottom first.
+    stacks: HashMap<Vec<usize>, usize>,
+    stack_costs: Vec<Cost>,
+
+    /// The stack of the last op, which only needs to be worked out again when
+    /// the call stack changes.
+    stack: Option<(usize, FunctionKey, usize)>,
+
+    /// The last op's function, line and stack, and when it started.
+    last: Option<(usize, Option<u32>, usize, Instant)>,
+}
+
+impl Profiler {
+    /// Forget when the last op ran, so that time spent not running code isn't
+    /// counted against it.
+    pub(crate) fn resume(&mut self) {
+        self.last = None;
+    }
+
+    /// Count the op about to run.
+    pub(crate) fn record(&mut self, call_stack: &CallStack, stack: &Stack) {
+        let now = Instant::now();
+
+        let frame = call_stack.frame();
+        let closure = stack[frame.bp()]
+            .as_gc::<Function>()
+            .expect("every frame base pointer is a closure");
+        let prototype = closure.prototype();
+
+        let function = self.function_id(&prototype);
+        let line = prototype
+            .debug_info()
+            .and_then(|d| d.span_of(frame.pc()))
+            .map(|span| span.start().line() + 1);
+        let stack_id = self.stack_id(call_stack, stack, &prototype);
+
+        if let Some((function, line, stack_id, then)) = self.last {
+            let time = now - then;
+            self.lines.entry((function, line)).or_default().time += time;
+            self.stack_costs[stack_id].time += time;
+        }
+
+        self.lines.entry((function, line)).or_default().ops += 1;
+        self.stack_costs[stack_id].ops += 1;
+
+        self.last = Some((function, line, stack_id, now));
+    }
+
+    fn function_id(&mut self, prototype: &Prototype) -> usize {
+        let key = function_key(prototype);
+
+        if let Some(id) = self.function_ids.get(&key) {
+            return *id;
+        }
+
+        let name = prototype
+            .name()
+            .as_str()
+            .map(|s| s.to_owned())
+            .unwrap_or_else(|| {
+                compiler::Function::DEFAULT_NAMELESS_NAME.to_owned()
+            });
+
+        let id = self.functions.len();
+        self.functions.push(FunctionInfo {
+            name,
+            span: prototype.span(),
+            _code: prototype.module().code().clone(),
+        });
+        self.function_ids.insert(key, id);
+
+        id
+    }
+
+    fn stack_id(
+        &mut self,
+        call_stack: &CallStack,
+        stack: &Stack,
+        top: &Prototype,
+    ) -> usize {
+        let key = (call_stack.len(), function_key(top));
+
+        if let Some((depth, function, id)) = self.stack {
+            if (depth, function) == key {
+                return id;
+            }
+        }
+
+        let mut functions: Vec<usize> = call_stack
+            .iter()
+            .map(|frame| {
+                let closure = stack[frame.bp()]
+                    .as_gc::<Function>()
+                    .expect("every frame base pointer is a closure");
+                self.function_id(&closure.prototype())
+            })
+            .collect();
+
+        functions.reverse();
+
+        let next = self.stack_costs.len();
+        let id = *self.stacks.entry(functions).or_insert(next);
+
+        if id == next {
+            self.stack_costs.push(Cost::default());
+        }
+
+        self.stack = Some((key.0, key.1, id));
+
+        id
+    }
+
+    /// Put together the results so far.
+    fn profile(&self) -> Profile {
+        let mut function_costs = vec![Cost::default(); self.functions.len()];
+
+        let mut lines: Vec<LineProfile> = self
+            .lines
+            .iter()
+            .map(|(&(function, line), &cost)| {
+                function_costs[function] += cost;
+
+                LineProfile {
+                    function: self.functions[function].name.clone(),
+                    line,
+                    cost,
+                }
+            })
+            .collect();
+
+        let mut functions: Vec<FunctionProfile> = self
+            .functions
+            .iter()
+            .zip(function_costs)
+            .map(|(info, cost)| FunctionProfile {
+                name: info.name.clone(),
+                span: info.span,
+                cost,
+            })
+            .collect();
+
+        let mut stacks: Vec<(Vec<std::string::String>, Cost)> = self
+            .stacks
+            .iter()
+            .map(|(functions, &id)| {
+                let names = functions
+                    .iter()
+                    .map(|&f| self.functions[f].name.clone())
+                    .collect();
+
+                (names, self.stack_costs[id])
+            })
+            .collect();
+
+        let most_expensive =
+            |a: &Cost, b: &Cost| b.time.cmp(&a.time).then(b.ops.cmp(&a.ops));
+
+        functions.sort_by(|a, b| most_expensive(&a.cost, &b.cost));
+        lines.sort_by(|a, b| most_expensive(&a.cost, &b.cost));
+        stacks.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Profile {
+            functions,
+            lines,
+            stacks,
+        }
+    }
+}
+
+impl VirtualMachine {
+    /// Start counting where time is spent, throwing away any earlier results.
+    pub fn start_profiling(&mut self) {
+        self.profiler = Some(Box::default());
+    }
+
+    /// Stop profiling, returning the results if profiling was started.
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profiler.take().map(|p| p.profile())
+    }
+
+    /// The results of profiling so far, if profiling was started.
+    pub fn profile(&self) -> Option<Profile> {
+        self.profiler.as_ref().map(|p| p.profile())
+    }
+}
@@ -0,0 +1,210 @@
+//! Replacing a loaded module's code while the VM keeps running.
+//!
+//! This is for picking up changes to a long-running script without starting
+//! over. The new code's top-level runs in place of the old, and then the two
+//! swap places: the loaded [`Module`] gets the new code, and everything made
+//! from the old code is moved to a retired module which is only kept alive by
+//! the closures still using it.
+
+use std::{collections::HashMap, sync::Arc};
+
+use common::Index;
+use compiler::Op;
+
+use crate::{
+    classes::{Function, Module, Prototype},
+    memory::Gc,
+    vm::{CallFrame, Stack, VirtualMachine},
+    Error, Result,
+};
+
+impl VirtualMachine {
+    /// Replace the most recently loaded module's code with `module`.
+    ///
+    /// The module's top-level code must have halted, like it has after
+    /// [`VirtualMachine::load`]. The new top-level code is run in its place,
+    /// and after that:
+    ///
+    /// - New calls use the new code, since the top-level bindings are the
+    ///   ones it made.
+    ///
+    /// - Closures made before reloading keep running their old code, with the
+    ///   old constants.
+    ///
+    /// - Top-level bindings are migrated by name. Closures which captured an
+    ///   old top-level binding see the new binding with the same name, or keep
+    ///   the old value if there isn't one. Names come from the debug info, so
+    ///   without it nothing is migrated.
+    ///
+    /// If the new code fails, nothing is reloaded and its error is returned.
+    pub fn reload(
+        &mut self,
+        module: impl Into<Arc<compiler::Module>>,
+    ) -> Result<()> {
+        let target = self.reload_target()?;
+
+        let old_bp = self.bp();
+        let old_pc = self.pc();
+        let old_main = self.current_closure().prototype();
+
+        let height = self.stack.len();
+        let depth = self.call_stack.len();
+        let loaded = self.modules.len();
+
+        if let Err(e) = self.run_reloaded(module) {
+            while self.call_stack.len() > depth {
+                self.call_stack.pop();
+            }
+
+            self.close_captures_above(Index::new(height as u32));
+            self.stack.truncate(height);
+            self.suspended = false;
+
+            self.modules.truncate(loaded);
+
+            return Err(e);
+        }
+
+        let new_bp = Index::<Stack>::new(height as u32);
+        let new_pc = self.pc();
+        let new_main = self.current_closure().prototype();
+
+        self.migrate_captures(
+            old_bp, old_pc, old_main, new_bp, new_pc, new_main,
+        );
+
+        // The new frame takes the old one's place on the stack.
+        let frame = self.stack.as_slice()[height..].to_vec();
+        self.stack.truncate(old_bp.as_usize());
+        for value in frame {
+            self.stack.push(value);
+        }
+
+        self.call_stack.pop();
+        self.call_stack.pop();
+        self.call_stack.push(CallFrame::new(new_pc, old_bp));
+
+        // The module the new code was loaded as ends up with the old code, and
+        // is only kept alive by anything still using it.
+        let retired = self.modules.pop().expect("the new module was loaded");
+
+        // SAFETY: Neither module's code is running, and they're both rooted:
+        //         the target is loaded and the retired one is on the stack.
+        unsafe { Module::destructively_swap(target, retired, self) };
+
+        // The old top-level code can't be extended any more.
+        self.checkpoint = None;
+
+        Ok(())
+    }
+
+    /// The module to reload, which must have halted top-level code.
+    fn reload_target(&self) -> Result<Gc<Module>> {
+        let target = *self.modules.last().ok_or(Error::InvalidReload)?;
+
+        if self.call_stack.len() == 0 || self.return_depth.is_some() {
+            return Err(Error::InvalidReload);
+        }
+
+        let main = self.current_closure().prototype();
+        let halted = self.pc().as_usize().checked_sub(2).is_some_and(|i| {
+            main.code().get(i..) == Some(&[Op::Nop, Op::Halt])
+        });
+
+        if !halted || !std::ptr::eq(&*main, &*target.main()) {
+            return Err(Error::InvalidReload);
+        }
+
+        Ok(target)
+    }
+
+    /// Load `module` and run its top-level code, on top of the old.
+    fn run_reloaded(
+        &mut self,
+        module: impl Into<Arc<compiler::Module>>,
+    ) -> Result<()> {
+        self.load_without_running(module)?;
+
+        let new_module = *self
+            .modules
+            .last()
+            .expect("load_without_running left a module for us");
+
+        let main_closure: Gc<Function> = self.make_from(new_module.main())?;
+
+        self.stack.push(main_closure.into());
+        let bp = self.stack.from_top(Index::START);
+
+        self.call_stack.push(CallFrame::new(Index::START, bp));
+
+        self.run()
+    }
+
+    /// Move the open capture cells to where they'll be once the new frame
+    /// replaces the old one.
+    fn migrate_captures(
+        &mut self,
+        old_bp: Index<Stack>,
+        old_pc: Index<Op>,
+        old_main: Gc<Prototype>,
+        new_bp: Index<Stack>,
+        new_pc: Index<Op>,
+        new_main: Gc<Prototype>,
+    ) {
+        // Where each top-level binding will be, by name.
+        let mut new_slots = HashMap::new();
+        if let Some(debug) = new_main.debug_info() {
+            for local in debug.locals_at(new_pc) {
+                new_slots.entry(local.name()).or_insert_with(|| {
+                    Stack::from_local(old_bp, local.index())
+                });
+            }
+        }
+
+        // The old top-level bindings, by where they are.
+        let mut old_names = HashMap::new();
+        if let Some(debug) = old_main.debug_info() {
+            for local in debug.locals_at(old_pc) {
+                let slot = Stack::from_local(old_bp, local.index());
+                old_names.entry(slot.as_usize()).or_insert(local.name());
+            }
+        }
+
+        let shift = new_bp.as_usize() - old_bp.as_usize();
+        let mut cells = self.open_captures.take();
+
+        cells.retain(|cell| {
+            let index = cell.stack_index().expect("open cells are open");
+
+            if index < old_bp {
+                return true;
+            }
+
+            if index >= new_bp {
+                cell.move_to(Index::new((index.as_usize() - shift) as u32));
+                return true;
+            }
+
+            let name = old_names.get(&index.as_usize());
+            match name.and_then(|name| new_slots.get(name)) {
+                Some(&slot) => {
+                    cell.move_to(slot);
+                    true
+                }
+                None => {
+                    let value = self.stack[index];
+                    cell.close(value, self);
+                    false
+                }
+            }
+        });
+
+        // Cells are closed from the top of the stack down, so they need to be
+        // in order.
+        cells.sort_by_key(|cell| cell.stack_index().map(Index::as_usize));
+
+        for cell in cells {
+            self.open_captures.push(cell);
+        }
+    }
+}
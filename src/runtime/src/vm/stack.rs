@@ -110,6 +110,13 @@ impl Stack {
         self.values.truncate(new_top.as_usize() + 1);
     }
 
+    /// Drop values from the top of the stack until there are only `len` left.
+    ///
+    /// Unlike [`Stack::truncate_above`], this can empty the stack.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+    }
+
     /// Returns a slice containing all the values on the stack above the given
     /// index.
     pub fn above(&self, top: Index<StackTop>) -> &[Value] {
@@ -5,12 +5,20 @@ use std::fmt::Write;
 use diagnostic::{Diagnostic, DiagnosticCoordinator, Level};
 
 use crate::{
-    classes::Function,
+    classes::{Function, Prototype},
     vm::{call_stack::CallFrame, VirtualMachine},
     Error,
 };
 
 impl VirtualMachine {
+    /// The longest block of frames that's looked for when collapsing
+    /// repeated frames in a stack trace.
+    const MAX_REPEATING_FRAMES: usize = 4;
+
+    /// How many times in a row a block of frames needs to appear before it's
+    /// collapsed in a stack trace.
+    const MIN_REPEATS: usize = 3;
+
     pub fn stack_trace(
         &self,
         error: Error,
@@ -18,6 +26,13 @@ impl VirtualMachine {
     ) {
         let mut d = Diagnostic::new(error.to_string());
 
+        // Errors can happen before anything starts running, like when a
+        // module fails to load, in which case there's no code to point to.
+        if self.call_stack.len() == 0 {
+            coordinator.register(d);
+            return;
+        }
+
         let id = self.current_closure().module().id();
 
         d.set_input(id);
@@ -28,13 +43,65 @@ impl VirtualMachine {
             d.info("debug info was stripped")
         });
 
-        for frame in self.call_stack.iter() {
-            let mut d = self.stack_trace_frame_diagnostic(frame);
-            d.set_input(id);
-            coordinator.register(d);
+        let frames: Vec<&CallFrame> = self.call_stack.iter().collect();
+        let mut i = 0;
+
+        while i < frames.len() {
+            let (period, repeats) = self.repetition(&frames[i..]);
+
+            for frame in &frames[i..i + period] {
+                let mut d = self.stack_trace_frame_diagnostic(frame);
+                d.set_input(id);
+                coordinator.register(d);
+            }
+
+            if repeats > 1 {
+                let mut d = Diagnostic::new(match period {
+                    1 => format!("the frame above repeats {} times", repeats),
+                    n => format!("the {n} frames above repeat {repeats} times"),
+                });
+                d.set_level(Level::Info);
+                d.set_input(id);
+                coordinator.register(d);
+            }
+
+            i += period * repeats;
         }
     }
 
+    /// Find a block of frames at the start of `frames` which repeats right
+    /// away, like with recursion, returning how many frames are in the block
+    /// and how many times in a row it appears.
+    ///
+    /// If nothing repeats enough to be worth collapsing, it's one frame
+    /// appearing once.
+    fn repetition(&self, frames: &[&CallFrame]) -> (usize, usize) {
+        let key = |frame: &CallFrame| {
+            let closure = self.stack[frame.bp()]
+                .as_gc::<Function>()
+                .expect("every frame base pointer is a closure");
+            (&*closure.prototype() as *const Prototype, frame.pc())
+        };
+
+        for period in 1..=Self::MAX_REPEATING_FRAMES.min(frames.len()) {
+            let block: Vec<_> =
+                frames[..period].iter().map(|f| key(f)).collect();
+
+            let repeats = frames
+                .chunks_exact(period)
+                .take_while(|chunk| {
+                    chunk.iter().map(|f| key(f)).eq(block.iter().cloned())
+                })
+                .count();
+
+            if repeats >= Self::MIN_REPEATS {
+                return (period, repeats);
+            }
+        }
+
+        (1, 1)
+    }
+
     fn stack_trace_frame_diagnostic(&self, frame: &CallFrame) -> Diagnostic {
         let mut message = String::from("called by ");
 
@@ -62,3 +129,42 @@ impl VirtualMachine {
         d
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::CallStack;
+
+    fn repetitions(input: &str) -> Vec<(usize, usize)> {
+        let mut vm = VirtualMachine::default();
+        let module = compiler::Module::try_from(input).unwrap();
+        assert!(matches!(vm.load(module), Err(Error::StackOverflow)));
+
+        let frames: Vec<&CallFrame> = vm.call_stack.iter().collect();
+        let mut found = Vec::new();
+        let mut i = 0;
+
+        while i < frames.len() {
+            let (period, repeats) = vm.repetition(&frames[i..]);
+            found.push((period, repeats));
+            i += period * repeats;
+        }
+
+        found
+    }
+
+    #[test]
+    fn recursion() {
+        let found = repetitions("let rec f = (n) => f(n + 1); f(0)");
+        assert_eq!(found, [(1, CallStack::MAX_DEPTH - 1), (1, 1)]);
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        let found = repetitions(
+            "let g = (f, n) => f(f, n); let f = (f, n) => g(f, n + 1); f(f, 0)",
+        );
+
+        assert_eq!(found, [(2, CallStack::MAX_DEPTH / 2 - 1), (1, 1), (1, 1)]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Test calling Kurt closures from Rust.
+
+use compiler::Module;
+use runtime::{Error, FromValue, IntoValue, Limits, Value, VirtualMachine};
+
+/// Load `input`, and return the VM along with the value it left.
+fn load(input: &str) -> (VirtualMachine, Value) {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    let value = *vm.stack_frame().last().unwrap();
+    (vm, value)
+}
+
+fn int(vm: &mut VirtualMachine, i: i64) -> Value {
+    i.into_value(vm).unwrap()
+}
+
+#[test]
+fn call() {
+    let (mut vm, f) = load("(x, y) => x + y");
+    let args = [int(&mut vm, 1), int(&mut vm, 2)];
+
+    let result = vm.call(f, &args).unwrap();
+    assert_eq!(i64::from_value(result), Ok(3));
+}
+
+#[test]
+fn handler_keeps_state() {
+    let (mut vm, handler) = load(
+        "let total = [0];
+        (n) => total[0] = total[0] + n",
+    );
+
+    for n in 1..=3 {
+        let arg = int(&mut vm, n);
+        vm.call(handler, &[arg]).unwrap();
+    }
+
+    let height = vm.stack_frame().len();
+    let arg = int(&mut vm, 0);
+    let result = vm.call(handler, &[arg]).unwrap();
+    assert_eq!(i64::from_value(result), Ok(6));
+
+    // Nothing is left behind on the stack.
+    assert_eq!(vm.stack_frame().len(), height);
+}
+
+#[test]
+fn nested_calls() {
+    let (mut vm, f) = load(
+        "let rec fact = (n) => if n == 0 { 1 } else { n * fact(n - 1) };
+        fact",
+    );
+
+    let arg = int(&mut vm, 10);
+    let result = vm.call(f, &[arg]).unwrap();
+    assert_eq!(i64::from_value(result), Ok(3628800));
+}
+
+#[test]
+fn returns_objects() {
+    let (mut vm, f) = load("(n) => [n, [n]]");
+    let arg = int(&mut vm, 1);
+    let result = vm.call(f, &[arg]).unwrap();
+    assert_eq!(format!("{result:?}"), "[1, [1]]");
+}
+
+#[test]
+fn usable_after_errors() {
+    let (mut vm, f) = load("(l) => l[9]");
+    let height = vm.stack_frame().len();
+
+    assert!(matches!(
+        vm.call(f, &[]),
+        Err(Error::InvalidArgCount {
+            found: 0,
+            expected: 1
+        })
+    ));
+
+    let list = vec![1, 2].into_value(&mut vm).unwrap();
+    assert!(matches!(
+        vm.call(f, &[list]),
+        Err(Error::SubscriptIndexOutOfRange)
+    ));
+
+    assert!(vm.call(Value::UNIT, &[]).is_err());
+    assert_eq!(vm.stack_frame().len(), height);
+
+    let list = (0..10).collect::<Vec<i64>>().into_value(&mut vm).unwrap();
+    let result = vm.call(f, &[list]).unwrap();
+    assert_eq!(i64::from_value(result), Ok(9));
+}
+
+#[test]
+fn out_of_fuel() {
+    let (mut vm, f) =
+        load("let rec f = (n) => if n == 0 { 0 } else { f(n - 1) }; f");
+    vm.set_limits(Limits {
+        fuel: Some(100),
+        ..Limits::default()
+    });
+
+    let arg = int(&mut vm, 1000);
+    assert!(matches!(vm.call(f, &[arg]), Err(Error::OutOfFuel)));
+
+    // A call can't be resumed, it's already been unwound.
+    assert!(matches!(vm.resume(), Err(Error::NothingToResume)));
+
+    vm.add_fuel(100_000);
+    assert_eq!(i64::from_value(vm.call(f, &[arg]).unwrap()), Ok(0));
+}
@@ -0,0 +1,128 @@
+//! Test converting between Rust values and Kurt values.
+
+use std::collections::HashMap;
+
+use compiler::Module;
+use runtime::{CastError, FromValue, GcConfig, IntoValue, VirtualMachine};
+
+/// Convert `value` into a [`Value`] and back.
+fn round_trip<T: IntoValue + FromValue>(value: T) -> T {
+    let mut vm = VirtualMachine::default();
+    let value = value.into_value(&mut vm).unwrap();
+    T::from_value(value).unwrap()
+}
+
+/// The last result of running `input`, converted to a `T`.
+fn eval<T: FromValue>(input: &str) -> Result<T, CastError> {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    T::from_value(*vm.stack_frame().last().unwrap())
+}
+
+#[test]
+fn simple() {
+    assert_eq!(round_trip(()), ());
+    assert!(round_trip(true));
+    assert_eq!(round_trip('🥳'), '🥳');
+    assert_eq!(round_trip(1.5), 1.5);
+    assert_eq!(round_trip(-7i8), -7);
+    assert_eq!(round_trip(1_000_000u64), 1_000_000);
+}
+
+#[test]
+fn strings() {
+    assert_eq!(round_trip(String::from("hi")), "hi");
+    assert_eq!(
+        round_trip(String::from("too long to be inline")),
+        "too long to be inline"
+    );
+}
+
+#[test]
+fn containers() {
+    assert_eq!(round_trip(vec![1, 2, 3]), [1, 2, 3]);
+    assert_eq!(round_trip((1, 'a', true)), (1, 'a', true));
+    assert_eq!(
+        round_trip(vec![(String::from("a long string"), vec![1.5])]),
+        [(String::from("a long string"), vec![1.5])]
+    );
+
+    let map: HashMap<String, i64> =
+        [("one".into(), 1), ("two".into(), 2)].into_iter().collect();
+    assert_eq!(round_trip(map.clone()), map);
+}
+
+#[test]
+fn from_kurt() {
+    assert_eq!(eval::<Vec<i32>>("[1, 2, 3]").unwrap(), [1, 2, 3]);
+    assert_eq!(eval::<(i32, bool)>("(1, true)").unwrap(), (1, true));
+    assert_eq!(eval::<(char,)>(":ok('a')").unwrap(), ('a',));
+}
+
+#[test]
+fn wrong_type() {
+    let error = eval::<bool>("1").unwrap_err();
+    assert_eq!(
+        error,
+        CastError {
+            from: "Int",
+            to: "Bool"
+        }
+    );
+
+    let error = eval::<Vec<i32>>("[1, 'a']").unwrap_err();
+    assert_eq!(
+        error,
+        CastError {
+            from: "Char",
+            to: "Int"
+        }
+    );
+
+    let error = eval::<String>(":ok").unwrap_err();
+    assert_eq!(
+        error,
+        CastError {
+            from: "Keyword",
+            to: "String"
+        }
+    );
+}
+
+#[test]
+fn does_not_fit() {
+    let error = eval::<u8>("-1").unwrap_err();
+    assert_eq!(
+        error,
+        CastError {
+            from: "Int",
+            to: "u8"
+        }
+    );
+
+    let error = eval::<(i32, i32)>("(1, 2, 3)").unwrap_err();
+    assert_eq!(
+        error,
+        CastError {
+            from: "Tuple",
+            to: "(i32, i32)"
+        }
+    );
+
+    let mut vm = VirtualMachine::default();
+    assert!(i64::MAX.into_value(&mut vm).is_err());
+}
+
+#[test]
+fn survives_collection() {
+    let mut vm = VirtualMachine::with_gc_config(GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    });
+
+    let strings: Vec<String> =
+        (0..100).map(|i| format!("string number {i}")).collect();
+
+    let value = strings.clone().into_value(&mut vm).unwrap();
+    assert_eq!(Vec::<String>::from_value(value).unwrap(), strings);
+}
@@ -0,0 +1,242 @@
+//! Test pausing programs with a debugger attached.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use compiler::Module;
+use runtime::{memory::Root, Debugger, Pause, Resume, VirtualMachine};
+
+/// What the debugger saw when it paused.
+#[derive(Debug, PartialEq)]
+struct Stop {
+    pause: Pause,
+    line: u32,
+    depth: usize,
+    locals: Vec<String>,
+}
+
+/// A debugger which follows a script of resumes, recording where it stopped.
+struct Scripted {
+    script: VecDeque<Resume>,
+    stops: Rc<RefCell<Vec<Stop>>>,
+}
+
+impl Debugger for Scripted {
+    fn paused(&mut self, vm: &mut VirtualMachine, pause: Pause) -> Resume {
+        let locals = vm
+            .frame_locals(0)
+            .into_iter()
+            .map(|(name, value)| format!("{name}={value:?}"))
+            .collect();
+
+        self.stops.borrow_mut().push(Stop {
+            pause,
+            line: vm.frame_span(0).unwrap().start().line() + 1,
+            depth: vm.frame_count(),
+            locals,
+        });
+
+        self.script.pop_front().unwrap_or(Resume::Continue)
+    }
+}
+
+const PROGRAM: &str = "\
+let double = (n) => {
+    let twice = n * 2;
+    twice
+};
+let a = 1;
+let b = double(a);
+b + 1";
+
+fn debug(
+    breakpoints: &[u32],
+    start: Resume,
+    script: &[Resume],
+) -> (Vec<Stop>, String) {
+    let stops = Rc::new(RefCell::new(Vec::new()));
+
+    let mut vm = VirtualMachine::default();
+
+    for line in breakpoints {
+        vm.set_breakpoint(*line);
+    }
+
+    vm.set_debugger(
+        Scripted {
+            script: script.iter().cloned().collect(),
+            stops: stops.clone(),
+        },
+        start,
+    );
+
+    vm.load(Module::try_from(PROGRAM).unwrap()).unwrap();
+
+    let stops = stops.take();
+    (stops, vm.last_result())
+}
+
+fn lines(stops: &[Stop]) -> Vec<u32> {
+    stops.iter().map(|s| s.line).collect()
+}
+
+#[test]
+fn no_breakpoints() {
+    let (stops, result) = debug(&[], Resume::Continue, &[]);
+    assert!(stops.is_empty());
+    assert_eq!(result, "3");
+}
+
+#[test]
+fn breakpoint() {
+    let (stops, result) = debug(&[2], Resume::Continue, &[]);
+
+    assert_eq!(
+        stops,
+        vec![Stop {
+            pause: Pause::Breakpoint(2),
+            line: 2,
+            depth: 2,
+            locals: vec!["n=1".into()],
+        }]
+    );
+    assert_eq!(result, "3");
+}
+
+#[test]
+fn step_into() {
+    let script = [Resume::StepInto; 8];
+    let (stops, _) = debug(&[], Resume::StepInto, &script);
+    assert_eq!(lines(&stops), [5, 6, 2, 3, 7]);
+}
+
+#[test]
+fn step_over() {
+    let script = [Resume::StepOver; 8];
+    let (stops, _) = debug(&[], Resume::StepInto, &script);
+    assert_eq!(lines(&stops), [5, 6, 7]);
+}
+
+#[test]
+fn step_out() {
+    let (stops, _) = debug(&[3], Resume::Continue, &[Resume::StepOut]);
+
+    assert_eq!(lines(&stops), [3, 7]);
+    assert_eq!(stops[0].locals, ["twice=2", "n=1"]);
+    assert_eq!(stops[1].depth, 1);
+}
+
+/// Evaluates an expression in each frame, when it reaches a breakpoint.
+struct Evaluator {
+    input: &'static str,
+    results: Rc<RefCell<Vec<String>>>,
+}
+
+impl Debugger for Evaluator {
+    fn paused(&mut self, vm: &mut VirtualMachine, _: Pause) -> Resume {
+        for frame in 0..vm.frame_count() {
+            let result = match vm.evaluate(frame, self.input) {
+                Ok(value) => format!("{value:?}"),
+                Err(e) => format!("error: {e}"),
+            };
+
+            self.results.borrow_mut().push(result);
+        }
+
+        Resume::Continue
+    }
+}
+
+fn evaluate(input: &'static str) -> Vec<String> {
+    let results = Rc::new(RefCell::new(Vec::new()));
+
+    let mut vm = VirtualMachine::default();
+    vm.set_breakpoint(3);
+    vm.set_debugger(
+        Evaluator {
+            input,
+            results: results.clone(),
+        },
+        Resume::Continue,
+    );
+
+    vm.load(Module::try_from(PROGRAM).unwrap()).unwrap();
+    assert_eq!(vm.last_result(), "3", "evaluating shouldn't change things");
+
+    results.take()
+}
+
+#[test]
+fn evaluate_locals() {
+    let results = evaluate("[n, twice]");
+    assert_eq!(results[0], "[1, 2]");
+    assert!(results[1].starts_with("error"), "{:?}", results);
+}
+
+#[test]
+fn evaluate_caller() {
+    // The caller can see `a`, but not `twice`.
+    let results = evaluate("a + 10");
+    assert_eq!(results[1], "11");
+}
+
+#[test]
+fn evaluate_closure() {
+    assert_eq!(evaluate("((x) => x + n)(5)")[0], "6");
+}
+
+#[test]
+fn evaluate_errors() {
+    let results = evaluate("[][0]");
+    assert_eq!(results[0], "error: subscript index out of range");
+}
+
+#[test]
+fn evaluate_return() {
+    let results = evaluate("return 1");
+    assert!(results[0].starts_with("error"), "{:?}", results);
+
+    // But it's fine inside a function.
+    assert_eq!(evaluate("(() => { return n; })()")[0], "1");
+}
+
+/// Evaluates a watch expression a few times, and counts the loaded modules.
+struct Watcher {
+    modules: Rc<RefCell<Vec<usize>>>,
+}
+
+impl Debugger for Watcher {
+    fn paused(&mut self, vm: &mut VirtualMachine, _: Pause) -> Resume {
+        for _ in 0..3 {
+            vm.evaluate(0, "n").unwrap();
+        }
+
+        let modules = vm
+            .heap_snapshot()
+            .roots
+            .iter()
+            .filter(|root| matches!(root, Root::Module { .. }))
+            .count();
+
+        self.modules.borrow_mut().push(modules);
+
+        Resume::Continue
+    }
+}
+
+#[test]
+fn evaluate_unloads_module() {
+    let modules = Rc::new(RefCell::new(Vec::new()));
+
+    let mut vm = VirtualMachine::default();
+    vm.set_breakpoint(3);
+    vm.set_debugger(
+        Watcher {
+            modules: modules.clone(),
+        },
+        Resume::Continue,
+    );
+
+    vm.load(Module::try_from(PROGRAM).unwrap()).unwrap();
+
+    assert_eq!(modules.take(), vec![1]);
+}
@@ -39,7 +39,18 @@ mod literals {
     test_eval! { literal_number, "99", "99" }
     test_eval! { literal_float, "1.5", "1.5" }
     test_eval! { literal_string, r#" "Hello, world!" "#, r#""Hello, world!""# }
+    test_eval! { literal_short_string, r#" "hi" "#, r#""hi""# }
+    test_eval! { short_string_eq, r#" "hi" == "hi" "#, "true" }
+    test_eval! { short_string_ne, r#" "hi" == "ho" "#, "false" }
+    test_eval! { long_string_eq, r#" "Hello, world!" == "Hello, world!" "#, "true" }
+    test_eval! { mixed_string_eq, r#" "abcd" == "abcdefgh" "#, "false" }
+    test_eval! { mixed_string_eq_flipped, r#" "abcdefgh" == "abcd" "#, "false" }
+    test_eval! { mixed_string_lt, r#" "abcd" < "abcdefgh" "#, "true" }
+    test_eval! { mixed_string_gt, r#" "abcdefgh" > "abcd" "#, "true" }
+    test_eval! { mixed_string_heap_first, r#" "abcdefgh" < "abd" "#, "true" }
     test_eval! { literal_keyword, " :hello ", ":hello" }
+    test_eval! { keyword_eq, ":hello == :hello", "true" }
+    test_eval! { keyword_ne, ":hello == :goodbye", "false" }
 }
 
 mod statement_sequences {
@@ -86,6 +97,7 @@ mod tuple {
     test_eval! { simple, "(1, 2, 3)", "(1, 2, 3)" }
     test_eval! { stack, "let x = 0; (1,2,3); x", "0" }
     test_eval! { tag_stack, "let x = 0; :foo(1,2,3); x", "0" }
+    test_eval! { tag_eq, ":ok(1) == :ok(1)", "true" }
 }
 
 mod conditionals {
@@ -192,6 +204,8 @@ mod looping {
     test_eval! { break_value, "loop { break 7 }", "7" }
     test_eval! { break_value_semicolon, "loop { break 7; }", "7" }
     test_eval! { break_while_value, "while true { break 7; }", "7" }
+    test_eval! { break_closes_locals, "let z = loop { let y = 1; break 7; }; z", "7" }
+    test_eval! { continue_closes_locals, "let x = 0; while x < 3 { let y = 1; x = x + y; continue; }; x", "3" }
     test_eval! { continue_expr, r#"
         let x = 1; 
         let y = :stack_guard;
@@ -206,6 +220,12 @@ mod looping {
             } 
         }
     "#, "3" }
+    test_eval! { break_closes_temporaries, "loop { [1, break 2] }", "2" }
+    test_eval! {
+        continue_closes_temporaries,
+        "let x = 0; while x < 3 { x = x + 1; [x, if x == 2 { continue; } else { 0 }] }; x",
+        "3"
+    }
 }
 
 mod assignment {
@@ -220,3 +240,53 @@ mod assignment {
 mod imports {
     test_eval_panic! { import_missing, "import missing;", "()" }
 }
+
+mod assembled {
+    use compiler::Module;
+    use runtime::{CastError, Error, VirtualMachine};
+
+    fn run(ops: &str) -> runtime::Result<()> {
+        let listing = format!(
+            "module {{
+            function 000:
+             parameters 0
+             span 000:000-000:000
+             {ops}
+            }}"
+        );
+
+        let module = Module::assemble(&listing).unwrap();
+        VirtualMachine::default().load(module)
+    }
+
+    #[test]
+    fn tuple_tag_must_be_keyword() {
+        let result =
+            run("000 | U48 1\n001 | U48 2\n002 | Tuple 1 true\n003 | Halt");
+        assert!(
+            matches!(result, Err(Error::Cast(CastError { to: "Keyword", .. }))),
+            "exited with {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn empty_tuple_tag_must_be_keyword() {
+        let result = run("000 | U48 1\n001 | Tuple 0 true\n002 | Halt");
+        assert!(
+            matches!(result, Err(Error::Cast(CastError { to: "Keyword", .. }))),
+            "exited with {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn return_from_main() {
+        let result = run("000 | Unit\n001 | Return");
+        assert!(
+            matches!(result, Err(Error::InvalidModule(_))),
+            "exited with {:?}",
+            result
+        );
+    }
+}
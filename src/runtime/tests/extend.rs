@@ -0,0 +1,98 @@
+//! Test running input a piece at a time, the way the REPL does.
+
+use compiler::ModuleBuilder;
+use runtime::{Error, VirtualMachine};
+
+/// Run each input in turn on the same VM, returning the last result.
+fn extend(inputs: &[&str]) -> String {
+    let mut builder = ModuleBuilder::default();
+    let mut vm = VirtualMachine::default();
+
+    for input in inputs {
+        builder.push_input(input).unwrap();
+        let exit = vm.extend(builder.build());
+        assert!(exit.is_ok(), "{input} exited with {:?}", exit);
+    }
+
+    vm.last_result()
+}
+
+#[test]
+fn single() {
+    assert_eq!(extend(&["1 + 2"]), "3");
+}
+
+#[test]
+fn bindings_persist() {
+    assert_eq!(extend(&["let x = 1", "let y = x + 1;", "y * 10"]), "20");
+}
+
+#[test]
+fn results_are_discarded() {
+    assert_eq!(extend(&["1", "2", "let a = 3", "4", "a"]), "3");
+}
+
+#[test]
+fn captures_persist() {
+    let inputs = [
+        "let x = 0",
+        "let inc = () => x = x + 1",
+        "inc(); inc()",
+        "x",
+    ];
+    assert_eq!(extend(&inputs), "2");
+}
+
+#[test]
+fn functions_persist() {
+    let f = "let rec f = (n) => if n == 0 { 1 } else { n * f(n - 1) }";
+    assert_eq!(extend(&[f, "f(5)"]), "120");
+}
+
+#[test]
+fn rewind_after_error() {
+    let mut builder = ModuleBuilder::default();
+    let mut vm = VirtualMachine::default();
+
+    builder.push_input("let x = [1]").unwrap();
+    vm.extend(builder.build()).unwrap();
+
+    let backup = builder.clone();
+    builder
+        .push_input("let y = () => x; let z = 2; x[9]")
+        .unwrap();
+    assert!(vm.extend(builder.build()).is_err());
+    vm.rewind();
+    builder = backup;
+
+    builder.push_input("x[0] + 1").unwrap();
+    vm.extend(builder.build()).unwrap();
+    assert_eq!(vm.last_result(), "2");
+}
+
+#[test]
+fn rewind_after_first_error() {
+    let mut builder = ModuleBuilder::default();
+    let mut vm = VirtualMachine::default();
+
+    let backup = builder.clone();
+    builder.push_input("let f = () => [][1]; f()").unwrap();
+    assert!(vm.extend(builder.build()).is_err());
+    vm.rewind();
+    builder = backup;
+
+    builder.push_input("7").unwrap();
+    vm.extend(builder.build()).unwrap();
+    assert_eq!(vm.last_result(), "7");
+}
+
+#[test]
+fn unrelated_module() {
+    let mut vm = VirtualMachine::default();
+    vm.extend(compiler::Module::try_from("let a = 1").unwrap())
+        .unwrap();
+
+    let other = compiler::Module::try_from("let b = 2; b").unwrap();
+    let exit = vm.extend(other);
+    assert!(matches!(exit, Err(Error::InvalidExtension)), "{:?}", exit);
+}
@@ -0,0 +1,202 @@
+//! Test passing host values into Kurt code.
+
+use std::{any::Any, cell::Cell, rc::Rc};
+
+use compiler::Module;
+use runtime::{
+    classes::{Foreign, HostObject},
+    memory::{ClassId, Local, Trace, WorkList},
+    Error, FromValue, GcConfig, IntoValue, Result, Value, VirtualMachine,
+};
+
+/// A host value which counts how many times it's been dropped.
+struct Counter {
+    id: i64,
+    dropped: Rc<Cell<usize>>,
+}
+
+impl HostObject for Counter {
+    fn type_name(&self) -> &'static str {
+        "Counter"
+    }
+
+    fn index(&self, _: Value, vm: &mut VirtualMachine) -> Result<Value> {
+        self.id.into_value(vm)
+    }
+
+    fn call(&self, args: &[Value], vm: &mut VirtualMachine) -> Result<Value> {
+        let mut total = self.id;
+        for arg in args {
+            total += i64::from_value(*arg)?;
+        }
+        total.into_value(vm)
+    }
+
+    fn eq(&self, other: &dyn Any) -> bool {
+        other
+            .downcast_ref::<Counter>()
+            .is_some_and(|other| other.id == self.id)
+    }
+}
+
+impl Drop for Counter {
+    fn drop(&mut self) {
+        self.dropped.set(self.dropped.get() + 1);
+    }
+}
+
+/// A host value which holds on to a Kurt value.
+struct Holder(Value);
+
+impl HostObject for Holder {
+    fn type_name(&self) -> &'static str {
+        "Holder"
+    }
+
+    fn enqueue_gc_references(&self, worklist: &mut WorkList) {
+        self.0.enqueue_gc_references(worklist);
+    }
+}
+
+fn vm() -> VirtualMachine {
+    VirtualMachine::with_gc_config(GcConfig {
+        nursery_capacity: 64,
+        ..GcConfig::default()
+    })
+}
+
+fn counter(vm: &mut VirtualMachine, id: i64) -> (Value, Rc<Cell<usize>>) {
+    let dropped = Rc::new(Cell::new(0));
+    let foreign = vm
+        .make_foreign(Counter {
+            id,
+            dropped: dropped.clone(),
+        })
+        .unwrap();
+
+    (Value::gc(foreign), dropped)
+}
+
+/// Load `input`, which should leave a closure, and call it with `args`.
+fn call(vm: &mut VirtualMachine, input: &str, args: &[Local]) -> Result<Value> {
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    let f = *vm.stack_frame().last().unwrap();
+    let args: Vec<Value> = args.iter().map(Local::get).collect();
+    vm.call(f, &args)
+}
+
+#[test]
+fn operations() {
+    let mut vm = vm();
+    let scope = vm.handle_scope();
+    let (c, _) = counter(&mut vm, 10);
+    let c = scope.local(c);
+
+    let result = call(&mut vm, "(c) => c(1, 2) + c[0]", &[c]).unwrap();
+    let c = c.get();
+    assert_eq!(i64::from_value(result), Ok(23));
+
+    assert_eq!(format!("{c:?}"), "<Counter>");
+    assert!(c.as_gc::<Foreign>().unwrap().is::<Counter>());
+    assert_eq!(
+        c.as_gc::<Foreign>()
+            .unwrap()
+            .downcast_ref::<Counter>()
+            .unwrap()
+            .id,
+        10
+    );
+}
+
+#[test]
+fn call_from_rust() {
+    let mut vm = vm();
+    let (c, _) = counter(&mut vm, 1);
+    let arg = 2.into_value(&mut vm).unwrap();
+
+    let result = vm.call(c, &[arg]).unwrap();
+    assert_eq!(i64::from_value(result), Ok(3));
+}
+
+#[test]
+fn unsupported_operations() {
+    let mut vm = vm();
+    let scope = vm.handle_scope();
+    let (c, _) = counter(&mut vm, 1);
+    let c = scope.local(c);
+
+    let error = call(&mut vm, "(c) => c + 1", &[c]).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::OperationNotSupported {
+            type_name: "Counter",
+            ..
+        }
+    ));
+
+    let holder = vm.make_foreign(Holder(Value::UNIT)).unwrap();
+    let holder = scope.local(Value::gc(holder));
+    let error = call(&mut vm, "(h) => h[0]", &[holder]).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::OperationNotSupported {
+            type_name: "Holder",
+            op_name: "index",
+        }
+    ));
+}
+
+#[test]
+fn equality() {
+    let mut vm = vm();
+    let scope = vm.handle_scope();
+    let (a, _) = counter(&mut vm, 1);
+    let a = scope.local(a);
+    let (b, _) = counter(&mut vm, 1);
+    let b = scope.local(b);
+    let (c, _) = counter(&mut vm, 2);
+    let c = scope.local(c);
+
+    let eq = "(a, b) => a == b";
+    let result = call(&mut vm, eq, &[a, b]).unwrap();
+    assert_eq!(bool::from_value(result), Ok(true));
+
+    let result = call(&mut vm, eq, &[a, c]).unwrap();
+    assert_eq!(bool::from_value(result), Ok(false));
+}
+
+#[test]
+fn dropped_when_collected() {
+    let mut vm = vm();
+    let (c, dropped) = counter(&mut vm, 1);
+    let global = vm.global(c);
+
+    vm.force_collect_garbage();
+    assert_eq!(dropped.get(), 0);
+
+    drop(global);
+    vm.force_collect_garbage();
+    assert_eq!(dropped.get(), 1);
+}
+
+#[test]
+fn traced() {
+    let mut vm = vm();
+    let scope = vm.handle_scope();
+    let list = vec![1, 2, 3].into_value(&mut vm).unwrap();
+    let list = scope.local(list);
+    let holder = vm.make_foreign(Holder(list.get())).unwrap();
+    let global = vm.global(Value::gc(holder));
+
+    // Only the host value is holding on to the list now.
+    list.set(Value::UNIT);
+    vm.force_collect_garbage();
+
+    let stats = vm.gc_stats();
+    assert_eq!(stats.live_objects.get(&ClassId::List), Some(&1));
+    assert_eq!(stats.live_objects.get(&ClassId::Foreign), Some(&1));
+
+    let foreign = global.get().as_gc::<Foreign>().unwrap();
+    let Holder(list) = foreign.downcast_ref::<Holder>().unwrap();
+    assert_eq!(Vec::<i64>::from_value(*list), Ok(vec![1, 2, 3]));
+}
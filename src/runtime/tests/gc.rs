@@ -0,0 +1,211 @@
+//! Test that values stay alive across collections.
+//!
+//! These use a tiny nursery, so that objects are promoted to the old
+//! generation and then changed to point at young objects, which is where a
+//! missing write barrier would free something still in use. They're also run
+//! in stress mode, so that objects are changed while a collection is part way
+//! through marking.
+
+use compiler::Module;
+use runtime::{memory::ClassId, Error, GcConfig, Limits, VirtualMachine};
+
+fn run(nursery: usize, input: &str) -> String {
+    let mut vm = VirtualMachine::with_gc_config(GcConfig {
+        nursery_capacity: nursery,
+        ..GcConfig::default()
+    });
+
+    let exit = vm.load(Module::try_from(input).unwrap());
+    assert!(exit.is_ok(), "exited with {:?}", exit);
+
+    vm.force_collect_nursery();
+    vm.force_collect_garbage();
+
+    vm.last_result()
+}
+
+const LIST_SET_INDEX: &str = "\
+let l = [[0], [0], [0]];
+let rec fill = (i) => if i < 3000 { l[i % 3] = [i, [i]]; fill(i + 1) } else { l };
+fill(0)";
+
+const CAPTURE_CLOSE: &str = "\
+let make = (x) => {
+    let set = (v) => x = [v];
+    let get = () => x;
+    [set, get]
+};
+let p = make(0);
+let rec go = (i) => if i < 3000 { p[0](i); [i, i, i]; go(i + 1) } else { p[1]() };
+go(0)";
+
+const CAPTURES: &str = "\
+let rec build = (i, f) => if i == 0 { f } else { build(i - 1, () => [[i], f]) };
+build(500, () => 0)()[0]";
+
+const NEST: &str = "\
+let rec nest = (n, l) => if n == 0 { l } else { nest(n - 1, [n, l]) };
+let rec length = (l) => if l == [] { 0 } else { 1 + length(l[1]) };
+length(nest(3000, []))";
+
+#[test]
+fn list_set_index() {
+    for nursery in [0, 64, 1024, 64 * 1024] {
+        assert_eq!(
+            run(nursery, LIST_SET_INDEX),
+            "[[2997, [2997]], [2998, [2998]], [2999, [2999]]]"
+        );
+    }
+}
+
+#[test]
+fn capture_close() {
+    for nursery in [0, 64, 1024, 64 * 1024] {
+        assert_eq!(run(nursery, CAPTURE_CLOSE), "[2999]");
+    }
+}
+
+#[test]
+fn captures() {
+    for nursery in [0, 64, 1024, 64 * 1024] {
+        assert_eq!(run(nursery, CAPTURES), "[1]");
+    }
+}
+
+fn stress(input: &str) -> String {
+    let mut vm = VirtualMachine::with_gc_config(GcConfig {
+        stress: true,
+        ..GcConfig::default()
+    });
+
+    let exit = vm.load(Module::try_from(input).unwrap());
+    assert!(exit.is_ok(), "exited with {:?}", exit);
+
+    vm.force_collect_garbage();
+    vm.last_result()
+}
+
+#[test]
+fn list_set_index_stress() {
+    assert_eq!(
+        stress(LIST_SET_INDEX),
+        "[[2997, [2997]], [2998, [2998]], [2999, [2999]]]"
+    );
+}
+
+#[test]
+fn capture_close_stress() {
+    assert_eq!(stress(CAPTURE_CLOSE), "[2999]");
+}
+
+#[test]
+fn captures_stress() {
+    assert_eq!(stress(CAPTURES), "[1]");
+}
+
+#[test]
+fn marking_is_incremental() {
+    let limits = Limits {
+        fuel: Some(0),
+        ..Limits::default()
+    };
+
+    let mut vm = VirtualMachine::with_limits(limits);
+    let mut result = vm.load(Module::try_from(NEST).unwrap());
+    let mut paused_while_collecting = 0;
+
+    // Running a few ops at a time, the program should be caught part way
+    // through a collection at least once.
+    while let Err(Error::OutOfFuel) = result {
+        if vm.is_collecting() {
+            paused_while_collecting += 1;
+        }
+
+        vm.add_fuel(10);
+        result = vm.resume();
+    }
+
+    assert!(result.is_ok(), "exited with {:?}", result);
+    assert!(paused_while_collecting > 0);
+    assert_eq!(vm.last_result(), "3000");
+}
+
+#[test]
+fn collect_between_loads() {
+    let mut vm = VirtualMachine::with_gc_config(GcConfig {
+        nursery_capacity: 64,
+        ..GcConfig::default()
+    });
+
+    vm.load(Module::try_from("let a = [1, [2]]; a").unwrap())
+        .unwrap();
+    vm.force_collect_garbage();
+
+    vm.load(Module::try_from("[3, [4], \"five\"]").unwrap())
+        .unwrap();
+    vm.force_collect_nursery();
+
+    assert_eq!(vm.last_result(), "[3, [4], \"five\"]");
+}
+
+#[test]
+fn stats() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(NEST).unwrap()).unwrap();
+    vm.force_collect_garbage();
+
+    let stats = vm.gc_stats();
+    assert!(stats.major_collections > 0);
+    assert!(stats.minor_collections > 0);
+    assert!(stats.pauses >= stats.major_collections);
+    assert!(stats.bytes_freed > 0);
+    assert_eq!(
+        stats.bytes_allocated - stats.bytes_freed,
+        stats.bytes_in_use
+    );
+
+    // The lists are garbage once the length is found.
+    assert_eq!(stats.live_objects.get(&ClassId::List), None);
+    assert_eq!(stats.live_objects.get(&ClassId::Module), Some(&1));
+}
+
+#[test]
+fn stats_count_elements() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("let a = 1").unwrap()).unwrap();
+    vm.force_collect_garbage();
+    let before = vm.gc_stats().bytes_in_use;
+
+    let input = format!("let b = [{}]", vec!["0"; 1000].join(", "));
+    vm.load(Module::try_from(input.as_str()).unwrap()).unwrap();
+    vm.force_collect_garbage();
+    let after = vm.gc_stats().bytes_in_use;
+
+    assert!(after - before >= 1000 * std::mem::size_of::<u64>());
+}
+
+#[test]
+fn keywords_are_interned() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(":ok").unwrap()).unwrap();
+    vm.load(Module::try_from(":ok(:ok)").unwrap()).unwrap();
+    vm.force_collect_garbage();
+
+    let stats = vm.gc_stats();
+    assert_eq!(stats.live_objects.get(&ClassId::Keyword), Some(&1));
+    assert_eq!(vm.last_result(), ":ok(:ok,)");
+}
+
+#[test]
+fn unused_keywords_are_collected() {
+    let mut vm = VirtualMachine::default();
+    vm.keyword("unused").unwrap();
+    vm.force_collect_garbage();
+
+    let stats = vm.gc_stats();
+    assert_eq!(stats.live_objects.get(&ClassId::Keyword), None);
+
+    // It's not left in the table after it's freed.
+    let keyword = vm.keyword("unused").unwrap();
+    assert_eq!(keyword.as_str(), "unused");
+}
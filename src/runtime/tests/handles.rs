@@ -0,0 +1,129 @@
+//! Test keeping values alive from Rust with handles.
+
+use compiler::Module;
+use runtime::{
+    memory::ClassId, FromValue, GcConfig, IntoValue, Value, VirtualMachine,
+};
+
+/// A VM which collects as often as it can.
+fn vm() -> VirtualMachine {
+    VirtualMachine::with_gc_config(GcConfig {
+        stress: true,
+        nursery_capacity: 64,
+        ..GcConfig::default()
+    })
+}
+
+fn list(vm: &mut VirtualMachine, n: i64) -> Value {
+    vec![vec![n]].into_value(vm).unwrap()
+}
+
+fn lists(vm: &VirtualMachine) -> usize {
+    let stats = vm.gc_stats();
+    stats.live_objects.get(&ClassId::List).copied().unwrap_or(0)
+}
+
+#[test]
+fn global() {
+    let mut vm = vm();
+    let value = list(&mut vm, 1);
+    let global = vm.global(value);
+
+    // Make lots of garbage.
+    for i in 0..100 {
+        list(&mut vm, i);
+    }
+    vm.force_collect_garbage();
+
+    assert_eq!(Vec::<Vec<i64>>::from_value(global.get()), Ok(vec![vec![1]]));
+    assert_eq!(lists(&vm), 2);
+
+    drop(global);
+    vm.force_collect_garbage();
+    assert_eq!(lists(&vm), 0);
+}
+
+#[test]
+fn global_clone_and_set() {
+    let mut vm = vm();
+    let value = list(&mut vm, 1);
+    let first = vm.global(value);
+    let second = first.clone();
+    drop(first);
+
+    vm.force_collect_garbage();
+    assert_eq!(lists(&vm), 2);
+
+    let value = list(&mut vm, 2);
+    second.set(value);
+    vm.force_collect_garbage();
+
+    assert_eq!(Vec::<Vec<i64>>::from_value(second.get()), Ok(vec![vec![2]]));
+    assert_eq!(lists(&vm), 2);
+}
+
+#[test]
+fn locals() {
+    let mut vm = vm();
+
+    {
+        let scope = vm.handle_scope();
+        let value = list(&mut vm, 1);
+        let a = scope.local(value);
+
+        {
+            let inner = vm.handle_scope();
+            let value = list(&mut vm, 2);
+            inner.local(value);
+            vm.force_collect_garbage();
+            assert_eq!(lists(&vm), 4);
+        }
+
+        let value = list(&mut vm, 3);
+        let b = scope.local(value);
+        vm.force_collect_garbage();
+        assert_eq!(lists(&vm), 4);
+
+        assert_eq!(Vec::<Vec<i64>>::from_value(a.get()), Ok(vec![vec![1]]));
+        assert_eq!(Vec::<Vec<i64>>::from_value(b.get()), Ok(vec![vec![3]]));
+    }
+
+    vm.force_collect_garbage();
+    assert_eq!(lists(&vm), 0);
+}
+
+#[test]
+fn callback() {
+    let mut vm = vm();
+    vm.load(Module::try_from("(n) => [n]").unwrap()).unwrap();
+    let handler = vm.global(*vm.stack_frame().last().unwrap());
+
+    // Running more code allocates, so the handler has to be kept alive.
+    vm.load(Module::try_from("[1, 2, 3]").unwrap()).unwrap();
+
+    let arg = 7.into_value(&mut vm).unwrap();
+    let result = vm.call(handler.get(), &[arg]).unwrap();
+    assert_eq!(Vec::<i64>::from_value(result), Ok(vec![7]));
+}
+
+#[test]
+#[should_panic(expected = "handle used after its VM was dropped")]
+fn global_outlives_vm() {
+    let mut vm = vm();
+    let value = list(&mut vm, 1);
+    let global = vm.global(value);
+    drop(vm);
+
+    global.get();
+}
+
+#[test]
+fn global_dropped_after_vm() {
+    let mut vm = vm();
+    let value = list(&mut vm, 1);
+    let global = vm.global(value);
+    drop(vm);
+
+    // Letting go of it is still fine.
+    drop(global);
+}
@@ -0,0 +1,102 @@
+//! Test taking, saving and analyzing heap snapshots.
+
+use compiler::Module;
+use runtime::{
+    memory::{ClassId, Root},
+    HeapSnapshot, VirtualMachine,
+};
+
+fn snapshot(input: &str) -> HeapSnapshot {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    vm.force_collect_garbage();
+    vm.heap_snapshot()
+}
+
+#[test]
+fn objects_and_roots() {
+    let snapshot = snapshot("let keep = [[1], [2]]; keep");
+
+    let lists = snapshot
+        .objects
+        .iter()
+        .filter(|o| o.class == ClassId::List)
+        .count();
+    assert_eq!(lists, 3);
+
+    assert!(snapshot
+        .roots
+        .iter()
+        .any(|root| matches!(root, Root::Module { index: 0, .. })));
+
+    // After collecting, everything is reachable from something.
+    assert!(snapshot.objects.iter().all(|o| o.root.is_some()));
+}
+
+#[test]
+fn round_trip() {
+    let snapshot = snapshot("let keep = [[1], \"two\", (x) => x]; keep");
+
+    let mut json = Vec::new();
+    snapshot.write_json(&mut json).unwrap();
+
+    let read = HeapSnapshot::read_json(json.as_slice()).unwrap();
+    assert_eq!(read, snapshot);
+}
+
+#[test]
+fn invalid() {
+    let bad_reference = r#"{
+        "version": 2,
+        "objects": [
+            { "id": 0, "class": "List", "size": 8, "references": [1], "root": null }
+        ],
+        "roots": []
+    }"#;
+    assert!(HeapSnapshot::read_json(bad_reference.as_bytes()).is_err());
+
+    let bad_version = r#"{ "version": 0, "objects": [], "roots": [] }"#;
+    assert!(HeapSnapshot::read_json(bad_version.as_bytes()).is_err());
+}
+
+#[test]
+fn retained_size() {
+    let snapshot = snapshot(
+        "let rec nest = (n, l) => if n == 0 { l } else { nest(n - 1, [l]) };
+let big = nest(100, []);
+big",
+    );
+
+    let analysis = snapshot.analyze();
+    let largest = analysis.largest()[0];
+
+    // Two stack slots point at the outermost list, so the roots dominate it,
+    // and it dominates all the other lists.
+    assert_eq!(largest.class, ClassId::List);
+    assert_eq!(largest.dominator, None);
+
+    let lists: usize = snapshot
+        .objects
+        .iter()
+        .filter(|o| o.class == ClassId::List)
+        .map(|o| o.size)
+        .sum();
+    assert_eq!(largest.retained_size, lists);
+}
+
+#[test]
+fn handles_are_roots() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("[[1]]").unwrap()).unwrap();
+    let global = vm.global(*vm.stack_frame().last().unwrap());
+
+    let snapshot = vm.heap_snapshot();
+    let root = snapshot
+        .roots
+        .iter()
+        .find(|root| matches!(root, Root::Global { .. }))
+        .unwrap();
+
+    assert_eq!(snapshot.objects[root.object()].class, ClassId::List);
+    drop(global);
+}
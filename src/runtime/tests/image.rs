@@ -0,0 +1,264 @@
+//! Test saving a VM to an image and restoring it.
+
+use compiler::{Module, ModuleBuilder};
+use runtime::{
+    classes::HostObject, Error, GcConfig, Image, Limits, Value, VirtualMachine,
+};
+
+/// Save `vm` as JSON and read it back into a new VM.
+fn round_trip(vm: &VirtualMachine) -> VirtualMachine {
+    let mut json = Vec::new();
+    vm.image().unwrap().write_json(&mut json).unwrap();
+
+    let image = Image::read_json(json.as_slice()).unwrap();
+    let mut restored = VirtualMachine::with_gc_config(GcConfig {
+        nursery_capacity: 64,
+        ..GcConfig::default()
+    });
+    restored.restore(&image).unwrap();
+    restored
+}
+
+/// The JSON for an image of `vm`, to mess with.
+fn json(vm: &VirtualMachine) -> serde_json::Value {
+    serde_json::to_value(vm.image().unwrap()).unwrap()
+}
+
+fn read(json: serde_json::Value) -> std::io::Result<Image> {
+    Image::read_json(json.to_string().as_bytes())
+}
+
+#[test]
+fn values() {
+    let input = "[1, -2, 3.5, 'c', \"a much longer string\", \"hi\", :kw, \
+                 (1, 2), true, (), [[]]]";
+
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+
+    let restored = round_trip(&vm);
+    assert_eq!(restored.last_result(), vm.last_result());
+}
+
+#[test]
+fn extend_after_restoring() {
+    let mut builder = ModuleBuilder::default();
+    let mut vm = VirtualMachine::default();
+
+    builder
+        .push_input("let table = [10, 20, 30]; let get = (i) => table[i]")
+        .unwrap();
+    vm.extend(builder.build()).unwrap();
+
+    let mut restored = round_trip(&vm);
+    drop(vm);
+
+    builder.push_input("get(1) + get(2)").unwrap();
+    restored.extend(builder.build()).unwrap();
+    assert_eq!(restored.last_result(), "50");
+}
+
+#[test]
+fn captures() {
+    let input = "{
+        let count = 0;
+        let inc = () => { count = count + 1; count };
+        inc();
+        inc
+    }";
+
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+
+    let mut restored = round_trip(&vm);
+    let inc = *restored.stack_frame().last().unwrap();
+    let result = restored.call(inc, &[]).unwrap();
+    assert_eq!(result.as_int().map(i64::from), Some(2));
+}
+
+#[test]
+fn shared_code() {
+    let module = std::sync::Arc::new(Module::try_from("1").unwrap());
+
+    let mut vm = VirtualMachine::default();
+    vm.load(module.clone()).unwrap();
+    vm.load(module).unwrap();
+
+    let json = json(&vm);
+    assert_eq!(json["code"].as_array().unwrap().len(), 1);
+    assert_eq!(json["modules"].as_array().unwrap().len(), 2);
+
+    let restored = round_trip(&vm);
+    assert_eq!(restored.last_result(), "1");
+}
+
+struct Host;
+
+impl HostObject for Host {
+    fn type_name(&self) -> &'static str {
+        "Host"
+    }
+}
+
+#[test]
+fn host_objects_cant_be_saved() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("(x) => [x]").unwrap()).unwrap();
+
+    let f = *vm.stack_frame().last().unwrap();
+    let host = Value::gc(vm.make_foreign(Host).unwrap());
+    vm.call(f, &[host]).unwrap();
+
+    assert!(vm.image().is_ok(), "the result isn't kept");
+
+    let input = "let held = (); let hold = (x) => held = x; hold";
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+
+    let hold = *vm.stack_frame().last().unwrap();
+    let host = Value::gc(vm.make_foreign(Host).unwrap());
+    vm.call(hold, &[host]).unwrap();
+
+    assert!(matches!(vm.image(), Err(Error::CannotSaveImage(_))));
+}
+
+#[test]
+fn invalid_images() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("let x = [1]; () => x").unwrap())
+        .unwrap();
+
+    assert!(read(json(&vm)).is_ok());
+
+    let mut wrong_version = json(&vm);
+    wrong_version["version"] = (Image::VERSION + 1).into();
+    assert!(read(wrong_version).is_err());
+
+    let mut unknown_object = json(&vm);
+    unknown_object["stack"][0] = serde_json::json!({ "object": 1000 });
+    assert!(read(unknown_object).is_err());
+
+    let mut bad_frame = json(&vm);
+    bad_frame["frames"][0]["bp"] = 1000.into();
+    assert!(read(bad_frame).is_err());
+
+    let mut too_big = json(&vm);
+    too_big["stack"][0] = serde_json::json!({ "int": i64::MAX });
+    assert!(read(too_big).is_err());
+
+    assert!(read(serde_json::json!({ "version": Image::VERSION })).is_err());
+}
+
+#[test]
+fn invalid_code() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("() => 1").unwrap()).unwrap();
+
+    let mut bad_listing = json(&vm);
+    bad_listing["code"][0] = "not a module".into();
+    let image = read(bad_listing).unwrap();
+    assert!(matches!(
+        VirtualMachine::default().restore(&image),
+        Err(Error::InvalidImage(_))
+    ));
+
+    let mut bad_pc = json(&vm);
+    bad_pc["frames"][0]["pc"] = 1000.into();
+    let image = read(bad_pc).unwrap();
+    assert!(matches!(
+        VirtualMachine::default().restore(&image),
+        Err(Error::InvalidImage(_))
+    ));
+}
+
+#[test]
+#[should_panic]
+fn restore_into_a_used_vm() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("1").unwrap()).unwrap();
+
+    let image = vm.image().unwrap();
+    vm.restore(&image).unwrap();
+}
+
+#[test]
+fn suspended() {
+    let input =
+        "let add = (a, b) => { let sum = a + b; sum }; [add(1, 2), add(3, 4)]";
+
+    let mut vm = VirtualMachine::default();
+    vm.set_limits(Limits {
+        fuel: Some(12),
+        ..Limits::default()
+    });
+    assert!(matches!(
+        vm.load(Module::try_from(input).unwrap()),
+        Err(Error::OutOfFuel)
+    ));
+
+    let mut restored = round_trip(&vm);
+    restored.resume().unwrap();
+    assert_eq!(restored.last_result(), "[3, 7]");
+}
+
+#[test]
+fn stack_doesnt_match_code() {
+    let mut vm = VirtualMachine::default();
+    vm.set_limits(Limits {
+        fuel: Some(9),
+        ..Limits::default()
+    });
+    let input = "let add = (a, b) => a + b; [add(1, 2), add(3, 4)]";
+    let _ = vm.load(Module::try_from(input).unwrap());
+
+    let mut short_stack = json(&vm);
+    let frames = short_stack["frames"].as_array().unwrap();
+    assert_eq!(frames.len(), 2, "stopped inside `add`");
+    let top = frames[1]["bp"].as_u64().unwrap() as usize;
+    short_stack["stack"]
+        .as_array_mut()
+        .unwrap()
+        .truncate(top + 1);
+
+    // Point the frame at the `Add`, which now has nothing to add.
+    let listing = short_stack["code"][0].as_str().unwrap().to_owned();
+    let function = listing.split("function 001").nth(1).unwrap();
+    let add = function
+        .lines()
+        .position(|line| line.ends_with("| Add"))
+        .and_then(|i| function.lines().nth(i))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap();
+    short_stack["frames"][1]["pc"] = add.parse::<u32>().unwrap().into();
+
+    let image = read(short_stack).unwrap();
+    assert!(matches!(
+        VirtualMachine::default().restore(&image),
+        Err(Error::InvalidImage("stack doesn't match the frame's code"))
+    ));
+
+    let mut bad_checkpoint = json(&vm);
+    bad_checkpoint["checkpoint"] =
+        serde_json::json!({ "height": 1000, "main": null });
+    assert!(read(bad_checkpoint).is_err());
+}
+
+#[test]
+fn rewind_after_restoring() {
+    let mut builder = ModuleBuilder::default();
+    let mut vm = VirtualMachine::default();
+
+    builder.push_input("let x = 1").unwrap();
+    vm.extend(builder.build()).unwrap();
+
+    let mut failing = builder.clone();
+    failing.push_input("[x, [][0]]").unwrap();
+    assert!(vm.extend(failing.build()).is_err());
+
+    let mut restored = round_trip(&vm);
+    restored.rewind();
+
+    builder.push_input("x + 1").unwrap();
+    restored.extend(builder.build()).unwrap();
+    assert_eq!(restored.last_result(), "2");
+}
@@ -0,0 +1,80 @@
+//! Test stopping a running VM from another thread.
+
+use std::{thread, time::Duration};
+
+use compiler::ModuleBuilder;
+use diagnostic::{DiagnosticCoordinator, InputCoordinator};
+use runtime::{Error, VirtualMachine};
+
+const FOREVER: &str = "let x = 0;\nloop { x = x + 1 }";
+
+#[test]
+fn interrupt_from_another_thread() {
+    let mut vm = VirtualMachine::default();
+    let handle = vm.interrupt_handle();
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.interrupt();
+    });
+
+    let module = compiler::Module::try_from(FOREVER).unwrap();
+    let result = vm.load(module);
+    interrupter.join().unwrap();
+
+    assert!(matches!(result, Err(Error::Interrupted)));
+    assert!(!vm.interrupt_handle().is_interrupted());
+}
+
+#[test]
+fn interrupt_before_running() {
+    let mut vm = VirtualMachine::default();
+    vm.interrupt_handle().interrupt();
+
+    let result = vm.load(compiler::Module::try_from("1").unwrap());
+    assert!(matches!(result, Err(Error::Interrupted)));
+
+    // Once it's stopped, it can carry on.
+    assert!(vm.resume().is_ok());
+    assert_eq!(vm.last_result(), "1");
+}
+
+#[test]
+fn cancel() {
+    let mut vm = VirtualMachine::default();
+    let handle = vm.interrupt_handle();
+    handle.interrupt();
+    handle.cancel();
+
+    assert!(vm.load(compiler::Module::try_from("1").unwrap()).is_ok());
+}
+
+#[test]
+fn stack_trace() {
+    let mut inputs = InputCoordinator::default();
+    let id = inputs.eval_input(FOREVER.into());
+
+    let module = ModuleBuilder::default()
+        .input(FOREVER)
+        .unwrap()
+        .with_id(Some(id))
+        .build();
+
+    let mut vm = VirtualMachine::default();
+    let handle = vm.interrupt_handle();
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.interrupt();
+    });
+
+    let error = vm.load(module).unwrap_err();
+    interrupter.join().unwrap();
+
+    // The trace points into the loop, which is on the second line.
+    let span = vm.last_op_span().expect("debug info for the loop");
+    assert_eq!(span.start().line(), 1);
+
+    let mut diagnostics = DiagnosticCoordinator::default();
+    vm.stack_trace(error, &mut diagnostics);
+}
@@ -0,0 +1,192 @@
+//! Test that resource limits stop programs which go over them.
+
+use compiler::{Module, ModuleBuilder};
+use runtime::{Error, IntoValue, Limits, VirtualMachine};
+
+const FIBONACCI: &str = "\
+let rec fib = (n) => if n < 2 { n } else { fib(n - 1) + fib(n - 2) };
+fib(10)";
+
+fn run(limits: Limits, input: &str) -> (VirtualMachine, runtime::Result<()>) {
+    let mut vm = VirtualMachine::with_limits(limits);
+    let result = vm.load(Module::try_from(input).unwrap());
+    (vm, result)
+}
+
+#[test]
+fn no_limits() {
+    let (vm, result) = run(Limits::default(), FIBONACCI);
+    assert!(result.is_ok());
+    assert_eq!(vm.last_result(), "55");
+}
+
+#[test]
+fn out_of_fuel() {
+    let limits = Limits {
+        fuel: Some(10),
+        ..Limits::default()
+    };
+
+    let (vm, result) = run(limits, FIBONACCI);
+    assert!(matches!(result, Err(Error::OutOfFuel)));
+    assert_eq!(vm.fuel(), Some(0));
+}
+
+#[test]
+fn resume_after_adding_fuel() {
+    let limits = Limits {
+        fuel: Some(0),
+        ..Limits::default()
+    };
+
+    let (mut vm, mut result) = run(limits, FIBONACCI);
+    let mut slices = 0;
+
+    while let Err(Error::OutOfFuel) = result {
+        slices += 1;
+        vm.add_fuel(100);
+        result = vm.resume();
+    }
+
+    assert!(result.is_ok(), "exited with {:?}", result);
+    assert!(slices > 1);
+    assert_eq!(vm.last_result(), "55");
+}
+
+#[test]
+fn fuel_is_not_limited_by_default() {
+    let (mut vm, _) = run(Limits::default(), "1");
+    vm.add_fuel(10);
+    assert_eq!(vm.fuel(), None);
+}
+
+#[test]
+fn nothing_to_resume() {
+    let (mut vm, result) = run(Limits::default(), "1");
+    assert!(result.is_ok());
+    assert!(matches!(vm.resume(), Err(Error::NothingToResume)));
+}
+
+#[test]
+fn stack_limit() {
+    let limits = Limits {
+        stack: Some(8),
+        ..Limits::default()
+    };
+
+    let (_, result) = run(limits, "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]");
+    assert!(matches!(result, Err(Error::StackLimitExceeded)));
+
+    let (vm, result) = run(limits, "[1, 2, 3]");
+    assert!(result.is_ok());
+    assert_eq!(vm.last_result(), "[1, 2, 3]");
+}
+
+#[test]
+fn call_depth_limit() {
+    let limits = Limits {
+        call_stack: Some(5),
+        ..Limits::default()
+    };
+
+    let (vm, result) = run(limits, "let rec f = (n) => f(n + 1); f(0)");
+    assert!(matches!(result, Err(Error::StackOverflow)));
+    assert_eq!(vm.frame_count(), 5);
+}
+
+#[test]
+fn heap_limit() {
+    let limits = Limits {
+        heap: Some(16 * 1024),
+        ..Limits::default()
+    };
+
+    let input = "\
+let rec nest = (n, list) => if n == 0 { list } else { nest(n - 1, [list]) };
+nest(10000, [])";
+
+    let (_, result) = run(limits, input);
+    assert!(matches!(result, Err(Error::OutOfMemory)));
+}
+
+#[test]
+fn list_elements_count() {
+    let limits = Limits {
+        heap: Some(16 * 1024),
+        ..Limits::default()
+    };
+
+    // The list object itself is small, but its elements don't fit.
+    let input = format!("[{}]", vec!["0"; 4096].join(", "));
+
+    let (_, result) = run(limits, &input);
+    assert!(matches!(result, Err(Error::OutOfMemory)));
+}
+
+#[test]
+fn growing_a_list_counts() {
+    let limits = Limits {
+        heap: Some(16 * 1024),
+        ..Limits::default()
+    };
+
+    let mut vm = VirtualMachine::with_limits(limits);
+    let result = vec![0i64; 4096].into_value(&mut vm);
+    assert!(matches!(result, Err(Error::OutOfMemory)));
+
+    // Nothing is left half-counted.
+    vm.force_collect_garbage();
+    let stats = vm.gc_stats();
+    assert_eq!(
+        stats.bytes_allocated - stats.bytes_freed,
+        stats.bytes_in_use
+    );
+    assert!(vec![0i64; 16].into_value(&mut vm).is_ok());
+}
+
+#[test]
+fn garbage_does_not_count() {
+    let limits = Limits {
+        heap: Some(16 * 1024),
+        ..Limits::default()
+    };
+
+    let input = "\
+let rec churn = (n) => if n == 0 { 0 } else { [n, n, n]; churn(n - 1) };
+churn(2000)";
+
+    let (vm, result) = run(limits, input);
+    assert!(result.is_ok(), "exited with {:?}", result);
+    assert_eq!(vm.last_result(), "0");
+}
+
+#[test]
+fn usable_after_out_of_memory() {
+    let limits = Limits {
+        heap: Some(16 * 1024),
+        ..Limits::default()
+    };
+
+    let mut builder = ModuleBuilder::default();
+    let mut vm = VirtualMachine::with_limits(limits);
+
+    builder
+        .push_input("let rec nest = (n, list) => if n == 0 { list } else { nest(n - 1, [list]) }")
+        .unwrap();
+    vm.extend(builder.build()).unwrap();
+
+    let backup = builder.clone();
+    builder.push_input("nest(10000, [])").unwrap();
+    assert!(matches!(
+        vm.extend(builder.build()),
+        Err(Error::OutOfMemory)
+    ));
+    vm.rewind();
+    builder = backup;
+
+    // Everything the failed input allocated is garbage now.
+    builder.push_input("nest(10, [])").unwrap();
+    let result = vm.extend(builder.build());
+    assert!(result.is_ok(), "exited with {:?}", result);
+    assert_eq!(vm.last_result(), "[[[[[[[[[[[]]]]]]]]]]]");
+}
@@ -0,0 +1,164 @@
+//! Test installing Rust functions and types made with `kurt-macros`.
+
+use std::cell::Cell;
+
+use compiler::Module;
+use runtime::{
+    native::Library, Error, FromValue, Local, Result, Value, VirtualMachine,
+};
+
+#[kurt::function]
+fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+#[kurt::function]
+fn greet(name: String, vm: &mut VirtualMachine) -> Result<Value> {
+    vm.string(&format!("hello, {name}"))
+}
+
+#[kurt::function]
+fn fail() -> Result<()> {
+    Err(Error::NumberTooBig)
+}
+
+#[kurt::function]
+fn apply(f: Value, vm: &mut VirtualMachine) -> Result<Value> {
+    vm.call(f, &[])
+}
+
+struct Counter {
+    count: Cell<i64>,
+}
+
+#[kurt::class]
+impl Counter {
+    fn new(start: i64) -> Counter {
+        Counter {
+            count: Cell::new(start),
+        }
+    }
+
+    fn count(&self) -> i64 {
+        self.count.get()
+    }
+
+    fn add(&self, n: i64) -> i64 {
+        self.count.set(self.count.get() + n);
+        self.count.get()
+    }
+}
+
+fn library() -> Library {
+    Library::new("test")
+        .function::<add>()
+        .function::<greet>()
+        .function::<fail>()
+        .function::<apply>()
+        .class::<Counter>()
+}
+
+/// Load `input`, which should leave a closure, and call it with `args`.
+fn call(vm: &mut VirtualMachine, input: &str, args: &[Local]) -> Result<Value> {
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    let f = *vm.stack_frame().last().unwrap();
+    let args: Vec<Value> = args.iter().map(Local::get).collect();
+    vm.call(f, &args)
+}
+
+/// Install the library and call `input` with it, converting the result
+/// before the VM goes away.
+fn run<T: FromValue>(input: &str) -> Result<T> {
+    let mut vm = VirtualMachine::default();
+    let lib = vm.install(&library()).unwrap();
+
+    let scope = vm.handle_scope();
+    let lib = scope.local(lib);
+    Ok(T::from_value(call(&mut vm, input, &[lib])?)?)
+}
+
+#[test]
+fn functions() {
+    assert_eq!(run::<i64>("(lib) => lib[:add](1, 2)").ok(), Some(3));
+
+    let result = run::<String>("(lib) => lib[:greet](\"kurt\")");
+    assert_eq!(result.ok().as_deref(), Some("hello, \"kurt\""));
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(
+        run::<()>("(lib) => lib[:fail]()"),
+        Err(Error::NumberTooBig)
+    ));
+
+    assert!(matches!(
+        run::<()>("(lib) => lib[:add](1)"),
+        Err(Error::InvalidArgCount {
+            found: 1,
+            expected: 2
+        })
+    ));
+
+    assert!(matches!(
+        run::<()>("(lib) => lib[:add](1, 'a')"),
+        Err(Error::Cast(_))
+    ));
+
+    assert!(matches!(
+        run::<()>("(lib) => lib[:nothing]"),
+        Err(Error::UndefinedMember)
+    ));
+}
+
+#[test]
+fn reentry_overflows() {
+    let mut vm = VirtualMachine::default();
+    let lib = vm.install(&library()).unwrap();
+
+    let scope = vm.handle_scope();
+    let lib = scope.local(lib);
+
+    // Each call goes through the host and back, so the Rust stack grows too.
+    let input = "(lib) => { let rec go = () => lib[:apply](go); go() }";
+    let result = call(&mut vm, input, &[lib.clone()]);
+    assert!(matches!(result, Err(Error::StackOverflow)));
+
+    let result = call(&mut vm, "(lib) => lib[:add](1, 2)", &[lib]);
+    assert_eq!(result.ok().and_then(|v| i64::from_value(v).ok()), Some(3));
+}
+
+#[test]
+fn classes() {
+    let input = "(lib) => {
+        let counter = lib[:Counter](10);
+        counter[:add](5);
+        counter[:add](2);
+        counter[:count]()
+    }";
+
+    assert_eq!(run::<i64>(input).ok(), Some(17));
+
+    assert!(matches!(
+        run::<()>("(lib) => lib[:Counter](1)[:missing]"),
+        Err(Error::OperationNotSupported {
+            type_name: "Counter",
+            op_name: "index",
+        })
+    ));
+}
+
+#[test]
+fn found_by_name() {
+    let mut vm = VirtualMachine::default();
+    assert!(vm.library("test").is_none());
+
+    let lib = vm.install(&library()).unwrap();
+    vm.force_collect_garbage();
+    assert_eq!(vm.library("test"), Some(lib));
+
+    let scope = vm.handle_scope();
+    let lib = scope.local(lib);
+    let result = call(&mut vm, "(lib) => lib[:add](2, 3)", &[lib]).unwrap();
+    assert_eq!(i64::from_value(result), Ok(5));
+}
@@ -0,0 +1,111 @@
+//! Test the profiler's counts.
+
+use compiler::Module;
+use runtime::VirtualMachine;
+
+const PROGRAM: &str = "\
+let double = (n) => {
+    n * 2
+};
+let add = (a, b) => a + b;
+add(double(1), double(2))";
+
+fn profile(input: &str) -> runtime::Profile {
+    let mut vm = VirtualMachine::default();
+    vm.start_profiling();
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    vm.stop_profiling().unwrap()
+}
+
+#[test]
+fn not_profiling() {
+    let mut vm = VirtualMachine::default();
+    vm.load(Module::try_from("1").unwrap()).unwrap();
+    assert!(vm.stop_profiling().is_none());
+}
+
+#[test]
+fn counts_every_op() {
+    let module = Module::try_from("1 + 2").unwrap();
+    let op_count = module.functions()[0].code().len() as u64;
+
+    let profile = profile("1 + 2");
+    assert_eq!(profile.total().ops, op_count);
+}
+
+#[test]
+fn functions() {
+    let profile = profile(PROGRAM);
+
+    let ops = |name: &str| {
+        profile
+            .functions()
+            .iter()
+            .find(|f| f.name == name)
+            .map(|f| f.cost.ops)
+    };
+
+    // LoadLocal, U48, Mul, Return, for each of the two calls
+    assert_eq!(ops("double"), Some(8));
+    assert_eq!(ops("add"), Some(4));
+    assert!(ops("main").is_some());
+}
+
+#[test]
+fn lines() {
+    let profile = profile(PROGRAM);
+
+    let line = profile
+        .lines()
+        .iter()
+        .find(|l| l.function == "double" && l.line == Some(2))
+        .unwrap();
+
+    // The `Return` is part of the function's whole body, which starts on the
+    // first line.
+    assert_eq!(line.cost.ops, 6);
+}
+
+#[test]
+fn folded() {
+    let profile = profile(PROGRAM);
+
+    let mut folded = Vec::new();
+    profile.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+
+    let stacks: Vec<&str> = folded
+        .lines()
+        .map(|line| line.rsplit_once(' ').unwrap().0)
+        .collect();
+
+    assert_eq!(stacks, ["main", "main;add", "main;double"]);
+    assert!(folded.contains("main;double 8\n"));
+}
+
+#[test]
+fn reloaded_functions_are_counted_apart() {
+    let mut vm = VirtualMachine::default();
+    vm.start_profiling();
+    vm.load(Module::try_from("let get = () => 1; get").unwrap())
+        .unwrap();
+    let old = *vm.stack_frame().last().unwrap();
+    vm.call(old, &[]).unwrap();
+
+    vm.reload(Module::try_from("let get = () => 1 + 1; get").unwrap())
+        .unwrap();
+    let new = *vm.stack_frame().last().unwrap();
+    vm.call(new, &[]).unwrap();
+
+    let profile = vm.stop_profiling().unwrap();
+    let mut ops: Vec<_> = profile
+        .functions()
+        .iter()
+        .filter(|f| f.name == "get")
+        .map(|f| f.cost.ops)
+        .collect();
+    ops.sort_unstable();
+
+    // U48, Return before reloading, and U48, U48, Add, Return after
+    assert_eq!(ops, [2, 4]);
+}
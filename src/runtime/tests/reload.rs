@@ -0,0 +1,125 @@
+//! Test replacing a module's code while the VM is running.
+
+use compiler::Module;
+use runtime::{Error, FromValue, VirtualMachine};
+
+fn module(input: &str) -> Module {
+    Module::try_from(input).unwrap()
+}
+
+fn call(vm: &mut VirtualMachine, f: runtime::Value) -> i64 {
+    let result = vm.call(f, &[]).unwrap();
+    i64::from_value(result).unwrap()
+}
+
+#[test]
+fn new_calls_use_the_new_code() {
+    let mut vm = VirtualMachine::default();
+    vm.load(module("let n = 2; let get = () => n; get"))
+        .unwrap();
+
+    vm.reload(module("let n = 2; let get = () => n * 10; get"))
+        .unwrap();
+
+    let get = *vm.stack_frame().last().unwrap();
+    assert_eq!(call(&mut vm, get), 20);
+}
+
+#[test]
+fn old_closures_see_migrated_bindings() {
+    let mut vm = VirtualMachine::default();
+    vm.load(module(
+        "let count = 0; let inc = () => { count = count + 1; count }; inc",
+    ))
+    .unwrap();
+
+    let old = vm.global(*vm.stack_frame().last().unwrap());
+    assert_eq!(call(&mut vm, old.get()), 1);
+
+    vm.reload(module(
+        "let count = 100; let inc = () => { count = count + 2; count }; inc",
+    ))
+    .unwrap();
+    vm.force_collect_garbage();
+
+    // The old code, with the new binding.
+    assert_eq!(call(&mut vm, old.get()), 101);
+
+    let new = *vm.stack_frame().last().unwrap();
+    assert_eq!(call(&mut vm, new), 103);
+    assert_eq!(call(&mut vm, old.get()), 104);
+}
+
+#[test]
+fn removed_bindings_keep_their_value() {
+    let mut vm = VirtualMachine::default();
+    vm.load(module("let gone = 7; let get = () => gone; get"))
+        .unwrap();
+    let old = vm.global(*vm.stack_frame().last().unwrap());
+
+    vm.reload(module("let other = [1, 2, 3]; other")).unwrap();
+    vm.force_collect_garbage();
+
+    assert_eq!(call(&mut vm, old.get()), 7);
+    assert_eq!(vm.last_result(), "[1, 2, 3]");
+}
+
+#[test]
+fn old_constants_survive() {
+    let mut vm = VirtualMachine::default();
+    vm.load(module("() => \"old\"")).unwrap();
+    let old = vm.global(*vm.stack_frame().last().unwrap());
+
+    vm.reload(module("() => \"new\"")).unwrap();
+    vm.force_collect_garbage();
+
+    let result = vm.call(old.get(), &[]).unwrap();
+    assert_eq!(result.as_str(), Some("\"old\""));
+
+    let new = *vm.stack_frame().last().unwrap();
+    let result = vm.call(new, &[]).unwrap();
+    assert_eq!(result.as_str(), Some("\"new\""));
+}
+
+#[test]
+fn failed_reloads_change_nothing() {
+    let mut vm = VirtualMachine::default();
+    vm.load(module("let n = 5; let get = () => n; get"))
+        .unwrap();
+    let old = vm.global(*vm.stack_frame().last().unwrap());
+
+    let result = vm.reload(module("let n = 6; let get = () => n; [][1]"));
+    assert!(result.is_err());
+    vm.force_collect_garbage();
+
+    assert_eq!(*vm.stack_frame().last().unwrap(), old.get());
+    assert_eq!(call(&mut vm, old.get()), 5);
+
+    // It can still be reloaded.
+    vm.reload(module("let n = 6; n")).unwrap();
+    assert_eq!(call(&mut vm, old.get()), 6);
+}
+
+#[test]
+fn reload_twice() {
+    let mut vm = VirtualMachine::default();
+    vm.load(module("let n = 1; let get = () => n; get"))
+        .unwrap();
+    let old = vm.global(*vm.stack_frame().last().unwrap());
+
+    vm.reload(module("let n = 2; let get = () => n; get"))
+        .unwrap();
+    vm.reload(module("let n = 3; let get = () => n; get"))
+        .unwrap();
+    vm.force_collect_garbage();
+
+    assert_eq!(call(&mut vm, old.get()), 3);
+    let new = *vm.stack_frame().last().unwrap();
+    assert_eq!(call(&mut vm, new), 3);
+}
+
+#[test]
+fn nothing_to_reload() {
+    let mut vm = VirtualMachine::default();
+    assert!(matches!(vm.reload(module("1")), Err(Error::InvalidReload)));
+}
@@ -0,0 +1,89 @@
+//! Test loading one compiled module into many VMs.
+
+use std::{sync::Arc, thread};
+
+use common::Index;
+use compiler::{Constant, Module};
+use runtime::{classes::Function, VirtualMachine};
+
+fn shared(input: &str) -> Arc<Module> {
+    Arc::new(Module::try_from(input).unwrap())
+}
+
+/// The index of the string constant which is `text`, quotes and all.
+fn string_constant(module: &Module, text: &str) -> Index<Constant> {
+    let i = module
+        .constants()
+        .iter()
+        .position(|c| *c == Constant::String(text.into()))
+        .expect("no such constant");
+
+    Index::new(i as u32)
+}
+
+#[test]
+fn many_vms() {
+    let module = shared("let x = \"hello\"; [x, 1 + 2]");
+    assert!(!module.is_verified());
+
+    let mut vms: Vec<VirtualMachine> = (0..3)
+        .map(|_| {
+            let mut vm = VirtualMachine::default();
+            vm.load(module.clone()).unwrap();
+            vm
+        })
+        .collect();
+
+    for vm in &vms {
+        assert_eq!(vm.last_result(), "[\"hello\", 3]");
+    }
+
+    // Only the first VM needed to check it.
+    assert!(module.is_verified());
+
+    assert_eq!(Arc::strong_count(&module), 4);
+    vms.clear();
+    assert_eq!(Arc::strong_count(&module), 1);
+}
+
+#[test]
+fn across_threads() {
+    let module = shared(
+        "let rec f = (n) => if n == 0 { 1 } else { n * f(n - 1) }; f(5)",
+    );
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let module = module.clone();
+            thread::spawn(move || {
+                let mut vm = VirtualMachine::default();
+                vm.load(module).unwrap();
+                vm.last_result()
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), "120");
+    }
+}
+
+#[test]
+fn constants_are_inflated_when_used() {
+    let module = shared("(x) => if x { \"yes\" } else { \"no\" }");
+    let yes = string_constant(&module, "\"yes\"");
+    let no = string_constant(&module, "\"no\"");
+
+    let mut vm = VirtualMachine::default();
+    vm.load(module).unwrap();
+
+    let f = *vm.stack_frame().last().unwrap();
+    let live = f.as_gc::<Function>().unwrap().module();
+    assert!(live.constant(yes).is_none());
+    assert!(live.constant(no).is_none());
+
+    let result = vm.call(f, &[true.into()]).unwrap();
+    assert_eq!(result.as_str(), Some("\"yes\""));
+    assert_eq!(live.constant(yes), Some(result));
+    assert!(live.constant(no).is_none());
+}
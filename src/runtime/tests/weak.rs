@@ -0,0 +1,115 @@
+//! Test weak references and finalizers.
+
+use std::{cell::RefCell, rc::Rc};
+
+use compiler::Module;
+use runtime::{
+    classes::List, memory::ClassId, GcConfig, Value, VirtualMachine,
+};
+
+/// Run `input`, which must leave a list on the stack, and return the list.
+fn run(vm: &mut VirtualMachine, input: &str) -> Value {
+    vm.load(Module::try_from(input).unwrap()).unwrap();
+    *vm.stack_frame().last().unwrap()
+}
+
+/// Replace `list[i]` with `value`.
+fn set(vm: &mut VirtualMachine, list: Value, i: i32, value: Value) {
+    list.as_gc::<List>()
+        .unwrap()
+        .set_index(Value::int(i.into()), value, vm)
+        .unwrap();
+}
+
+fn get(list: Value, i: i32) -> Value {
+    list.as_gc::<List>()
+        .unwrap()
+        .index(Value::int(i.into()))
+        .unwrap()
+}
+
+#[test]
+fn kept_alive_by_something_else() {
+    let mut vm = VirtualMachine::default();
+    let holder = run(&mut vm, "[0, [1]]");
+
+    let weak = vm.make_weak(get(holder, 1)).unwrap();
+    set(&mut vm, holder, 0, Value::gc(weak));
+
+    vm.force_collect_garbage();
+
+    assert_eq!(format!("{:?}", weak.get().unwrap()), "[1]");
+}
+
+#[test]
+fn cleared_when_collected() {
+    let mut vm = VirtualMachine::default();
+    let holder = run(&mut vm, "[0, [1]]");
+
+    let weak = vm.make_weak(get(holder, 1)).unwrap();
+    set(&mut vm, holder, 0, Value::gc(weak));
+    set(&mut vm, holder, 1, Value::UNIT);
+
+    vm.force_collect_garbage();
+
+    assert!(weak.get().is_none());
+    assert_eq!(vm.last_result(), "[<weak (cleared)>, ()]");
+}
+
+#[test]
+fn cleared_by_minor_collection() {
+    // Under stress a full collection could be part way done, and then the
+    // target would float until the next one.
+    let mut vm = VirtualMachine::with_gc_config(GcConfig {
+        stress: false,
+        ..GcConfig::default()
+    });
+    let holder = run(&mut vm, "[0, 0]");
+
+    // The holder is old, and the target is young.
+    vm.force_collect_garbage();
+    let young = run(&mut vm, "[[1]]");
+    let target = get(young, 0);
+    set(&mut vm, young, 0, Value::UNIT);
+    set(&mut vm, holder, 1, target);
+
+    let weak = vm.make_weak(target).unwrap();
+    set(&mut vm, holder, 0, Value::gc(weak));
+    set(&mut vm, holder, 1, Value::UNIT);
+
+    vm.force_collect_nursery();
+    assert!(weak.get().is_none());
+}
+
+#[test]
+fn values_not_on_the_heap_stay() {
+    let mut vm = VirtualMachine::default();
+    let holder = run(&mut vm, "[0]");
+
+    let weak = vm.make_weak(Value::int(7.into())).unwrap();
+    set(&mut vm, holder, 0, Value::gc(weak));
+
+    vm.force_collect_garbage();
+    assert_eq!(weak.get(), Some(Value::int(7.into())));
+}
+
+#[test]
+fn finalizer() {
+    let mut vm = VirtualMachine::default();
+    let finalized = Rc::new(RefCell::new(Vec::new()));
+
+    let log = finalized.clone();
+    vm.set_finalizer(Some(Box::new(move |object| {
+        log.borrow_mut().push(object.class_id());
+    })));
+
+    let holder = run(&mut vm, "[[1], [2]]");
+    vm.finalize_on_collect(get(holder, 0));
+
+    // Only objects which asked for it are finalized.
+    set(&mut vm, holder, 0, Value::UNIT);
+    set(&mut vm, holder, 1, Value::UNIT);
+    vm.force_collect_garbage();
+
+    assert_eq!(*finalized.borrow(), [ClassId::List]);
+}
@@ -80,6 +80,10 @@ impl Syntax for Unary<'_> {
 pub struct Binary<'a> {
     token: Token<'a>,
     operands: Box<(Expression<'a>, Expression<'a>)>,
+
+    /// Chains of operators can be very long, so this is worked out once up
+    /// front instead of walking the whole chain each time it's needed.
+    span: Span,
 }
 
 impl<'a> Binary<'a> {
@@ -88,9 +92,12 @@ impl<'a> Binary<'a> {
         lhs: Expression<'a>,
         rhs: Expression<'a>,
     ) -> Binary<'a> {
+        let span = lhs.span() + rhs.span();
+
         Binary {
             token,
             operands: Box::new((lhs, rhs)),
+            span,
         }
     }
 
@@ -117,6 +124,6 @@ impl<'a> Binary<'a> {
 
 impl Syntax for Binary<'_> {
     fn span(&self) -> Span {
-        self.left().span() + self.right().span()
+        self.span
     }
 }